@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Chunk-based reader producing normalized output for comparison.
+//!
+//! The reader never buffers a whole line. Instead, runs of trailing whitespace
+//! and newlines are tracked as counters, and only materialized when followed
+//! by other content.
+
+use std::collections::VecDeque;
+use std::io;
+
+use tokio::io::{AsyncBufReadExt as _, AsyncRead, BufReader};
+
+use crate::comparer::Normalization;
+
+/// Default size of a single block, in bytes.
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// UTF-8 encoded byte order mark.
+const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// Max number of pending runs of whitespace, beyond which they are emitted.
+const MAX_PENDING_RUNS: usize = 1024;
+
+/// A reader yielding fixed-size blocks of normalized content.
+///
+/// The normalized content is the original content with:
+///
+/// - a leading BOM removed, if [`Normalization::strip_bom`] is set;
+/// - `\r\n` replaced by `\n`, if [`Normalization::crlf_to_lf`] is set;
+/// - whitespace before a newline or EOF removed, if
+///   `ignore_trailing_whitespace` is set, except for leading groups of
+///   [`MAX_PENDING_RUNS`] runs of the same byte, which are kept so that
+///   alternating whitespace can't grow the pending runs without bound;
+/// - newlines at EOF removed, if `ignore_trailing_newline` is set.
+///
+/// Whitespace refers to spaces (`0x20`), and also tabs and `\r` if
+/// [`Normalization::extended_whitespace`] is set.
+///
+/// Two streams are considered equal by [`DefaultComparer`] if and only if
+/// their normalized contents are equal.
+///
+/// [`DefaultComparer`]: crate::comparer::DefaultComparer
+pub(crate) struct ChunkedReader<R> {
+    reader: BufReader<R>,
+    block: Box<[u8]>,
+    start: usize,
+    end: usize,
+    state: State,
+}
+
+/// Normalization state carried across chunk boundaries.
+struct State {
+    ignore_trailing_whitespace: bool,
+    ignore_trailing_newline: bool,
+    normalization: Normalization,
+
+    /// Newlines read but not yet known to be followed by content.
+    pending_newlines: u64,
+    /// Whitespace read but not yet known to be followed by content, as
+    /// run-length encoded `(byte, count)` pairs.
+    ///
+    /// Pending whitespace always comes after pending newlines.
+    pending_whitespace: VecDeque<(u8, u64)>,
+    /// Whether the pending runs are followed by content and must be emitted.
+    committed: bool,
+
+    /// Number of BOM bytes matched so far, or `None` once past the BOM.
+    bom_matched: Option<usize>,
+    /// A `\r` read but not yet known to be part of a `\r\n`.
+    pending_cr: bool,
+    /// Bytes already consumed from the input, which still need processing.
+    replay: VecDeque<u8>,
+}
+
+impl<R: AsyncRead + Unpin> ChunkedReader<R> {
+    pub(crate) fn new(
+        reader: R,
+        chunk_size: usize,
+        ignore_trailing_whitespace: bool,
+        ignore_trailing_newline: bool,
+        normalization: Normalization,
+    ) -> Self {
+        debug_assert_ne!(chunk_size, 0);
+        Self {
+            reader: BufReader::with_capacity(chunk_size, reader),
+            block: vec![0; chunk_size].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            state: State {
+                ignore_trailing_whitespace,
+                ignore_trailing_newline,
+                normalization,
+                pending_newlines: 0,
+                pending_whitespace: VecDeque::new(),
+                committed: false,
+                bom_matched: normalization.strip_bom.then_some(0),
+                pending_cr: false,
+                replay: VecDeque::new(),
+            },
+        }
+    }
+
+    /// Returns the normalized bytes not yet consumed, reading more from the
+    /// inner reader if none are left.
+    ///
+    /// An empty slice means EOF.
+    pub(crate) async fn fill(&mut self) -> io::Result<&[u8]> {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+
+            while self.end == 0 {
+                if self.state.committed {
+                    self.state.drain(&mut self.block, &mut self.end);
+                    if self.end == self.block.len() {
+                        break;
+                    }
+                }
+
+                let input = self.reader.fill_buf().await?;
+                if input.is_empty() {
+                    self.state.finish();
+                    self.state.normalize(&[], &mut self.block, &mut self.end);
+                    if self.state.replay.is_empty() {
+                        // Anything still pending is trailing, drop it.
+                        self.state.pending_newlines = 0;
+                        self.state.pending_whitespace.clear();
+                        break;
+                    }
+                    continue;
+                }
+
+                let consumed = self.state.normalize(input, &mut self.block, &mut self.end);
+                self.reader.consume(consumed);
+            }
+        }
+
+        Ok(&self.block[self.start..self.end])
+    }
+
+    /// Mark `amount` bytes returned by [`fill`](Self::fill) as consumed.
+    pub(crate) fn consume(&mut self, amount: usize) {
+        self.start = (self.start + amount).min(self.end);
+    }
+}
+
+impl State {
+    /// Write committed newlines and whitespace into `block[*end..]`, as many
+    /// as fit.
+    fn drain(&mut self, block: &mut [u8], end: &mut usize) {
+        let runs = std::iter::once((b'\n', &mut self.pending_newlines)).chain(
+            self.pending_whitespace
+                .iter_mut()
+                .map(|(byte, count)| (*byte, count)),
+        );
+        for (byte, pending) in runs {
+            let room = (block.len() - *end) as u64;
+            let count = (*pending).min(room) as usize;
+            block[*end..*end + count].fill(byte);
+            *end += count;
+            *pending -= count as u64;
+        }
+        while self
+            .pending_whitespace
+            .front()
+            .is_some_and(|&(_, count)| count == 0)
+        {
+            self.pending_whitespace.pop_front();
+        }
+
+        if self.pending_newlines == 0 && self.pending_whitespace.is_empty() {
+            self.committed = false;
+        }
+    }
+
+    /// Queue any partially matched BOM or `\r` for processing at EOF.
+    fn finish(&mut self) {
+        if let Some(matched) = self.bom_matched.take() {
+            self.replay.extend(&BOM[..matched]);
+        }
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.replay.push_back(b'\r');
+        }
+    }
+
+    /// Normalize `input` into `block[*end..]`, returning the number of input
+    /// bytes consumed.
+    ///
+    /// Stops early when the block is full, or when pending runs get committed
+    /// and must be drained before more content is written.
+    fn normalize(&mut self, input: &[u8], block: &mut [u8], end: &mut usize) -> usize {
+        let mut consumed = 0;
+
+        loop {
+            while let Some(&byte) = self.replay.front() {
+                if !self.push(byte, block, end) {
+                    return consumed;
+                }
+                self.replay.pop_front();
+            }
+
+            let Some(&byte) = input.get(consumed) else {
+                return consumed;
+            };
+
+            if let Some(matched) = self.bom_matched {
+                if byte == BOM[matched] {
+                    consumed += 1;
+                    self.bom_matched = (matched + 1 < BOM.len()).then_some(matched + 1);
+                } else {
+                    self.bom_matched = None;
+                    self.replay.extend(&BOM[..matched]);
+                }
+                continue;
+            }
+
+            if self.pending_cr {
+                self.pending_cr = false;
+                if byte != b'\n' {
+                    self.replay.push_back(b'\r');
+                    continue;
+                }
+                // Otherwise the `\r` is dropped, and the `\n` is handled below.
+            } else if byte == b'\r' && self.normalization.crlf_to_lf {
+                self.pending_cr = true;
+                consumed += 1;
+                continue;
+            }
+
+            if !self.push(byte, block, end) {
+                return consumed;
+            }
+            consumed += 1;
+        }
+    }
+
+    /// Push a single byte into `block[*end..]`, or hold it back if it may be
+    /// trailing.
+    ///
+    /// Returns `false` if the byte cannot be accepted for now.
+    fn push(&mut self, byte: u8, block: &mut [u8], end: &mut usize) -> bool {
+        let is_whitespace =
+            byte == b' ' || self.normalization.extended_whitespace && matches!(byte, b'\t' | b'\r');
+
+        match byte {
+            b'\n' if self.ignore_trailing_newline => {
+                // Whitespace before a newline is trailing.
+                self.pending_whitespace.clear();
+                self.pending_newlines += 1;
+            }
+            _ if is_whitespace && self.ignore_trailing_whitespace => {
+                let runs = self.pending_whitespace.len();
+                match self.pending_whitespace.back_mut() {
+                    Some((last, count)) if *last == byte => *count += 1,
+                    _ if runs == MAX_PENDING_RUNS => {
+                        self.committed = true;
+                        return false;
+                    }
+                    _ => self.pending_whitespace.push_back((byte, 1)),
+                }
+            }
+            _ => {
+                if byte == b'\n' {
+                    self.pending_whitespace.clear();
+                }
+                if self.pending_newlines != 0 || !self.pending_whitespace.is_empty() {
+                    self.committed = true;
+                    return false;
+                }
+                if *end == block.len() {
+                    return false;
+                }
+                block[*end] = byte;
+                *end += 1;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{ChunkedReader, MAX_PENDING_RUNS};
+    use crate::comparer::Normalization;
+
+    async fn normalize_with(
+        input: &[u8],
+        chunk_size: usize,
+        ignore_trailing_whitespace: bool,
+        ignore_trailing_newline: bool,
+        normalization: Normalization,
+    ) -> io::Result<Vec<u8>> {
+        let mut reader = ChunkedReader::new(
+            input,
+            chunk_size,
+            ignore_trailing_whitespace,
+            ignore_trailing_newline,
+            normalization,
+        );
+        let mut output = Vec::new();
+        loop {
+            let block = reader.fill().await?;
+            if block.is_empty() {
+                return Ok(output);
+            }
+            assert!(block.len() <= chunk_size);
+            let len = block.len();
+            output.extend_from_slice(block);
+            reader.consume(len);
+        }
+    }
+
+    async fn normalize(
+        input: &[u8],
+        chunk_size: usize,
+        ignore_trailing_whitespace: bool,
+        ignore_trailing_newline: bool,
+    ) -> io::Result<Vec<u8>> {
+        normalize_with(
+            input,
+            chunk_size,
+            ignore_trailing_whitespace,
+            ignore_trailing_newline,
+            Normalization::NONE,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn normalize_across_chunks() -> io::Result<()> {
+        let input = b"a  b   \n\n  \nc \n\n";
+        for chunk_size in [1, 2, 3, 7, 64] {
+            assert_eq!(
+                normalize(input, chunk_size, true, true).await?,
+                b"a  b\n\n\nc"
+            );
+            assert_eq!(
+                normalize(input, chunk_size, true, false).await?,
+                b"a  b\n\n\nc\n\n"
+            );
+            assert_eq!(
+                normalize(input, chunk_size, false, true).await?,
+                b"a  b   \n\n  \nc "
+            );
+            assert_eq!(normalize(input, chunk_size, false, false).await?, input);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn normalize_long_space_run() -> io::Result<()> {
+        let mut input = vec![b' '; 10_000];
+        input.push(b'x');
+        input.extend_from_slice(&[b' '; 10_000]);
+        input.extend_from_slice(&[b'\n'; 10_000]);
+
+        let output = normalize(&input, 16, true, true).await?;
+        assert_eq!(output, &input[..10_001]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn normalize_alternating_whitespace() -> io::Result<()> {
+        // Each byte is a run of its own.
+        let whitespace = b" \t".repeat(MAX_PENDING_RUNS);
+        let input = [&b"a"[..], &whitespace, b"b"].concat();
+        let output = normalize_with(&input, 16, true, true, Normalization::PORTABLE).await?;
+        assert_eq!(output, input);
+
+        // Only the runs beyond the last full group are trailing.
+        let input = [&b"a"[..], &whitespace, b"\n"].concat();
+        let output = normalize_with(&input, 16, true, true, Normalization::PORTABLE).await?;
+        assert_eq!(
+            output,
+            [&b"a"[..], &whitespace[..MAX_PENDING_RUNS]].concat()
+        );
+
+        let input = [&b"a"[..], &whitespace[..MAX_PENDING_RUNS], b"\n"].concat();
+        let output = normalize_with(&input, 16, true, true, Normalization::PORTABLE).await?;
+        assert_eq!(output, b"a");
+
+        let input = [&b"a"[..], &whitespace, &whitespace, b"\n"].concat();
+        let mut reader = ChunkedReader::new(&input[..], 16, true, true, Normalization::PORTABLE);
+        loop {
+            let len = reader.fill().await?.len();
+            assert!(reader.state.pending_whitespace.len() <= MAX_PENDING_RUNS);
+            if len == 0 {
+                break;
+            }
+            reader.consume(len);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn normalize_portable() -> io::Result<()> {
+        let input = b"\xEF\xBB\xBFa\t b \t\r\n\r\r\nc\rd\r";
+        for chunk_size in [1, 2, 3, 64] {
+            assert_eq!(
+                normalize_with(input, chunk_size, true, true, Normalization::PORTABLE).await?,
+                b"a\t b\n\nc\rd"
+            );
+            assert_eq!(
+                normalize_with(input, chunk_size, false, false, Normalization::PORTABLE).await?,
+                b"a\t b \t\n\r\nc\rd\r"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn normalize_partial_bom() -> io::Result<()> {
+        let normalization = Normalization {
+            strip_bom: true,
+            ..Normalization::NONE
+        };
+        for chunk_size in [1, 2, 64] {
+            assert_eq!(
+                normalize_with(b"\xEF\xBBa", chunk_size, true, true, normalization).await?,
+                b"\xEF\xBBa"
+            );
+            assert_eq!(
+                normalize_with(b"\xEF\xBB", chunk_size, true, true, normalization).await?,
+                b"\xEF\xBB"
+            );
+            assert_eq!(
+                normalize_with(b"a\xEF\xBB\xBF", chunk_size, true, true, normalization).await?,
+                b"a\xEF\xBB\xBF"
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! A default comparer implementation, supporting ignoring trailing whitespace
-//! and/or trailing newline.
+//! and/or trailing newline, with optional [`Normalization`].
+//!
+//! Outputs are compared in fixed-size blocks, so memory usage is bounded no
+//! matter how long a single line is.
 
 use std::io;
 
 use futures::try_join;
-use rsjudge_utils::trim_space_end;
-use tokio::io::{AsyncBufReadExt as _, AsyncRead, BufReader};
+use tokio::io::AsyncRead;
 
-use crate::comparer::{CompareResult, Comparer};
+use crate::comparer::chunked::{ChunkedReader, DEFAULT_CHUNK_SIZE};
+use crate::comparer::unicode::UnicodeReader;
+use crate::comparer::{CompareResult, Comparer, Normalization};
 
 /// A default comparer implementation with basic configurations.
 #[must_use = "Comparer makes no sense if it is not used"]
@@ -17,6 +21,7 @@ pub struct DefaultComparer {
     case_sensitive: bool,
     ignore_trailing_whitespace: bool,
     ignore_trailing_newline: bool,
+    normalization: Normalization,
 }
 
 impl DefaultComparer {
@@ -29,9 +34,16 @@ impl DefaultComparer {
             case_sensitive,
             ignore_trailing_whitespace,
             ignore_trailing_newline,
+            normalization: Normalization::NONE,
         }
     }
 
+    /// Set the [`Normalization`] applied before comparing.
+    pub const fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
     pub const fn common() -> Self {
         Self::new(true, true, true)
     }
@@ -40,78 +52,107 @@ impl DefaultComparer {
         Self::new(true, false, false)
     }
 
-    fn compare_line(&self, out_line: &[u8], ans_line: &[u8]) -> bool {
-        let (out, ans) = match (out_line, ans_line) {
-            ([out @ .., b'\n'], [ans @ .., b'\n']) => (out, ans),
-            (out, [ans @ .., b'\n']) | ([out @ .., b'\n'], ans) => {
-                if !self.ignore_trailing_newline {
-                    return false;
-                }
-                (out, ans)
-            }
-            (out, ans) => (out, ans),
-        };
-
-        let (out, ans) = if self.ignore_trailing_whitespace {
-            (trim_space_end(out), trim_space_end(ans))
-        } else {
-            (out, ans)
-        };
-
+    fn compare_block(&self, out: &[u8], ans: &[u8]) -> bool {
         if self.case_sensitive {
             out == ans
         } else {
             out.eq_ignore_ascii_case(ans)
         }
     }
-}
 
-impl Default for DefaultComparer {
-    fn default() -> Self {
-        Self::common()
+    fn reader<R: AsyncRead + Unpin>(&self, reader: R, chunk_size: usize) -> NormalizedReader<R> {
+        let reader = ChunkedReader::new(
+            reader,
+            chunk_size,
+            self.ignore_trailing_whitespace,
+            self.ignore_trailing_newline,
+            self.normalization,
+        );
+
+        let nfc = self.normalization.unicode_nfc;
+        let case_fold = self.normalization.unicode_case_fold && !self.case_sensitive;
+        if nfc || case_fold {
+            NormalizedReader::Unicode(UnicodeReader::new(reader, chunk_size, nfc, case_fold))
+        } else {
+            NormalizedReader::Bytes(reader)
+        }
     }
-}
 
-impl Comparer for DefaultComparer {
-    async fn compare<Out, Ans>(&self, out: Out, ans: Ans) -> io::Result<CompareResult>
+    async fn compare_chunked<Out, Ans>(
+        &self,
+        out: Out,
+        ans: Ans,
+        chunk_size: usize,
+    ) -> io::Result<CompareResult>
     where
         Out: AsyncRead + Send + Unpin,
         Ans: AsyncRead + Send + Unpin,
     {
-        let mut out = BufReader::new(out);
-        let mut ans = BufReader::new(ans);
-
-        let mut out_buf = Vec::new();
-        let mut ans_buf = Vec::new();
+        let mut out = self.reader(out, chunk_size);
+        let mut ans = self.reader(ans, chunk_size);
 
         loop {
-            // The "line" read by `read_until` includes the delimiter,
-            // i.e., the `b'\n'` byte.
-            // This is important since a trailing newline needs to be detected
-            // for an exact match.
+            // Both readers yield normalized content, so trailing whitespace
+            // and newlines split across blocks are already taken care of.
             //
-            // Read beyond the EOF will always return `Ok(0)` with an empty buffer.
-            // So any trailing content is compared with an empty buffer,
+            // Reading beyond the EOF will always return an empty block.
+            // So any trailing content is compared with an empty block,
             // leading to `CompareResult::WrongAnswer`.
-            let (out_len, ans_len) = try_join!(
-                out.read_until(b'\n', &mut out_buf),
-                ans.read_until(b'\n', &mut ans_buf),
-            )?;
+            let (out_block, ans_block) = try_join!(out.fill(), ans.fill())?;
 
-            if out_len == 0 && ans_len == 0 {
+            if out_block.is_empty() && ans_block.is_empty() {
                 return Ok(CompareResult::Accepted);
             }
 
-            if self.compare_line(&out_buf, &ans_buf) {
-                out_buf.clear();
-                ans_buf.clear();
-            } else {
+            let len = out_block.len().min(ans_block.len());
+            if len == 0 || !self.compare_block(&out_block[..len], &ans_block[..len]) {
                 return Ok(CompareResult::WrongAnswer);
             }
+
+            out.consume(len);
+            ans.consume(len);
         }
     }
 }
 
+/// Either a byte-level or a Unicode aware normalizing reader.
+enum NormalizedReader<R> {
+    Bytes(ChunkedReader<R>),
+    Unicode(UnicodeReader<R>),
+}
+
+impl<R: AsyncRead + Unpin> NormalizedReader<R> {
+    async fn fill(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Self::Bytes(reader) => reader.fill().await,
+            Self::Unicode(reader) => reader.fill().await,
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            Self::Bytes(reader) => reader.consume(amount),
+            Self::Unicode(reader) => reader.consume(amount),
+        }
+    }
+}
+
+impl Default for DefaultComparer {
+    fn default() -> Self {
+        Self::common()
+    }
+}
+
+impl Comparer for DefaultComparer {
+    async fn compare<Out, Ans>(&self, out: Out, ans: Ans) -> io::Result<CompareResult>
+    where
+        Out: AsyncRead + Send + Unpin,
+        Ans: AsyncRead + Send + Unpin,
+    {
+        self.compare_chunked(out, ans, DEFAULT_CHUNK_SIZE).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io;
@@ -120,7 +161,7 @@ mod tests {
     use tokio::fs::File;
     use tokio::io::{AsyncWriteExt as _, empty};
 
-    use crate::comparer::{CompareResult, Comparer as _, DefaultComparer};
+    use crate::comparer::{CompareResult, Comparer as _, DefaultComparer, Normalization};
 
     #[tokio::test]
     async fn compare_empty() -> io::Result<()> {
@@ -235,4 +276,84 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn compare_across_chunk_boundaries() -> io::Result<()> {
+        let out = b"1 2 3    \n4 5 6\n\n\n";
+        let ans = b"1 2 3\n4 5 6 ";
+        for chunk_size in [1, 2, 3, 5, 8] {
+            let common_comparer = DefaultComparer::common();
+            let common_result = common_comparer
+                .compare_chunked(&out[..], &ans[..], chunk_size)
+                .await?;
+            assert_eq!(common_result, CompareResult::Accepted);
+            let exact_comparer = DefaultComparer::exact_match();
+            let exact_result = exact_comparer
+                .compare_chunked(&out[..], &ans[..], chunk_size)
+                .await?;
+            assert_eq!(exact_result, CompareResult::WrongAnswer);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_long_single_line() -> io::Result<()> {
+        let line: Vec<u8> = (0..1_000_000)
+            .flat_map(|i: u32| format!("{i} ").into_bytes())
+            .collect();
+        let mut wrong = line.clone();
+        let middle = wrong.len() / 2;
+        wrong[middle] = b'x';
+
+        let comparer = DefaultComparer::common();
+        let result = comparer.compare(&line[..], &line[..line.len() - 1]).await?;
+        assert_eq!(result, CompareResult::Accepted);
+        let result = comparer.compare(&wrong[..], &line[..]).await?;
+        assert_eq!(result, CompareResult::WrongAnswer);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_with_normalization() -> io::Result<()> {
+        let out = b"\xEF\xBB\xBF1 2\t\r\n3\r\n";
+        let ans = b"1 2\n3\n";
+
+        let common_comparer = DefaultComparer::common();
+        let common_result = common_comparer.compare(&out[..], &ans[..]).await?;
+        assert_eq!(common_result, CompareResult::WrongAnswer);
+
+        let portable_comparer =
+            DefaultComparer::common().with_normalization(Normalization::PORTABLE);
+        let portable_result = portable_comparer.compare(&out[..], &ans[..]).await?;
+        assert_eq!(portable_result, CompareResult::Accepted);
+
+        // A lone `\r` is not a line ending.
+        let lone_cr_result = portable_comparer
+            .compare(&b"1\r2"[..], &b"1\n2"[..])
+            .await?;
+        assert_eq!(lone_cr_result, CompareResult::WrongAnswer);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_with_unicode_normalization() -> io::Result<()> {
+        let out = "STRASSE Cafe\u{301}".as_bytes();
+        let ans = "straße café".as_bytes();
+
+        let ascii_comparer = DefaultComparer::new(false, true, true);
+        let ascii_result = ascii_comparer.compare(out, ans).await?;
+        assert_eq!(ascii_result, CompareResult::WrongAnswer);
+
+        let unicode_comparer =
+            DefaultComparer::new(false, true, true).with_normalization(Normalization {
+                unicode_nfc: true,
+                unicode_case_fold: true,
+                ..Normalization::NONE
+            });
+        let unicode_result = unicode_comparer.compare(out, ans).await?;
+        assert_eq!(unicode_result, CompareResult::Accepted);
+
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
+mod chunked;
 mod default_comparer;
+mod unicode;
 
 use std::future::Future;
 use std::io;
 
+use serde::{Deserialize, Serialize};
 use tokio::io::AsyncRead;
 
 pub use self::default_comparer::DefaultComparer;
@@ -26,3 +29,42 @@ pub trait Comparer {
         Out: AsyncRead + Send + Unpin,
         Ans: AsyncRead + Send + Unpin;
 }
+
+/// Normalization applied to both the output and the answer before comparing.
+///
+/// All options are disabled by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Normalization {
+    /// Treat `\r\n` as `\n`.
+    pub crlf_to_lf: bool,
+    /// Strip the UTF-8 byte order mark at the start of the content.
+    pub strip_bom: bool,
+    /// Treat tabs and `\r` as trailing whitespace, in addition to spaces.
+    pub extended_whitespace: bool,
+    /// Apply Unicode Normalization Form C.
+    pub unicode_nfc: bool,
+    /// Use Unicode case folding instead of ASCII case insensitivity, when the
+    /// comparison is case insensitive.
+    pub unicode_case_fold: bool,
+}
+
+impl Normalization {
+    /// No normalization at all.
+    pub const NONE: Self = Self {
+        crlf_to_lf: false,
+        strip_bom: false,
+        extended_whitespace: false,
+        unicode_nfc: false,
+        unicode_case_fold: false,
+    };
+
+    /// Normalization smoothing out differences between platforms, i.e. line
+    /// endings, BOM and trailing tabs or `\r`.
+    pub const PORTABLE: Self = Self {
+        crlf_to_lf: true,
+        strip_bom: true,
+        extended_whitespace: true,
+        ..Self::NONE
+    };
+}
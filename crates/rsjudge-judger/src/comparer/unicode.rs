@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Unicode aware normalization on top of [`ChunkedReader`].
+//!
+//! NFC and case folding may change the length of the content, and may need to
+//! look at several code points at once. Blocks are therefore only transformed
+//! up to a safe boundary, i.e. before an ASCII byte, and the rest is carried
+//! over to the next block.
+
+use std::io;
+
+use caseless::Caseless as _;
+use tokio::io::AsyncRead;
+use unicode_normalization::UnicodeNormalization as _;
+use unicode_normalization::char::canonical_combining_class;
+
+use crate::comparer::chunked::ChunkedReader;
+
+/// Carry size beyond which a split is forced even without a starter.
+///
+/// Stream-safe text (UAX #15) has at most 30 consecutive non-starters, which
+/// take at most 4 bytes each.
+const MAX_NON_STARTER_BYTES: usize = 30 * 4;
+
+/// A reader yielding blocks of NFC normalized and/or case folded content.
+///
+/// Invalid UTF-8 sequences are kept as is.
+pub(crate) struct UnicodeReader<R> {
+    inner: ChunkedReader<R>,
+    nfc: bool,
+    case_fold: bool,
+
+    /// Content read from `inner`, but not yet transformed.
+    carry: Vec<u8>,
+    /// Carry size beyond which a non-ASCII boundary is used as well.
+    carry_limit: usize,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: AsyncRead + Unpin> UnicodeReader<R> {
+    pub(crate) fn new(
+        inner: ChunkedReader<R>,
+        chunk_size: usize,
+        nfc: bool,
+        case_fold: bool,
+    ) -> Self {
+        Self {
+            inner,
+            nfc,
+            case_fold,
+            carry: Vec::new(),
+            carry_limit: chunk_size,
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the transformed bytes not yet consumed, reading more from the
+    /// inner reader if none are left.
+    ///
+    /// An empty slice means EOF.
+    pub(crate) async fn fill(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.output.len() {
+            self.pos = 0;
+            self.output.clear();
+
+            while self.output.is_empty() {
+                let block = self.inner.fill().await?;
+                if block.is_empty() {
+                    transform(&self.carry, self.nfc, self.case_fold, &mut self.output);
+                    self.carry.clear();
+                    break;
+                }
+
+                let len = block.len();
+                self.carry.extend_from_slice(block);
+                self.inner.consume(len);
+
+                let split = split_point(&self.carry, self.carry_limit);
+                transform(
+                    &self.carry[..split],
+                    self.nfc,
+                    self.case_fold,
+                    &mut self.output,
+                );
+                self.carry.drain(..split);
+            }
+        }
+
+        Ok(&self.output[self.pos..])
+    }
+
+    /// Mark `amount` bytes returned by [`fill`](Self::fill) as consumed.
+    pub(crate) fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.output.len());
+    }
+}
+
+/// Find the last position in `carry` where it can be split without affecting
+/// the transformation.
+///
+/// Returns `0` if no such position is found, unless `carry` is longer than
+/// both `carry_limit` and [`MAX_NON_STARTER_BYTES`]. Such a carry, e.g. a run
+/// of combining marks, is split at the last code point boundary instead, which
+/// may affect the transformation of text that is not stream-safe anyway, but
+/// bounds the carry.
+fn split_point(carry: &[u8], carry_limit: usize) -> usize {
+    // ASCII characters never compose with preceding characters.
+    let ascii = carry.iter().rposition(u8::is_ascii).unwrap_or(0);
+    if ascii != 0 || carry.len() <= carry_limit {
+        return ascii;
+    }
+
+    // No ASCII in a long run, split before the last starter instead.
+    let mut split = 0;
+    let mut offset = 0;
+    for chunk in carry.utf8_chunks() {
+        for (index, c) in chunk.valid().char_indices() {
+            if canonical_combining_class(c) == 0 {
+                split = offset + index;
+            }
+        }
+        offset += chunk.valid().len();
+        // Invalid bytes at the end may be an incomplete code point.
+        if !chunk.invalid().is_empty() && offset + chunk.invalid().len() < carry.len() {
+            split = offset;
+        }
+        offset += chunk.invalid().len();
+    }
+    if split != 0 || carry.len() <= MAX_NON_STARTER_BYTES {
+        return split;
+    }
+
+    // Continuation bytes never start a code point.
+    carry
+        .iter()
+        .rposition(|&byte| byte & 0xC0 != 0x80)
+        .filter(|&boundary| boundary != 0)
+        .unwrap_or(carry.len())
+}
+
+/// Transform `bytes` and append the result to `output`.
+fn transform(bytes: &[u8], nfc: bool, case_fold: bool, output: &mut Vec<u8>) {
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        let mut push = |c: char| output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        match (nfc, case_fold) {
+            // Case folding may denormalize the content, so normalize again.
+            (true, true) => valid.nfc().default_case_fold().nfc().for_each(push),
+            (true, false) => valid.nfc().for_each(push),
+            (false, true) => valid.chars().default_case_fold().for_each(&mut push),
+            (false, false) => output.extend_from_slice(valid.as_bytes()),
+        }
+        output.extend_from_slice(chunk.invalid());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{MAX_NON_STARTER_BYTES, UnicodeReader};
+    use crate::comparer::Normalization;
+    use crate::comparer::chunked::ChunkedReader;
+
+    async fn transform(
+        input: &[u8],
+        chunk_size: usize,
+        nfc: bool,
+        case_fold: bool,
+    ) -> io::Result<Vec<u8>> {
+        let inner = ChunkedReader::new(input, chunk_size, false, false, Normalization::NONE);
+        let mut reader = UnicodeReader::new(inner, chunk_size, nfc, case_fold);
+        let mut output = Vec::new();
+        loop {
+            let block = reader.fill().await?;
+            if block.is_empty() {
+                return Ok(output);
+            }
+            let len = block.len();
+            output.extend_from_slice(block);
+            reader.consume(len);
+        }
+    }
+
+    #[tokio::test]
+    async fn nfc_across_chunks() -> io::Result<()> {
+        let input = "Cafe\u{301} \u{212B}\u{301}".as_bytes();
+        for chunk_size in [1, 2, 3, 64] {
+            assert_eq!(
+                transform(input, chunk_size, true, false).await?,
+                "Caf\u{E9} \u{1FA}".as_bytes()
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn case_fold_across_chunks() -> io::Result<()> {
+        let input = "Straße ΣΑΣ \u{FF}".as_bytes();
+        for chunk_size in [1, 2, 5, 64] {
+            assert_eq!(
+                transform(input, chunk_size, false, true).await?,
+                "strasse σασ \u{FF}".as_bytes()
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn split_long_combining_run() -> io::Result<()> {
+        let input = format!("a{}", "\u{301}".repeat(1000));
+        let inner = ChunkedReader::new(input.as_bytes(), 16, false, false, Normalization::NONE);
+        let mut reader = UnicodeReader::new(inner, 16, true, true);
+        let mut output = Vec::new();
+        loop {
+            let block = reader.fill().await?;
+            if block.is_empty() {
+                break;
+            }
+            let len = block.len();
+            output.extend_from_slice(block);
+            reader.consume(len);
+            assert!(reader.carry.len() <= MAX_NON_STARTER_BYTES + 16);
+        }
+        assert_eq!(
+            output,
+            format!("\u{e1}{}", "\u{301}".repeat(999)).as_bytes()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_invalid_utf8() -> io::Result<()> {
+        let input = b"A\xFF\xC3B";
+        assert_eq!(transform(input, 2, true, true).await?, b"a\xFF\xC3b");
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Content-addressed cache of compilation results.
+//!
+//! Each entry is a directory named after its [`CacheKey`], containing:
+//!
+//! - `status`: the raw wait status of the compiler;
+//! - `stdout` and `stderr`: the compiler output, replayed on hit;
+//! - `artifacts/`: the compiled artifacts, relative to the work directory.
+//!
+//! Only successful and failed compilations are cached, as timeouts and
+//! oversized artifacts may depend on the load of the judger.
+
+use std::fmt::{self, Display};
+use std::io;
+use std::os::unix::process::ExitStatusExt as _;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use lru::LruCache;
+use rsjudge_traits::language::option::{ConfigValue, LanguageOption};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::fs;
+
+use crate::judger::compile::{CompileInfo, CompileStatus};
+
+/// Configuration of the compile cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Whether the cache is enabled.
+    pub enabled: bool,
+    /// Directory to store cache entries in.
+    pub dir: PathBuf,
+    /// Max total size of cache entries **in MiB**.
+    pub size: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from("/var/cache/rsjudge/compile"),
+            size: 4096,
+        }
+    }
+}
+
+/// Key of a compilation result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Create a key from everything affecting the compilation result.
+    ///
+    /// `template` is the compile command template, `version` is the resolved
+    /// toolchain version, and `extra` identifies other files compiled along
+    /// with the code, like [`InstalledGrader::identity`], or is empty.
+    ///
+    /// [`InstalledGrader::identity`]: crate::judger::grader::InstalledGrader::identity
+    #[must_use]
+    pub fn new(
+        code: &[u8],
+        option: &LanguageOption,
+        template: &str,
+        version: &str,
+        extra: &[u8],
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        let mut field = |bytes: &[u8]| {
+            // Length prefix, so that fields can't be shifted into each other.
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+
+        field(code);
+        field(option.name.as_bytes());
+        let mut config: Vec<_> = option.config.iter().collect();
+        config.sort_unstable_by_key(|&(name, _)| name);
+        for (name, value) in config {
+            field(name.as_bytes());
+            match value {
+                ConfigValue::Bool(value) => field(&[0, u8::from(*value)]),
+                ConfigValue::Enum(value) => field(&[&[1], value.as_bytes()].concat()),
+            }
+        }
+        field(template.as_bytes());
+        field(version.as_bytes());
+        field(extra);
+
+        Self(hasher.finalize().into())
+    }
+}
+
+impl Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// A compile cache with a size budget and LRU eviction.
+#[derive(Debug)]
+pub struct CompileCache {
+    dir: PathBuf,
+    budget: u64,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug)]
+struct Entries {
+    /// Size of each entry **in bytes**.
+    lru: LruCache<CacheKey, u64>,
+    total: u64,
+}
+
+impl CompileCache {
+    /// Open the cache described by `config`.
+    ///
+    /// Returns `None` if the cache is disabled. Existing entries are kept,
+    /// with the least recently modified ones evicted first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be created or read.
+    pub async fn open(config: &CacheConfig) -> io::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        fs::create_dir_all(&config.dir).await?;
+
+        let mut existing = Vec::new();
+        let mut dir = fs::read_dir(&config.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let Some(key) = entry.file_name().to_str().and_then(parse_key) else {
+                // Unfinished entries, or unrelated files.
+                continue;
+            };
+            let modified = entry.metadata().await?.modified()?;
+            existing.push((modified, key, dir_size(&entry.path()).await?));
+        }
+        existing.sort_unstable_by_key(|&(modified, ..)| modified);
+
+        let cache = Self {
+            dir: config.dir.clone(),
+            budget: config.size << 20,
+            entries: Mutex::new(Entries {
+                lru: LruCache::unbounded(),
+                total: 0,
+            }),
+        };
+        for (_, key, size) in existing {
+            cache.insert(key, size).await?;
+        }
+
+        Ok(Some(cache))
+    }
+
+    /// Look up `key`, copying the cached artifacts into `work_dir` on hit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cached entry cannot be read.
+    pub async fn get(&self, key: CacheKey, work_dir: &Path) -> io::Result<Option<CompileInfo>> {
+        if self.lock().lru.get(&key).is_none() {
+            return Ok(None);
+        }
+
+        let entry = self.dir.join(key.to_string());
+        let status = match fs::read_to_string(entry.join("status")).await {
+            Ok(status) => status,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                // Removed behind our back.
+                self.remove(key);
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+        let status = ExitStatus::from_raw(status.trim().parse().map_err(io::Error::other)?);
+
+        match copy_dir(&entry.join("artifacts"), work_dir).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                self.remove(key);
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(Some(CompileInfo {
+            status: if status.success() {
+                CompileStatus::Success
+            } else {
+                CompileStatus::Failed(status)
+            },
+            stdout: fs::read(entry.join("stdout")).await?,
+            stderr: fs::read(entry.join("stderr")).await?,
+        }))
+    }
+
+    /// Store `info` and `artifacts` relative to `work_dir` under `key`.
+    ///
+    /// Compilations timed out or producing oversized artifacts are not stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry cannot be written.
+    pub async fn put(
+        &self,
+        key: CacheKey,
+        info: &CompileInfo,
+        work_dir: &Path,
+        artifacts: &[&Path],
+    ) -> io::Result<()> {
+        let status = match info.status {
+            CompileStatus::Success => ExitStatus::default(),
+            CompileStatus::Failed(status) => status,
+            CompileStatus::TimeLimitExceeded(_) | CompileStatus::ArtifactTooLarge(_) => {
+                return Ok(());
+            }
+        };
+
+        // Write to a temporary directory first, so that no partial entry is
+        // visible.
+        static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+        let temp = self.dir.join(format!(
+            ".{key}.{}.{}",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = async {
+            fs::create_dir_all(temp.join("artifacts")).await?;
+            fs::write(temp.join("status"), status.into_raw().to_string()).await?;
+            fs::write(temp.join("stdout"), &info.stdout).await?;
+            fs::write(temp.join("stderr"), &info.stderr).await?;
+            // Failed compilations may leave no or broken artifacts.
+            for artifact in artifacts.iter().filter(|_| status.success()) {
+                let target = temp.join("artifacts").join(artifact);
+                copy(&work_dir.join(artifact), &target).await?;
+            }
+            dir_size(&temp).await
+        }
+        .await;
+
+        let entry = self.dir.join(key.to_string());
+        match result {
+            Ok(size) if size <= self.budget && fs::rename(&temp, &entry).await.is_ok() => {
+                self.insert(key, size).await
+            }
+            // Too large, or already stored by another compilation.
+            Ok(_) => fs::remove_dir_all(&temp).await,
+            Err(error) => {
+                let _ = fs::remove_dir_all(&temp).await;
+                Err(error)
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Record an entry of `size`, evicting the least recently used entries
+    /// beyond the budget.
+    async fn insert(&self, key: CacheKey, size: u64) -> io::Result<()> {
+        let evicted = {
+            let mut entries = self.lock();
+            if let Some(old) = entries.lru.put(key, size) {
+                entries.total -= old;
+            }
+            entries.total += size;
+
+            let mut evicted = Vec::new();
+            while entries.total > self.budget {
+                let Some((key, size)) = entries.lru.pop_lru() else {
+                    break;
+                };
+                entries.total -= size;
+                evicted.push(key);
+            }
+            evicted
+        };
+
+        for key in evicted {
+            fs::remove_dir_all(self.dir.join(key.to_string())).await?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: CacheKey) {
+        let mut entries = self.lock();
+        if let Some(size) = entries.lru.pop(&key) {
+            entries.total -= size;
+        }
+    }
+}
+
+fn parse_key(name: &str) -> Option<CacheKey> {
+    if name.len() != 64 {
+        return None;
+    }
+    let mut key = [0; 32];
+    for (byte, hex) in key.iter_mut().zip(name.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+    Some(CacheKey(key))
+}
+
+/// Copy a file or directory from `source` to `target`.
+async fn copy(source: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if fs::metadata(source).await?.is_dir() {
+        copy_dir(source, target).await
+    } else {
+        fs::copy(source, target).await.map(drop)
+    }
+}
+
+/// Copy the content of directory `source` into `target`.
+async fn copy_dir(source: &Path, target: &Path) -> io::Result<()> {
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        fs::create_dir_all(target.join(&relative)).await?;
+        let mut dir = fs::read_dir(source.join(&relative)).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let relative = relative.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(relative);
+            } else {
+                fs::copy(source.join(&relative), target.join(&relative)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Get the total size of files in `dir`.
+async fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use rsjudge_traits::language::config::CompileLimits;
+    use rsjudge_traits::language::option::{ConfigValue, LanguageOption};
+    use tokio::fs;
+    use tokio::process::Command;
+
+    use super::{CacheConfig, CacheKey, CompileCache};
+    use crate::judger::compile::compile;
+
+    fn option(o2: bool) -> LanguageOption {
+        LanguageOption {
+            name: "C".into(),
+            config: HashMap::from([
+                ("O2".into(), ConfigValue::Bool(o2)),
+                ("version".into(), ConfigValue::Enum("C99".into())),
+            ]),
+        }
+    }
+
+    #[test]
+    fn key_depends_on_inputs() {
+        let key = CacheKey::new(b"int main() {}", &option(true), "gcc", "13", b"");
+        assert_eq!(
+            key,
+            CacheKey::new(b"int main() {}", &option(true), "gcc", "13", b"")
+        );
+        assert_ne!(
+            key,
+            CacheKey::new(b"int main() {}", &option(false), "gcc", "13", b"")
+        );
+        assert_ne!(
+            key,
+            CacheKey::new(b"int main() {}", &option(true), "gcc", "14", b"")
+        );
+        assert_ne!(key, CacheKey::new(b"", &option(true), "gcc", "13", b""));
+        assert_ne!(
+            key,
+            CacheKey::new(b"int main() {}", &option(true), "gcc", "13", b"grader")
+        );
+        assert_eq!(super::parse_key(&key.to_string()), Some(key));
+    }
+
+    #[tokio::test]
+    async fn replay_and_evict() -> anyhow::Result<()> {
+        let cache_dir = tempfile::tempdir()?;
+        let config = CacheConfig {
+            enabled: true,
+            dir: cache_dir.path().to_path_buf(),
+            size: 1,
+        };
+        let cache = CompileCache::open(&config).await?.unwrap();
+
+        let work_dir = tempfile::tempdir()?;
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo warning >&2; head -c 600000 /dev/zero > out")
+            .current_dir(work_dir.path());
+        let info = compile(command, &CompileLimits::default(), &[]).await?;
+        assert!(info.is_success());
+
+        let first = CacheKey::new(b"first", &option(true), "gcc", "13", b"");
+        let second = CacheKey::new(b"second", &option(true), "gcc", "13", b"");
+        cache
+            .put(first, &info, work_dir.path(), &[Path::new("out")])
+            .await?;
+
+        let replay_dir = tempfile::tempdir()?;
+        let replayed = cache.get(first, replay_dir.path()).await?.unwrap();
+        assert_eq!(replayed.status(), info.status());
+        assert_eq!(replayed.stderr(), b"warning\n");
+        assert_eq!(fs::read(replay_dir.path().join("out")).await?.len(), 600000);
+
+        // Both entries don't fit in 1 MiB, so the first one is evicted.
+        cache
+            .put(second, &info, work_dir.path(), &[Path::new("out")])
+            .await?;
+        assert!(cache.get(first, replay_dir.path()).await?.is_none());
+        assert!(cache.get(second, replay_dir.path()).await?.is_some());
+
+        // Entries are kept across restarts.
+        drop(cache);
+        let cache = CompileCache::open(&config).await?.unwrap();
+        assert!(cache.get(second, replay_dir.path()).await?.is_some());
+
+        // Entries missing artifacts are misses.
+        fs::remove_dir_all(cache_dir.path().join(second.to_string()).join("artifacts")).await?;
+        assert!(cache.get(second, replay_dir.path()).await?.is_none());
+
+        let disabled = CacheConfig {
+            enabled: false,
+            ..config
+        };
+        assert!(CompileCache::open(&disabled).await?.is_none());
+        Ok(())
+    }
+}
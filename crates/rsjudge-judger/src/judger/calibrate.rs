@@ -0,0 +1,495 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Time limit calibration for problem setters.
+//!
+//! Reference solutions run on all cases of a problem several times, to get
+//! stable timings. Time limits are then suggested per language, by scaling the
+//! slowest CPU time of the references with a [`TimeLimitRule`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::time::Duration;
+
+use rsjudge_traits::judger::JudgeResult;
+use rsjudge_traits::language::config::deserialize_factor;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+use tokio::fs::File;
+
+use crate::comparer::{CompareResult, Comparer as _};
+use crate::judger::file_io::OutputError;
+use crate::judger::request::cases::{CasesConfig, JudgeType};
+use crate::judger::run::Program;
+
+/// Error calibrating time limits.
+///
+/// Cases are numbered from 1.
+#[derive(Debug, Error)]
+pub enum CalibrateError {
+    #[error("Reference `{reference}` failed on case {case} with {result:?}")]
+    ReferenceFailed {
+        reference: String,
+        case: u32,
+        result: JudgeResult,
+    },
+    #[error("Checker of the problem is not given")]
+    MissingChecker,
+    #[error("Interactive problems cannot be calibrated")]
+    Interactive,
+    #[error("Checker failed on case {case} of reference `{reference}`: {message}")]
+    CheckerFailed {
+        reference: String,
+        case: u32,
+        message: String,
+    },
+    #[error(transparent)]
+    Output(#[from] OutputError),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Configuration of a calibration.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrateConfig {
+    /// Runs of each reference on each case.
+    #[serde(default = "default_runs")]
+    pub(crate) runs: NonZeroU32,
+    #[serde(default)]
+    pub(crate) rule: TimeLimitRule,
+}
+
+fn default_runs() -> NonZeroU32 {
+    NonZeroU32::new(3).unwrap()
+}
+
+/// Rule suggesting the time limit of a language from the slowest CPU time of
+/// its references.
+///
+/// Defaults to twice the slowest time, rounded up to 100 ms. Multipliers must
+/// be finite and positive.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeLimitRule {
+    /// Multiplier of the slowest CPU time.
+    #[serde(deserialize_with = "factor")]
+    pub(crate) factor: f64,
+    /// Multipliers overriding `factor`, keyed by language name.
+    #[serde(
+        deserialize_with = "factors",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub(crate) languages: HashMap<String, f64>,
+    /// Granularity of suggested limits **in milliseconds**.
+    pub(crate) round: NonZeroU32,
+}
+
+impl Default for TimeLimitRule {
+    fn default() -> Self {
+        Self {
+            factor: 2.0,
+            languages: HashMap::new(),
+            round: NonZeroU32::new(100).unwrap(),
+        }
+    }
+}
+
+impl TimeLimitRule {
+    /// Suggest the time limit of `language` from its `slowest` CPU time,
+    /// saturating at the max.
+    pub fn suggest(&self, language: &str, slowest: Duration) -> Duration {
+        let factor = self.languages.get(language).copied().unwrap_or(self.factor);
+        let round = u128::from(self.round.get());
+        let scaled =
+            Duration::try_from_secs_f64(slowest.as_secs_f64() * factor).unwrap_or(Duration::MAX);
+        let limit = scaled.as_millis().div_ceil(round) * round;
+        Duration::from_millis(u64::try_from(limit.max(round)).unwrap_or(u64::MAX))
+    }
+}
+
+fn factor<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    deserialize_factor(deserializer)?.ok_or_else(|| D::Error::custom("missing factor"))
+}
+
+fn factors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, f64>, D::Error> {
+    let factors = HashMap::<String, f64>::deserialize(deserializer)?;
+    match factors
+        .iter()
+        .find(|&(_, factor)| !(factor.is_finite() && *factor > 0.0))
+    {
+        Some((language, factor)) => Err(D::Error::custom(format!(
+            "invalid factor {factor} of `{language}`, expected a finite positive number"
+        ))),
+        None => Ok(factors),
+    }
+}
+
+/// A compiled reference solution.
+#[derive(Debug, Clone, Copy)]
+pub struct Reference<'a> {
+    /// Name of the reference, e.g. its file name.
+    pub name: &'a str,
+    pub language: &'a str,
+    pub program: &'a Program,
+}
+
+/// Timings of a reference on a case, over all runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaseTiming {
+    /// ID of the case, starting from 1.
+    pub case: u32,
+    pub max_cpu_time: Duration,
+    pub median_cpu_time: Duration,
+    pub max_wall_time: Duration,
+    pub median_wall_time: Duration,
+    /// Peak memory usage **in bytes**.
+    pub max_memory: u64,
+}
+
+/// Timings of a reference on all cases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceReport {
+    pub name: String,
+    pub language: String,
+    pub cases: Vec<CaseTiming>,
+}
+
+/// Report of a calibration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalibrationReport {
+    pub references: Vec<ReferenceReport>,
+    /// Suggested time limits, keyed by language name.
+    pub suggested: BTreeMap<String, Duration>,
+}
+
+/// Run all `references` on all cases of `cases` in `cases_dir`, writing
+/// outputs into `dir`.
+///
+/// Outputs of references are compared with the comparer of the problem, or
+/// checked by the compiled `checker` of a [`JudgeType::SpecialJudge`] problem,
+/// so that wrong references don't skew the timings.
+///
+/// # Errors
+///
+/// Returns an error if the problem is interactive, the checker is not given
+/// for a problem with one, a reference fails on a case, or cannot be run.
+pub async fn calibrate(
+    config: &CalibrateConfig,
+    cases: &CasesConfig,
+    cases_dir: &Path,
+    references: &[Reference<'_>],
+    checker: Option<&Program>,
+    dir: &Path,
+) -> Result<CalibrationReport, CalibrateError> {
+    let checker = match cases.judge {
+        JudgeType::Classic => None,
+        JudgeType::SpecialJudge { .. } => Some(checker.ok_or(CalibrateError::MissingChecker)?),
+        JudgeType::Interactive { .. } => return Err(CalibrateError::Interactive),
+    };
+    let comparer = cases.comparer();
+    let output_path = dir.join("output");
+    let mut reports = Vec::with_capacity(references.len());
+    let mut slowest = BTreeMap::<String, Duration>::new();
+
+    for reference in references {
+        let mut timings = Vec::new();
+
+        for (case, id) in cases.cases().zip(1..) {
+            let failed = |result| CalibrateError::ReferenceFailed {
+                reference: reference.name.to_owned(),
+                case: id,
+                result,
+            };
+            let mut cpu_times = Vec::new();
+            let mut wall_times = Vec::new();
+            let mut max_memory = 0;
+
+            for _ in 0..config.runs.get() {
+                let work_dir = reference.program.work_dir();
+                let stdin = cases
+                    .io()
+                    .prepare_input(&cases_dir.join(case.input()), work_dir)
+                    .await?;
+                let info = reference.program.run(&[], stdin, &output_path).await?;
+                if let Some(result) = info.result() {
+                    return Err(failed(result));
+                }
+
+                let mut output = cases.io().open_output(work_dir, &output_path).await?;
+                let answer_path = cases_dir.join(case.answer());
+                let result = match checker {
+                    Some(checker) => {
+                        // The output file of the run may be overwritten by the
+                        // next one, so check a copy.
+                        let checked_path = dir.join("checked");
+                        tokio::io::copy(&mut output, &mut File::create(&checked_path).await?)
+                            .await?;
+                        let info = checker
+                            .check(
+                                &cases_dir.join(case.input()),
+                                &checked_path,
+                                &answer_path,
+                                dir,
+                            )
+                            .await?;
+                        info.verdict()
+                            .ok_or_else(|| CalibrateError::CheckerFailed {
+                                reference: reference.name.to_owned(),
+                                case: id,
+                                message: info.message(),
+                            })?
+                    }
+                    None => {
+                        let answer = File::open(&answer_path).await?;
+                        match comparer.compare(output, answer).await? {
+                            CompareResult::Accepted => JudgeResult::Accepted,
+                            CompareResult::WrongAnswer => JudgeResult::WrongAnswer,
+                            CompareResult::PresentationError => JudgeResult::PresentationError,
+                        }
+                    }
+                };
+                cases.io().clean(work_dir).await?;
+                if result != JudgeResult::Accepted {
+                    return Err(failed(result));
+                }
+
+                cpu_times.extend(info.cpu_time());
+                wall_times.push(info.wall_time());
+                max_memory = max_memory.max(info.memory().unwrap_or_default());
+            }
+
+            cpu_times.sort_unstable();
+            wall_times.sort_unstable();
+            let timing = CaseTiming {
+                case: id,
+                max_cpu_time: cpu_times.last().copied().unwrap_or_default(),
+                median_cpu_time: median(&cpu_times),
+                max_wall_time: wall_times.last().copied().unwrap_or_default(),
+                median_wall_time: median(&wall_times),
+                max_memory,
+            };
+
+            let language_slowest = slowest.entry(reference.language.to_owned()).or_default();
+            *language_slowest = (*language_slowest).max(timing.max_cpu_time);
+            timings.push(timing);
+        }
+
+        reports.push(ReferenceReport {
+            name: reference.name.to_owned(),
+            language: reference.language.to_owned(),
+            cases: timings,
+        });
+    }
+
+    Ok(CalibrationReport {
+        references: reports,
+        suggested: slowest
+            .into_iter()
+            .map(|(language, time)| {
+                let limit = config.rule.suggest(&language, time);
+                (language, limit)
+            })
+            .collect(),
+    })
+}
+
+/// Get the lower median of sorted `values`.
+fn median(values: &[Duration]) -> Duration {
+    values
+        .get(values.len().saturating_sub(1) / 2)
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{CalibrateConfig, CalibrateError, Reference, TimeLimitRule, calibrate};
+    use crate::judger::request::cases::CasesConfig;
+    use crate::judger::run::test_program;
+
+    #[test]
+    fn suggest_time_limits() {
+        let rule = TimeLimitRule {
+            languages: HashMap::from([("Python".to_owned(), 5.0)]),
+            ..TimeLimitRule::default()
+        };
+        assert_eq!(
+            rule.suggest("C++", Duration::from_millis(420)),
+            Duration::from_millis(900)
+        );
+        assert_eq!(
+            rule.suggest("Python", Duration::from_millis(420)),
+            Duration::from_millis(2100)
+        );
+        assert_eq!(
+            rule.suggest("C++", Duration::ZERO),
+            Duration::from_millis(100)
+        );
+
+        let rule = TimeLimitRule {
+            factor: 1e300,
+            ..TimeLimitRule::default()
+        };
+        assert_eq!(
+            rule.suggest("C++", Duration::from_secs(1)),
+            Duration::from_millis(u64::MAX)
+        );
+
+        for rule in [
+            json!({ "factor": -1.0 }),
+            json!({ "factor": 0.0 }),
+            json!({ "languages": { "Python": -5.0 } }),
+        ] {
+            assert!(serde_json::from_value::<TimeLimitRule>(rule).is_err());
+        }
+        let rule = serde_json::from_value::<TimeLimitRule>(json!({ "factor": 3 })).unwrap();
+        assert_eq!(rule.factor, 3.0);
+    }
+
+    #[tokio::test]
+    async fn calibrate_references() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let work_dir = tempfile::tempdir()?;
+        for (name, content) in [
+            ("1.in", "1\n"),
+            ("1.ans", "2\n"),
+            ("2.in", "5\n"),
+            ("2.ans", "10\n"),
+        ] {
+            fs::write(cases_dir.path().join(name), content).await?;
+        }
+        let cases = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": { "judgeType": "classic" },
+            "resourceLimits": { "time": 1000, "memory": 256 },
+            "task": {
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans" },
+                    { "input": "2.in", "answer": "2.ans" }
+                ]
+            }
+        }))?;
+        let program = |command: &str| test_program(command, work_dir.path());
+        let config = serde_json::from_value::<CalibrateConfig>(json!({ "runs": 2 }))?;
+
+        let correct = program("read n; echo $((n * 2))");
+        let report = calibrate(
+            &config,
+            &cases,
+            cases_dir.path(),
+            &[Reference {
+                name: "main.sh",
+                language: "Shell",
+                program: &correct,
+            }],
+            None,
+            work_dir.path(),
+        )
+        .await?;
+        assert_eq!(report.references[0].cases.len(), 2);
+        assert_eq!(report.references[0].cases[1].case, 2);
+        assert!(report.suggested["Shell"] >= Duration::from_millis(100));
+
+        let wrong = program("read n; echo $((n + 1))");
+        let error = calibrate(
+            &config,
+            &cases,
+            cases_dir.path(),
+            &[Reference {
+                name: "wrong.sh",
+                language: "Shell",
+                program: &wrong,
+            }],
+            None,
+            work_dir.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            CalibrateError::ReferenceFailed { case: 2, .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn calibrate_with_checker() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let work_dir = tempfile::tempdir()?;
+        for (name, content) in [("1.in", "3\n"), ("1.ans", "6\n")] {
+            fs::write(cases_dir.path().join(name), content).await?;
+        }
+        let cases = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": { "judgeType": "special-judge", "checker": "checker.sh" },
+            "resourceLimits": { "time": 1000, "memory": 256 },
+            "task": {
+                "taskType": "simple",
+                "cases": [{ "input": "1.in", "answer": "1.ans" }]
+            }
+        }))?;
+        let program = |command: &str| test_program(command, work_dir.path());
+        let config = serde_json::from_value::<CalibrateConfig>(json!({ "runs": 1 }))?;
+
+        // Leading zeros are accepted by the checker, but not by the comparer.
+        let reference = program("read n; echo 0$((n * 2))");
+        let references = [Reference {
+            name: "main.sh",
+            language: "Shell",
+            program: &reference,
+        }];
+        let checker =
+            program(r#"read out < "$2"; read ans < "$3"; [ "$out" -eq "$ans" ] || exit 1"#);
+        let report = calibrate(
+            &config,
+            &cases,
+            cases_dir.path(),
+            &references,
+            Some(&checker),
+            work_dir.path(),
+        )
+        .await?;
+        assert_eq!(report.references[0].cases.len(), 1);
+
+        let error = calibrate(
+            &config,
+            &cases,
+            cases_dir.path(),
+            &references,
+            None,
+            work_dir.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, CalibrateError::MissingChecker));
+
+        let broken = program("exit 3");
+        let error = calibrate(
+            &config,
+            &cases,
+            cases_dir.path(),
+            &references,
+            Some(&broken),
+            work_dir.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            CalibrateError::CheckerFailed { case: 1, .. }
+        ));
+        Ok(())
+    }
+}
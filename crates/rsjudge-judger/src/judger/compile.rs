@@ -0,0 +1,671 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compile phase of judging.
+//!
+//! The compile phase runs the compilation command, or the syntax check command
+//! of languages executed from source code, as the builder user.
+//!
+//! The compiler runs with [`CompileLimits`], and without network access
+//! unless allowed. Its stdout and stderr are truncated to the output budget,
+//! instead of being buffered without bound, and read until the wall time limit
+//! at most, as processes escaping its process group may hold them open.
+
+use std::fmt::{self, Display};
+use std::io;
+use std::os::unix::process::ExitStatusExt as _;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use rsjudge_runner::user::builder;
+use rsjudge_runner::utils::network::DenyNetwork as _;
+use rsjudge_runner::utils::resources::WithResourceLimit as _;
+use rsjudge_runner::utils::resources::rusage::WaitForResourceUsage as _;
+use rsjudge_runner::{Error, RunAs as _};
+use rsjudge_traits::judger::JudgeResult;
+use rsjudge_traits::language::config::{CompileLimits, DiagnosticFormat, LanguageDef, Phase};
+use rsjudge_traits::language::option::LanguageOption;
+use rsjudge_traits::language::template::{TemplateError, TemplateVars};
+use thiserror::Error;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+use tokio::process::Command;
+
+use crate::judger::cache::{CacheKey, CompileCache};
+use crate::judger::diagnostic::{Diagnostic, parse_diagnostics};
+use crate::judger::grader::InstalledGrader;
+use crate::judger::request::cases::CasesConfig;
+
+/// Status of a compilation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompileStatus {
+    /// The compiler exited successfully.
+    Success,
+    /// The compiler exited with a failure.
+    Failed(ExitStatus),
+    /// The compiler was killed for exceeding the time limit.
+    TimeLimitExceeded(Duration),
+    /// The artifacts exceeded the size limit **in bytes**.
+    ArtifactTooLarge(u64),
+}
+
+impl Display for CompileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "Compiled successfully"),
+            Self::Failed(status) => write!(f, "Compiler failed with {status}"),
+            Self::TimeLimitExceeded(limit) => {
+                write!(f, "Compilation timed out after {limit:?}")
+            }
+            Self::ArtifactTooLarge(limit) => write!(
+                f,
+                "Compiled artifacts exceeded the size limit of {} MiB",
+                limit >> 20
+            ),
+        }
+    }
+}
+
+/// Result of a compilation, with the compiler output.
+#[derive(Debug, Clone)]
+pub struct CompileInfo {
+    pub(crate) status: CompileStatus,
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+}
+
+impl CompileInfo {
+    /// Get the status of the compilation.
+    pub fn status(&self) -> CompileStatus {
+        self.status
+    }
+
+    /// Whether the compilation succeeded.
+    pub fn is_success(&self) -> bool {
+        self.status == CompileStatus::Success
+    }
+
+    /// Get the judge result decided by the compilation, if any.
+    pub fn result(&self) -> Option<JudgeResult> {
+        (!self.is_success()).then_some(JudgeResult::CompileError)
+    }
+
+    /// Get the exit code of the compiler, `-1` if it didn't exit normally.
+    pub fn exit_code(&self) -> i32 {
+        match self.status {
+            CompileStatus::Success => 0,
+            CompileStatus::Failed(status) => status.code().unwrap_or(-1),
+            CompileStatus::TimeLimitExceeded(_) | CompileStatus::ArtifactTooLarge(_) => -1,
+        }
+    }
+
+    /// Get the compiler stdout, possibly truncated.
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Get the compiler stderr, possibly truncated.
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Parse the compiler output into structured diagnostics.
+    pub fn diagnostics(&self, format: DiagnosticFormat) -> Vec<Diagnostic> {
+        parse_diagnostics(format, &self.stdout, &self.stderr)
+    }
+}
+
+/// Error running the compile phase.
+///
+/// A failed compilation is not an error, but reported by [`CompileInfo`].
+#[derive(Debug, Error)]
+pub enum CompileError {
+    /// The compile command cannot be rendered.
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+    /// The compile command cannot be run.
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+    /// The compile cache cannot be read or written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Lookup of a submission in the [`CompileCache`] by [`compile_source`].
+#[derive(Debug, Clone, Copy)]
+pub struct CacheLookup<'a> {
+    cache: &'a CompileCache,
+    code: &'a [u8],
+    option: &'a LanguageOption,
+    extra: &'a [u8],
+}
+
+impl<'a> CacheLookup<'a> {
+    /// Look up a submission of the problem `config` in `cache`.
+    ///
+    /// `cache` is `None` if disabled by the judger, and `None` is returned if
+    /// either the judger or the problem disables the cache. `code` identifies
+    /// the code, see [`Code::identity`], and `grader` is the grader installed
+    /// for the submission, if any, whose files are compiled along with it.
+    ///
+    /// [`Code::identity`]: crate::judger::request::source::Code::identity
+    #[must_use]
+    pub fn new(
+        cache: Option<&'a CompileCache>,
+        config: &CasesConfig,
+        code: &'a [u8],
+        option: &'a LanguageOption,
+        grader: Option<&'a InstalledGrader>,
+    ) -> Option<Self> {
+        Some(Self {
+            cache: cache.filter(|_| config.compile_cache_enabled())?,
+            code,
+            option,
+            extra: grader.map_or(&[], InstalledGrader::identity),
+        })
+    }
+}
+
+/// Build the command of the compile phase of `language`, run in `work_dir`.
+///
+/// `project` tells whether the submission has several files.
+///
+/// Returns `None` if the language has nothing to run in the compile phase.
+///
+/// # Errors
+///
+/// Returns an error if the command template cannot be rendered with `vars`.
+pub fn compile_command(
+    language: &LanguageDef,
+    project: bool,
+    vars: &TemplateVars,
+    work_dir: &Path,
+) -> Result<Option<Command>, TemplateError> {
+    let Some(template) = language.compile_command(project) else {
+        return Ok(None);
+    };
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(vars.render(template)?)
+        .current_dir(work_dir);
+    language.environment(Phase::Compile).apply(&mut command);
+
+    Ok(Some(command))
+}
+
+/// Run the compile phase of `language` as the builder user.
+///
+/// With `cache`, the result and `artifacts` of a previous compilation with
+/// the same code, language option, compile command and toolchain version are
+/// reused, and the result of a new compilation is stored.
+///
+/// Returns `None` if the language has nothing to run in the compile phase,
+/// e.g. an interpreted language without a syntax check command.
+///
+/// # Errors
+///
+/// Returns an error if the compile command cannot be rendered or run, or the
+/// cache cannot be read or written.
+pub async fn compile_source(
+    language: &LanguageDef,
+    project: bool,
+    vars: &TemplateVars,
+    work_dir: &Path,
+    artifacts: &[&Path],
+    cache: Option<CacheLookup<'_>>,
+) -> Result<Option<CompileInfo>, CompileError> {
+    compile_as(language, project, vars, work_dir, artifacts, cache, true).await
+}
+
+/// Run the compile phase of `language`, as the builder user if `sandbox`.
+async fn compile_as(
+    language: &LanguageDef,
+    project: bool,
+    vars: &TemplateVars,
+    work_dir: &Path,
+    artifacts: &[&Path],
+    cache: Option<CacheLookup<'_>>,
+    sandbox: bool,
+) -> Result<Option<CompileInfo>, CompileError> {
+    let Some(template) = language.compile_command(project) else {
+        return Ok(None);
+    };
+    let prepare = |mut command: Command| -> Result<Command, CompileError> {
+        if sandbox {
+            command.run_as(builder().map_err(Error::from)?)?;
+        }
+        Ok(command)
+    };
+
+    let key = match cache {
+        Some(lookup) => {
+            let version = match language.version() {
+                Some(version) => {
+                    let mut command = Command::new("sh");
+                    command
+                        .arg("-c")
+                        .arg(format!("printf '%s' \"{version}\""))
+                        .current_dir(work_dir);
+                    language.environment(Phase::Compile).apply(&mut command);
+                    let info = compile(prepare(command)?, language.compile_limits(), &[]).await?;
+                    info.stdout
+                }
+                None => Vec::new(),
+            };
+            let key = CacheKey::new(
+                lookup.code,
+                lookup.option,
+                template,
+                &String::from_utf8_lossy(&version),
+                lookup.extra,
+            );
+            if let Some(info) = lookup.cache.get(key, work_dir).await? {
+                return Ok(Some(info));
+            }
+            Some((lookup.cache, key))
+        }
+        None => None,
+    };
+
+    let Some(command) = compile_command(language, project, vars, work_dir)? else {
+        return Ok(None);
+    };
+    let info = compile(prepare(command)?, language.compile_limits(), artifacts).await?;
+    if let Some((cache, key)) = key {
+        cache.put(key, &info, work_dir, artifacts).await?;
+    }
+    Ok(Some(info))
+}
+
+/// Run the compiler `command` with `limits`.
+///
+/// `artifacts` are the files or directories produced by the compiler, which
+/// are checked against the artifact size limit.
+///
+/// # Errors
+///
+/// Returns an error if the compiler cannot be spawned or waited for.
+pub async fn compile(
+    mut command: Command,
+    limits: &CompileLimits,
+    artifacts: &[&Path],
+) -> rsjudge_runner::Result<CompileInfo> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Allow killing all processes spawned by the compiler.
+        .process_group(0)
+        .kill_on_drop(true);
+    if !limits.network {
+        command.deny_network();
+    }
+
+    let deadline = Instant::now() + limits.wall_time();
+    let mut child = command
+        .with_resource_limit(limits.resource_limit())
+        .spawn()?;
+    let pid = child.child().id();
+    let stdout = child.child_mut().stdout.take();
+    let stderr = child.child_mut().stderr.take();
+    let budget = u64::from(limits.output);
+
+    let (status, stdout, stderr) = tokio::join!(
+        async {
+            let status = child.wait_for_resource_usage().await;
+            if let Some(pid) = pid {
+                // Descendants may still hold the pipes open.
+                let _ = killpg(Pid::from_raw(pid as _), Signal::SIGKILL);
+            }
+            status
+        },
+        read_truncated(stdout, budget, Some(deadline)),
+        read_truncated(stderr, budget, Some(deadline)),
+    );
+
+    let cpu_time_limit = Duration::from_millis(limits.time.get().into());
+    let status = match status {
+        Err(Error::TimeLimitExceeded(..)) => CompileStatus::TimeLimitExceeded(limits.wall_time()),
+        Err(error) => return Err(error),
+        Ok((status, usage)) => match status.signal().and_then(|s| Signal::try_from(s).ok()) {
+            Some(Signal::SIGXCPU) => CompileStatus::TimeLimitExceeded(cpu_time_limit),
+            Some(Signal::SIGKILL) if usage.cpu_time() >= cpu_time_limit => {
+                CompileStatus::TimeLimitExceeded(cpu_time_limit)
+            }
+            Some(Signal::SIGXFSZ) => CompileStatus::ArtifactTooLarge(limits.artifact_size()),
+            // The file size limit may also fail the compiler with an error
+            // message, so check the artifacts regardless of the exit status.
+            _ if artifacts_size(artifacts).await? > limits.artifact_size() => {
+                CompileStatus::ArtifactTooLarge(limits.artifact_size())
+            }
+            _ if status.success() => CompileStatus::Success,
+            _ => CompileStatus::Failed(status),
+        },
+    };
+
+    Ok(CompileInfo {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+/// Read at most `budget` bytes from `reader`, discarding the rest.
+///
+/// Reading stops at `deadline` if any, and `reader` is closed then, even if
+/// the writer holds it open.
+pub(crate) async fn read_truncated<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    budget: u64,
+    deadline: Option<Instant>,
+) -> io::Result<Vec<u8>> {
+    let Some(mut reader) = reader else {
+        return Ok(Vec::new());
+    };
+
+    let mut output = Vec::new();
+    let read = async {
+        (&mut reader).take(budget).read_to_end(&mut output).await?;
+        tokio::io::copy(&mut reader, &mut tokio::io::sink()).await
+    };
+    let discarded = match deadline {
+        Some(deadline) => match tokio::time::timeout_at(deadline.into(), read).await {
+            Ok(discarded) => discarded?,
+            Err(_) => {
+                output.extend_from_slice(b"\n[output not closed within the time limit]\n");
+                return Ok(output);
+            }
+        },
+        None => read.await?,
+    };
+    if discarded != 0 {
+        output.extend_from_slice(format!("\n[{discarded} bytes truncated]\n").as_bytes());
+    }
+
+    Ok(output)
+}
+
+/// Get the total size of `artifacts`, ignoring missing ones.
+async fn artifacts_size(artifacts: &[&Path]) -> io::Result<u64> {
+    let mut size = 0;
+    let mut pending: Vec<_> = artifacts.iter().map(|path| path.to_path_buf()).collect();
+
+    while let Some(path) = pending.pop() {
+        let metadata = match fs::symlink_metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+
+        if metadata.is_dir() {
+            let mut entries = fs::read_dir(&path).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                pending.push(entry.path());
+            }
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::num::{NonZeroU32, NonZeroU64};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use rsjudge_traits::judger::JudgeResult;
+    use rsjudge_traits::language::config::{CompileLimits, LanguageDef};
+    use rsjudge_traits::language::option::LanguageOption;
+    use rsjudge_traits::language::template::TemplateVars;
+    use tokio::process::Command;
+
+    use super::{CacheLookup, CompileStatus, compile, compile_as, compile_command};
+    use crate::judger::cache::{CacheConfig, CompileCache};
+    use crate::judger::request::cases::{CasesConfig, Grader};
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[tokio::test]
+    async fn compile_success_and_failure() -> rsjudge_runner::Result<()> {
+        let limits = CompileLimits::default();
+
+        let info = compile(sh("echo out; echo err >&2"), &limits, &[]).await?;
+        assert!(info.is_success());
+        assert_eq!(info.result(), None);
+        assert_eq!(info.stdout(), b"out\n");
+        assert_eq!(info.stderr(), b"err\n");
+
+        let info = compile(sh("echo error >&2; exit 3"), &limits, &[]).await?;
+        assert_eq!(info.result(), Some(JudgeResult::CompileError));
+        assert_eq!(info.exit_code(), 3);
+        assert_eq!(info.stderr(), b"error\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compile_timeout() -> rsjudge_runner::Result<()> {
+        let limits = CompileLimits {
+            wall_time: NonZeroU32::new(200),
+            ..CompileLimits::default()
+        };
+
+        let start = Instant::now();
+        // The background process holds the pipes open.
+        let info = compile(sh("sleep 10 & sleep 10"), &limits, &[]).await?;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            info.status(),
+            CompileStatus::TimeLimitExceeded(Duration::from_millis(200))
+        );
+        assert_eq!(info.result(), Some(JudgeResult::CompileError));
+        assert_eq!(
+            info.status().to_string(),
+            "Compilation timed out after 200ms"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compile_detached_descendant() -> rsjudge_runner::Result<()> {
+        let limits = CompileLimits {
+            wall_time: NonZeroU32::new(500),
+            ..CompileLimits::default()
+        };
+
+        let start = Instant::now();
+        // The descendant leaves the process group, holding the pipes open.
+        let info = compile(sh("setsid sleep 10 & echo done"), &limits, &[]).await?;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(info.is_success());
+        assert!(info.stdout().starts_with(b"done\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compile_output_truncated() -> rsjudge_runner::Result<()> {
+        let limits = CompileLimits {
+            output: 1000,
+            ..CompileLimits::default()
+        };
+
+        let info = compile(sh("head -c 100000 /dev/zero >&2"), &limits, &[]).await?;
+        assert!(info.is_success());
+        assert!(info.stderr().starts_with(&[0; 1000]));
+        assert_eq!(&info.stderr()[1000..], b"\n[99000 bytes truncated]\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compile_artifact_too_large() -> rsjudge_runner::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let limits = CompileLimits {
+            artifact_size: NonZeroU32::MIN,
+            ..CompileLimits::default()
+        };
+        let artifact = dir.path().join("out");
+        let script = format!("head -c 2000000 /dev/zero > {}", artifact.display());
+
+        let info = compile(sh(&script), &limits, &[dir.path()]).await?;
+        assert_eq!(info.status(), CompileStatus::ArtifactTooLarge(1 << 20));
+        assert_eq!(
+            limits.resource_limit().max_file_size_limit(),
+            NonZeroU64::new((1 << 20) + 1).map(NonZeroU64::get)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reuse_cached_compilation() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let count = dir.path().join("count");
+        let version = dir.path().join("version");
+        tokio::fs::write(&version, "1").await?;
+        let language: LanguageDef = toml::from_str(&format!(
+            r#"
+            exec_type = "binary"
+            compile = "echo compiling >&2; cp {{src_file}} out; echo >> {}"
+            version = "$(cat {})"
+            "#,
+            count.display(),
+            version.display(),
+        ))?;
+        let mut vars = TemplateVars::new();
+        vars.set("src_file", "main.sh");
+
+        let cache_dir = tempfile::tempdir()?;
+        let cache = CompileCache::open(&CacheConfig {
+            enabled: true,
+            dir: cache_dir.path().to_path_buf(),
+            size: 1,
+        })
+        .await?;
+        let config: CasesConfig = serde_json::from_value(serde_json::json!({
+            "score": 100,
+            "judge": { "judgeType": "classic" },
+            "resourceLimits": { "time": 1000, "memory": 256 },
+            "task": { "taskType": "simple", "cases": [] }
+        }))?;
+        let option = LanguageOption {
+            name: "Shell".to_owned(),
+            config: HashMap::new(),
+        };
+        let lookup = CacheLookup::new(cache.as_ref(), &config, b"echo 1", &option, None);
+        assert!(lookup.is_some());
+
+        let compile_in = async |lookup: Option<CacheLookup<'_>>| -> anyhow::Result<_> {
+            let work_dir = tempfile::tempdir()?;
+            tokio::fs::write(work_dir.path().join("main.sh"), "echo 1").await?;
+            let out = Path::new("out");
+            let info = compile_as(
+                &language,
+                false,
+                &vars,
+                work_dir.path(),
+                &[out],
+                lookup,
+                false,
+            )
+            .await?
+            .unwrap();
+            let artifact = tokio::fs::read_to_string(work_dir.path().join(out)).await?;
+            let count = tokio::fs::read_to_string(&count).await?.lines().count();
+            Ok((info, artifact, count))
+        };
+
+        let (info, artifact, count) = compile_in(lookup).await?;
+        assert!(info.is_success());
+        assert_eq!((artifact.as_str(), count), ("echo 1", 1));
+
+        // The compiler doesn't run on hit, but its artifact and output are
+        // replayed.
+        let (info, artifact, count) = compile_in(lookup).await?;
+        assert_eq!(info.stderr(), b"compiling\n");
+        assert_eq!((artifact.as_str(), count), ("echo 1", 1));
+
+        tokio::fs::write(&version, "2").await?;
+        let (_, _, count) = compile_in(lookup).await?;
+        assert_eq!(count, 2);
+
+        // Changing the grader compiled along with the code also misses.
+        let grader = Grader {
+            sources: vec![PathBuf::from("grader.sh")],
+            headers: Vec::new(),
+        };
+        for (content, expected) in [("a", 3), ("b", 4), ("a", 4)] {
+            tokio::fs::write(dir.path().join("grader.sh"), content).await?;
+            let grader_dir = tempfile::tempdir()?;
+            let installed = grader
+                .install(dir.path(), grader_dir.path(), &mut TemplateVars::new())
+                .await?;
+            let lookup = CacheLookup::new(
+                cache.as_ref(),
+                &config,
+                b"echo 1",
+                &option,
+                Some(&installed),
+            );
+            let (_, _, count) = compile_in(lookup).await?;
+            assert_eq!(count, expected);
+        }
+
+        let (_, _, count) = compile_in(None).await?;
+        assert_eq!(count, 5);
+
+        let disabled: CasesConfig = serde_json::from_value(serde_json::json!({
+            "score": 100,
+            "judge": { "judgeType": "classic" },
+            "resourceLimits": { "time": 1000, "memory": 256 },
+            "task": { "taskType": "simple", "cases": [] },
+            "disableCompileCache": true
+        }))?;
+        assert!(CacheLookup::new(cache.as_ref(), &disabled, b"echo 1", &option, None).is_none());
+        assert!(CacheLookup::new(None, &config, b"echo 1", &option, None).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_source_code() -> anyhow::Result<()> {
+        let language: LanguageDef = toml::from_str(
+            r#"
+            exec_type = "source_code"
+            check = "sh -n {src_file}"
+            execute = "sh {src_file}"
+            "#,
+        )?;
+        let dir = tempfile::tempdir()?;
+        let mut vars = TemplateVars::new();
+        vars.set("src_file", "main.sh");
+
+        tokio::fs::write(dir.path().join("main.sh"), "echo ok\n").await?;
+        let command = compile_command(&language, false, &vars, dir.path())?.unwrap();
+        let info = compile(command, language.compile_limits(), &[]).await?;
+        assert!(info.is_success());
+
+        tokio::fs::write(dir.path().join("main.sh"), "if then fi\n").await?;
+        let command = compile_command(&language, false, &vars, dir.path())?.unwrap();
+        let info = compile(command, language.compile_limits(), &[]).await?;
+        assert_eq!(info.result(), Some(JudgeResult::CompileError));
+        assert!(!info.stderr().is_empty());
+
+        let language: LanguageDef = toml::from_str(
+            r#"
+            exec_type = "source_code"
+            execute = "sh {src_file}"
+            "#,
+        )?;
+        assert!(compile_command(&language, false, &vars, dir.path())?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of [DOMjudge] problem packages, with `problem.yaml`,
+//! `domjudge-problem.ini`, and cases in `data/sample` and `data/secret`.
+//!
+//! DOMjudge judges pass or fail, so problems are imported in
+//! [`JudgeMode::Icpc`], and scores are dropped on export.
+//!
+//! [DOMjudge]: https://www.domjudge.org
+
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::judger::convert::{
+    ConvertError, Converted, ProblemFormat, copy_file, copy_problem_files, export_warnings,
+    judge_program, write_config,
+};
+use crate::judger::discover::{list_files, natural_cmp};
+use crate::judger::request::cases::{
+    Case, CasesConfig, JudgeMode, JudgeType, ResourceLimits, TaskType,
+};
+
+const PROBLEM_YAML: &str = "problem.yaml";
+const PROBLEM_INI: &str = "domjudge-problem.ini";
+const VALIDATORS_DIR: &str = "output_validators";
+const FORMAT: &str = "DOMjudge";
+
+/// Time limit used if not set **in milliseconds**.
+const DEFAULT_TIME: u32 = 1000;
+/// Memory limit used if not set **in MiB**.
+const DEFAULT_MEMORY: u32 = 2048;
+
+/// DOMjudge problem packages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Domjudge;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct ProblemYaml {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// `default`, `custom`, or `custom interactive`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validator_flags: Option<String>,
+    #[serde(default)]
+    limits: Limits,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Limits {
+    /// Memory limit **in MiB**.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory: Option<u32>,
+}
+
+impl ProblemFormat for Domjudge {
+    async fn import(&self, src: &Path, dst: &Path) -> Result<Vec<Converted>, ConvertError> {
+        let problem: ProblemYaml = match fs::read(src.join(PROBLEM_YAML)).await {
+            Ok(problem) => serde_yaml_ng::from_slice(&problem)
+                .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => ProblemYaml::default(),
+            Err(error) => return Err(error.into()),
+        };
+        let mut warnings = Vec::new();
+
+        let time = match fs::read_to_string(src.join(PROBLEM_INI)).await {
+            Ok(ini) => ini_time_limit(&ini)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        let time = time.unwrap_or_else(|| {
+            warnings.push(format!("Time limit is not set, {DEFAULT_TIME} ms is used"));
+            DEFAULT_TIME
+        });
+        let memory = problem.limits.memory.unwrap_or(DEFAULT_MEMORY);
+
+        let files = list_files(src).await?;
+        let mut inputs: Vec<_> = files
+            .iter()
+            .filter(|path| {
+                (path.starts_with("data/sample") || path.starts_with("data/secret"))
+                    && path.extension().is_some_and(|extension| extension == "in")
+            })
+            .collect();
+        inputs.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        let cases: Vec<_> = inputs
+            .into_iter()
+            .map(|input| Case::new(input.clone(), input.with_extension("ans")))
+            .collect();
+
+        let validators: Vec<_> = files
+            .iter()
+            .filter(|path| path.starts_with(VALIDATORS_DIR))
+            .collect();
+        let validator = || match validators.as_slice() {
+            [validator] => Ok(PathBuf::from(validator)),
+            [] => Err(ConvertError::MissingFile(format!(
+                "{VALIDATORS_DIR}/<validator>"
+            ))),
+            _ => Err(ConvertError::Unsupported {
+                format: "rsjudge",
+                feature: "Output validators of multiple files".to_owned(),
+            }),
+        };
+        let judge = match problem.validation.as_deref() {
+            None | Some("default") => JudgeType::Classic,
+            Some("custom") => {
+                warnings.push(
+                    "Output validator is used as a testlib checker, which has another protocol"
+                        .to_owned(),
+                );
+                JudgeType::SpecialJudge {
+                    checker: validator()?,
+                }
+            }
+            Some("custom interactive") => JudgeType::Interactive {
+                interactor: validator()?,
+            },
+            Some(validation) => {
+                return Err(ConvertError::Unsupported {
+                    format: "rsjudge",
+                    feature: format!("Validation `{validation}`"),
+                });
+            }
+        };
+        if problem.validator_flags.is_some() {
+            warnings.push("Validator flags are dropped".to_owned());
+        }
+
+        let mut config = CasesConfig::new(
+            NonZeroU32::new(100).unwrap(),
+            judge,
+            ResourceLimits::new(time, memory),
+            TaskType::Simple { cases },
+        );
+        config.mode = JudgeMode::Icpc;
+
+        copy_problem_files(&config, src, dst).await?;
+        write_config(&config, dst).await?;
+        Ok(vec![Converted {
+            dir: dst.to_path_buf(),
+            config,
+            warnings,
+        }])
+    }
+
+    async fn export(
+        &self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<String>, ConvertError> {
+        if config.is_output_only() {
+            return Err(ConvertError::Unsupported {
+                format: FORMAT,
+                feature: "Output-only problems".to_owned(),
+            });
+        }
+
+        let mut warnings = export_warnings(config);
+        if config.mode != JudgeMode::Icpc {
+            warnings.push(format!(
+                "Scores are dropped, as {FORMAT} judges pass or fail"
+            ));
+        }
+        if !config.io().is_stdio() {
+            warnings.push("Input and output files are dropped".to_owned());
+        }
+
+        let cases: Vec<_> = config.cases().collect();
+        for (case, number) in cases.into_iter().zip(1..) {
+            let data = Path::new("data/secret");
+            copy_file(src, case.input(), dst, &data.join(format!("{number}.in"))).await?;
+            copy_file(src, case.answer(), dst, &data.join(format!("{number}.ans"))).await?;
+        }
+
+        let mut problem = ProblemYaml {
+            limits: Limits {
+                memory: Some((config.resource_limits().memory() >> 20) as u32),
+            },
+            ..ProblemYaml::default()
+        };
+        if let Some(program) = judge_program(&config.judge) {
+            let interactive = matches!(config.judge, JudgeType::Interactive { .. });
+            problem.validation = Some(
+                if interactive {
+                    "custom interactive"
+                } else {
+                    warnings.push(
+                        "Checker is used as an output validator, which has another protocol"
+                            .to_owned(),
+                    );
+                    "custom"
+                }
+                .to_owned(),
+            );
+            let name = program.file_name().map(PathBuf::from).unwrap_or_default();
+            let target = Path::new(VALIDATORS_DIR).join("validator").join(name);
+            copy_file(src, program, dst, &target).await?;
+        }
+
+        let yaml = serde_yaml_ng::to_string(&problem)
+            .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?;
+        fs::write(dst.join(PROBLEM_YAML), yaml).await?;
+        let seconds = config.resource_limits().time().as_secs_f64();
+        fs::write(dst.join(PROBLEM_INI), format!("timelimit='{seconds}'\n")).await?;
+        Ok(warnings)
+    }
+}
+
+/// Read the time limit **in milliseconds** from `domjudge-problem.ini`, where
+/// it is set in seconds.
+fn ini_time_limit(ini: &str) -> Result<Option<u32>, ConvertError> {
+    for line in ini.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key.trim() != "timelimit" {
+            continue;
+        }
+        let value = value.trim().trim_matches(['\'', '"']);
+        return value
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(|seconds| Some((seconds * 1000.0).round() as u32))
+            .ok_or_else(|| ConvertError::InvalidPackage(format!("Invalid time limit `{value}`")));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::fs;
+
+    use super::{Domjudge, ini_time_limit};
+    use crate::judger::convert::ProblemFormat as _;
+    use crate::judger::request::cases::{JudgeMode, JudgeType};
+
+    #[test]
+    fn parse_ini() {
+        assert_eq!(ini_time_limit("timelimit='2.5'\n").unwrap(), Some(2500));
+        assert_eq!(ini_time_limit("name='A'\n").unwrap(), None);
+        assert!(ini_time_limit("timelimit=fast").is_err());
+    }
+
+    #[tokio::test]
+    async fn import_and_export() -> anyhow::Result<()> {
+        let src = tempfile::tempdir()?;
+        let imported = tempfile::tempdir()?;
+        let exported = tempfile::tempdir()?;
+        for name in [
+            "data/sample/1.in",
+            "data/sample/1.ans",
+            "data/secret/2.in",
+            "data/secret/2.ans",
+            "data/secret/10.in",
+            "data/secret/10.ans",
+            "output_validators/check/check.cpp",
+        ] {
+            let path = src.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).await?;
+            fs::write(path, name).await?;
+        }
+        fs::write(
+            src.path().join("problem.yaml"),
+            "name: A\nvalidation: custom\nlimits:\n  memory: 512\n",
+        )
+        .await?;
+        fs::write(src.path().join("domjudge-problem.ini"), "timelimit='2'\n").await?;
+
+        let converted = Domjudge.import(src.path(), imported.path()).await?;
+        let config = &converted[0].config;
+        let inputs: Vec<_> = config.cases().map(|case| case.input()).collect();
+        assert_eq!(
+            inputs,
+            ["data/sample/1.in", "data/secret/2.in", "data/secret/10.in"].map(Path::new)
+        );
+        assert_eq!(config.mode, JudgeMode::Icpc);
+        assert_eq!(config.resource_limits().time().as_millis(), 2000);
+        assert_eq!(config.resource_limits().memory(), 512 << 20);
+        assert!(matches!(
+            &config.judge,
+            JudgeType::SpecialJudge { checker }
+                if checker == Path::new("output_validators/check/check.cpp")
+        ));
+        assert_eq!(converted[0].warnings.len(), 1);
+
+        let warnings = Domjudge
+            .export(config, imported.path(), exported.path())
+            .await?;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            fs::read_to_string(exported.path().join("data/secret/3.ans")).await?,
+            "data/secret/10.ans"
+        );
+        assert!(
+            exported
+                .path()
+                .join("output_validators/validator/check.cpp")
+                .exists()
+        );
+        assert_eq!(
+            fs::read_to_string(exported.path().join("domjudge-problem.ini")).await?,
+            "timelimit='2'\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of [Hydro] test data, described by `config.yaml`.
+//!
+//! Problems without subtasks in `config.yaml` have their cases discovered
+//! from file names, as Hydro does.
+//!
+//! [Hydro]: https://hydro.js.org
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::judger::convert::{
+    ConvertError, Converted, ProblemFormat, copy_problem_files, export_warnings, write_config,
+};
+use crate::judger::discover::{DiscoverConfig, discover};
+use crate::judger::request::cases::{
+    Case, CasesConfig, IoConfig, JudgeMode, JudgeType, ResourceLimits, ScorePolicy, Subtask,
+    TaskType,
+};
+use crate::judger::score::ScoreError;
+
+const CONFIG_FILE: &str = "config.yaml";
+const FORMAT: &str = "Hydro";
+
+/// Time limit used if not set **in milliseconds**.
+const DEFAULT_TIME: u32 = 1000;
+/// Memory limit used if not set **in MiB**.
+const DEFAULT_MEMORY: u32 = 256;
+
+/// Hydro test data, with `config.yaml` in the data directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hydro;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct HydroConfig {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    problem_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checker_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interactor: Option<String>,
+    /// Base name of the input and output files, instead of stdio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    subtasks: Vec<HydroSubtask>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct HydroSubtask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<u32>,
+    /// `sum`, `min` or `max`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    policy: Option<String>,
+    #[serde(default)]
+    cases: Vec<HydroCase>,
+    /// IDs of subtasks depended on.
+    #[serde(rename = "if", default, skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct HydroCase {
+    input: String,
+    output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory: Option<String>,
+}
+
+impl ProblemFormat for Hydro {
+    async fn import(&self, src: &Path, dst: &Path) -> Result<Vec<Converted>, ConvertError> {
+        let hydro: HydroConfig = match fs::read(src.join(CONFIG_FILE)).await {
+            Ok(config) => serde_yaml_ng::from_slice(&config)
+                .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HydroConfig::default(),
+            Err(error) => return Err(error.into()),
+        };
+        let mut warnings = Vec::new();
+
+        let time = match &hydro.time {
+            Some(time) => parse_time(time)?,
+            None => DEFAULT_TIME,
+        };
+        let memory = match &hydro.memory {
+            Some(memory) => parse_memory(memory)?,
+            None => DEFAULT_MEMORY,
+        };
+
+        let program = |name: &str, path: &Option<String>| {
+            path.clone().ok_or_else(|| {
+                ConvertError::InvalidPackage(format!("Missing {name} in {CONFIG_FILE}"))
+            })
+        };
+        let mut output_only = false;
+        let judge = match hydro.problem_type.as_deref() {
+            None | Some("default") => match hydro.checker_type.as_deref() {
+                None | Some("default") => JudgeType::Classic,
+                Some("strict") => {
+                    warnings.push("Strict comparison is replaced by the default".to_owned());
+                    JudgeType::Classic
+                }
+                Some(checker_type) => {
+                    if checker_type != "testlib" {
+                        warnings.push(format!(
+                            "Checker of type `{checker_type}` is used as a testlib checker"
+                        ));
+                    }
+                    JudgeType::SpecialJudge {
+                        checker: program("checker", &hydro.checker)?.into(),
+                    }
+                }
+            },
+            Some("interactive") => JudgeType::Interactive {
+                interactor: program("interactor", &hydro.interactor)?.into(),
+            },
+            Some("submit_answer") => {
+                output_only = true;
+                JudgeType::Classic
+            }
+            Some(problem_type) => {
+                return Err(ConvertError::Unsupported {
+                    format: "rsjudge",
+                    feature: format!("Problem type `{problem_type}`"),
+                });
+            }
+        };
+
+        let mut config = if hydro.subtasks.is_empty() {
+            let discover_config = DiscoverConfig {
+                judge,
+                resource_limits: ResourceLimits::new(time, memory),
+                ..DiscoverConfig::default()
+            };
+            discover(src, discover_config)
+                .await
+                .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?
+        } else {
+            let (score, task) = task(hydro.subtasks, &mut warnings)?;
+            CasesConfig::new(score, judge, ResourceLimits::new(time, memory), task)
+        };
+        config.output_only = output_only;
+        if let Some(filename) = hydro.filename {
+            config.io = IoConfig {
+                input_file: Some(format!("{filename}.in")),
+                output_file: Some(format!("{filename}.out")),
+                stdin: false,
+            };
+        }
+
+        copy_problem_files(&config, src, dst).await?;
+        write_config(&config, dst).await?;
+        Ok(vec![Converted {
+            dir: dst.to_path_buf(),
+            config,
+            warnings,
+        }])
+    }
+
+    async fn export(
+        &self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<String>, ConvertError> {
+        let mut warnings = export_warnings(config);
+        let plan = config.score_plan()?;
+
+        let mut hydro = HydroConfig {
+            time: Some(format!("{}ms", config.resource_limits().time().as_millis())),
+            memory: Some(format!("{}m", config.resource_limits().memory() >> 20)),
+            ..HydroConfig::default()
+        };
+        match &config.judge {
+            JudgeType::Classic => {}
+            JudgeType::SpecialJudge { checker } => {
+                hydro.checker_type = Some("testlib".to_owned());
+                hydro.checker = Some(checker.to_string_lossy().into_owned());
+            }
+            JudgeType::Interactive { interactor } => {
+                hydro.problem_type = Some("interactive".to_owned());
+                hydro.interactor = Some(interactor.to_string_lossy().into_owned());
+            }
+        }
+        if config.is_output_only() {
+            hydro.problem_type = Some("submit_answer".to_owned());
+        }
+        if config.mode == JudgeMode::Icpc {
+            warnings.push(format!(
+                "ICPC judge mode is not supported by {FORMAT}, and is dropped"
+            ));
+        }
+        if !config.io().is_stdio() {
+            let io = config.io();
+            let stem = |file: &Option<String>, extension| {
+                file.as_deref()
+                    .and_then(|file| file.strip_suffix(extension))
+                    .map(str::to_owned)
+            };
+            match (stem(&io.input_file, ".in"), stem(&io.output_file, ".out")) {
+                (Some(input), Some(output)) if input == output => hydro.filename = Some(input),
+                _ => warnings.push("Input and output files are dropped".to_owned()),
+            }
+        }
+
+        let case = |case: &Case| HydroCase {
+            input: case.input().to_string_lossy().into_owned(),
+            output: case.answer().to_string_lossy().into_owned(),
+            time: None,
+            memory: None,
+        };
+        let mut id = 0;
+        let mut subtask = |score, policy: &str, cases, dependencies| {
+            id += 1;
+            HydroSubtask {
+                id: Some(id),
+                score: Some(score),
+                policy: Some(policy.to_owned()),
+                cases,
+                dependencies,
+                time: None,
+                memory: None,
+            }
+        };
+
+        match &config.task {
+            TaskType::Simple { cases } => {
+                let (total, scores) = plan.scores().next().unwrap_or_default();
+                if scores.windows(2).all(|pair| pair[0] == pair[1]) {
+                    let cases = cases.iter().map(case).collect();
+                    hydro
+                        .subtasks
+                        .push(subtask(total, "sum", cases, Vec::new()));
+                } else {
+                    // Hydro shares the score of a subtask evenly, so each case
+                    // with its own score is a subtask.
+                    for (case_config, &score) in cases.iter().zip(scores) {
+                        let cases = vec![case(case_config)];
+                        hydro
+                            .subtasks
+                            .push(subtask(score, "sum", cases, Vec::new()));
+                    }
+                }
+            }
+            TaskType::Subtask { subtasks } => {
+                for (number, (subtask_config, (score, scores))) in
+                    subtasks.iter().zip(plan.scores()).enumerate()
+                {
+                    let policy = match subtask_config.policy {
+                        ScorePolicy::Min => "min",
+                        ScorePolicy::AllOrNothing => {
+                            warnings.push(format!(
+                                "All-or-nothing subtask {} is judged by the lowest case score",
+                                number + 1
+                            ));
+                            "min"
+                        }
+                        ScorePolicy::Sum => {
+                            if scores.windows(2).any(|pair| pair[0] != pair[1]) {
+                                warnings.push(format!(
+                                    "Case scores of subtask {} are shared evenly",
+                                    number + 1
+                                ));
+                            }
+                            "sum"
+                        }
+                    };
+                    let dependencies = subtask_config
+                        .dependencies
+                        .iter()
+                        .map(|&dependency| dependency as u32)
+                        .collect();
+                    let cases = subtask_config.cases.iter().map(case).collect();
+                    hydro
+                        .subtasks
+                        .push(subtask(score, policy, cases, dependencies));
+                }
+            }
+        }
+
+        copy_problem_files(config, src, dst).await?;
+        let yaml = serde_yaml_ng::to_string(&hydro)
+            .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?;
+        fs::write(dst.join(CONFIG_FILE), yaml).await?;
+        Ok(warnings)
+    }
+}
+
+/// Map Hydro subtasks into a task, with the total score.
+///
+/// Returns an error if the scores overflow.
+fn task(
+    subtasks: Vec<HydroSubtask>,
+    warnings: &mut Vec<String>,
+) -> Result<(NonZeroU32, TaskType), ConvertError> {
+    let total = subtasks
+        .iter()
+        .map(|subtask| subtask.score)
+        .collect::<Option<Vec<_>>>()
+        .map(|scores| {
+            scores
+                .into_iter()
+                .try_fold(0, u32::checked_add)
+                .ok_or(ScoreError::Overflow)
+        })
+        .transpose()?
+        .and_then(NonZeroU32::new)
+        .unwrap_or(NonZeroU32::new(100).unwrap());
+
+    let ids: HashMap<u32, usize> = subtasks
+        .iter()
+        .zip(1..)
+        .map(|(subtask, number)| (subtask.id.unwrap_or(number as u32), number))
+        .collect();
+
+    let subtasks = subtasks
+        .into_iter()
+        .zip(1..)
+        .map(|(subtask, number)| {
+            if subtask.time.is_some()
+                || subtask.memory.is_some()
+                || subtask
+                    .cases
+                    .iter()
+                    .any(|case| case.time.is_some() || case.memory.is_some())
+            {
+                warnings.push(format!("Limits of subtask {number} are dropped"));
+            }
+            let policy = match subtask.policy.as_deref() {
+                None | Some("min") => ScorePolicy::Min,
+                Some("sum") => ScorePolicy::Sum,
+                Some(policy) => {
+                    warnings.push(format!(
+                        "Subtask {number} of type `{policy}` is judged by the lowest case score"
+                    ));
+                    ScorePolicy::Min
+                }
+            };
+
+            let mut dependencies = Vec::new();
+            for id in subtask.dependencies {
+                match ids.get(&id) {
+                    Some(&dependency) if dependency < number => dependencies.push(dependency),
+                    _ => warnings.push(format!(
+                        "Dependency of subtask {number} on subtask {id} is dropped"
+                    )),
+                }
+            }
+
+            Subtask {
+                cases: subtask
+                    .cases
+                    .into_iter()
+                    .map(|case| Case::new(case.input, case.output))
+                    .collect(),
+                score: subtask.score.and_then(NonZeroU32::new),
+                policy,
+                dependencies,
+            }
+        })
+        .collect();
+
+    Ok((total, TaskType::Subtask { subtasks }))
+}
+
+/// Parse a Hydro time limit like `1s` or `500ms` **in milliseconds**.
+fn parse_time(time: &str) -> Result<u32, ConvertError> {
+    let time = time.trim().to_ascii_lowercase();
+    let (value, scale) = if let Some(value) = time.strip_suffix("ms") {
+        (value, 1.0)
+    } else if let Some(value) = time.strip_suffix('s') {
+        (value, 1000.0)
+    } else {
+        (time.as_str(), 1.0)
+    };
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+        .map(|value| (value * scale).round() as u32)
+        .ok_or_else(|| ConvertError::InvalidPackage(format!("Invalid time limit `{time}`")))
+}
+
+/// Parse a Hydro memory limit like `256m` or `1g` **in MiB**.
+fn parse_memory(memory: &str) -> Result<u32, ConvertError> {
+    let memory = memory.trim().to_ascii_lowercase();
+    let value = memory.strip_suffix('b').unwrap_or(&memory);
+    let (value, scale) = if let Some(value) = value.strip_suffix('k') {
+        (value, 1.0 / 1024.0)
+    } else if let Some(value) = value.strip_suffix('m') {
+        (value, 1.0)
+    } else if let Some(value) = value.strip_suffix('g') {
+        (value, 1024.0)
+    } else {
+        (value, 1.0)
+    };
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+        .map(|value| (value * scale).ceil() as u32)
+        .ok_or_else(|| ConvertError::InvalidPackage(format!("Invalid memory limit `{memory}`")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::fs;
+
+    use super::{Hydro, HydroSubtask, parse_memory, parse_time, task};
+    use crate::judger::convert::{ConvertError, ProblemFormat as _};
+    use crate::judger::request::cases::{JudgeType, ScorePolicy, TaskType};
+    use crate::judger::score::ScoreError;
+
+    #[test]
+    fn parse_limits() {
+        assert_eq!(parse_time("1s").unwrap(), 1000);
+        assert_eq!(parse_time("1.5s").unwrap(), 1500);
+        assert_eq!(parse_time("500ms").unwrap(), 500);
+        assert_eq!(parse_memory("256m").unwrap(), 256);
+        assert_eq!(parse_memory("512MB").unwrap(), 512);
+        assert_eq!(parse_memory("1g").unwrap(), 1024);
+        assert!(parse_time("fast").is_err());
+    }
+
+    #[test]
+    fn reject_overflowing_scores() -> anyhow::Result<()> {
+        let subtasks: Vec<HydroSubtask> =
+            serde_yaml_ng::from_str("[{ score: 4294967295 }, { score: 1 }]")?;
+        let error = task(subtasks, &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, ConvertError::Score(ScoreError::Overflow)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_and_export() -> anyhow::Result<()> {
+        let src = tempfile::tempdir()?;
+        let imported = tempfile::tempdir()?;
+        let exported = tempfile::tempdir()?;
+        for name in ["1.in", "1.out", "2.in", "2.out", "3.in", "3.out", "chk.cc"] {
+            fs::write(src.path().join(name), name).await?;
+        }
+        fs::write(
+            src.path().join("config.yaml"),
+            r"
+time: 2s
+memory: 512m
+checker_type: testlib
+checker: chk.cc
+filename: sum
+subtasks:
+  - score: 30
+    type: min
+    id: 1
+    cases:
+      - { input: 1.in, output: 1.out }
+  - score: 70
+    type: max
+    id: 2
+    if: [1]
+    cases:
+      - { input: 2.in, output: 2.out }
+      - { input: 3.in, output: 3.out }
+",
+        )
+        .await?;
+
+        let converted = Hydro.import(src.path(), imported.path()).await?;
+        let converted = &converted[0];
+        assert_eq!(
+            converted.warnings,
+            ["Subtask 2 of type `max` is judged by the lowest case score"]
+        );
+        let config = &converted.config;
+        assert_eq!(config.resource_limits().time().as_millis(), 2000);
+        assert_eq!(config.resource_limits().memory(), 512 << 20);
+        assert_eq!(config.io().input_file.as_deref(), Some("sum.in"));
+        assert!(matches!(
+            &config.judge,
+            JudgeType::SpecialJudge { checker } if checker == Path::new("chk.cc")
+        ));
+        let TaskType::Subtask { subtasks } = &config.task else {
+            panic!("expected subtasks");
+        };
+        assert_eq!(subtasks[1].policy, ScorePolicy::Min);
+        assert_eq!(subtasks[1].dependencies, [1]);
+        assert_eq!(
+            fs::read_to_string(imported.path().join("3.out")).await?,
+            "3.out"
+        );
+        assert!(imported.path().join("cases.json").exists());
+
+        let warnings = Hydro
+            .export(config, imported.path(), exported.path())
+            .await?;
+        assert!(warnings.is_empty());
+        let yaml = fs::read_to_string(exported.path().join("config.yaml")).await?;
+        let reimported = tempfile::tempdir()?;
+        let converted = Hydro.import(exported.path(), reimported.path()).await?;
+        assert!(converted[0].warnings.is_empty(), "{yaml}");
+        assert_eq!(converted[0].config.score_plan()?.case_count(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn discover_without_subtasks() -> anyhow::Result<()> {
+        let src = tempfile::tempdir()?;
+        let dst = tempfile::tempdir()?;
+        for name in ["1.in", "1.out", "2.in", "2.out"] {
+            fs::write(src.path().join(name), "").await?;
+        }
+
+        let converted = Hydro.import(src.path(), dst.path()).await?;
+        assert_eq!(converted[0].config.cases().count(), 2);
+        Ok(())
+    }
+}
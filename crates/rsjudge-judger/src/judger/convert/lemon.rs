@@ -0,0 +1,529 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of [Lemon] contests, described by a `.cdf` contest file in the
+//! JSON format of LemonLime, with test data in `data`.
+//!
+//! A contest holds several problems, each imported into a subdirectory named
+//! by its title. A problem is exported as a contest of itself.
+//!
+//! [Lemon]: https://github.com/Project-LemonLime/Project_LemonLime
+
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::judger::convert::{
+    ConvertError, Converted, ProblemFormat, copy_file, export_warnings, write_config,
+};
+use crate::judger::request::cases::{
+    Case, CasesConfig, IoConfig, JudgeType, ResourceLimits, ScorePolicy, Subtask, TaskType,
+};
+
+const DATA_DIR: &str = "data";
+const FORMAT: &str = "Lemon";
+
+/// Task types of Lemon.
+const TRADITIONAL: u8 = 0;
+const ANSWERS_ONLY: u8 = 1;
+
+/// Comparison modes of Lemon.
+const LINE_BY_LINE: u8 = 0;
+const SPECIAL_JUDGE: u8 = 4;
+
+/// Lemon contests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lemon;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Contest {
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    contest_title: String,
+    #[serde(default)]
+    tasks: Vec<Task>,
+    #[serde(default)]
+    contestants: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    problem_title: String,
+    #[serde(default)]
+    source_file_name: String,
+    #[serde(default)]
+    input_file_name: String,
+    #[serde(default)]
+    output_file_name: String,
+    #[serde(default = "default_true")]
+    standard_input_check: bool,
+    #[serde(default = "default_true")]
+    standard_output_check: bool,
+    #[serde(default)]
+    task_type: u8,
+    #[serde(default)]
+    comparison_mode: u8,
+    /// Checker, relative to the data directory.
+    #[serde(default)]
+    special_judge: String,
+    #[serde(default)]
+    test_cases: Vec<TestCase>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TestCase {
+    full_score: u32,
+    /// Time limit **in milliseconds**.
+    time_limit: u32,
+    /// Memory limit **in MiB**.
+    memory_limit: u32,
+    /// Files relative to the data directory.
+    input_files: Vec<String>,
+    output_files: Vec<String>,
+    /// Test cases depended on, starting from 1.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dependence_subtask: Vec<usize>,
+}
+
+impl ProblemFormat for Lemon {
+    async fn import(&self, src: &Path, dst: &Path) -> Result<Vec<Converted>, ConvertError> {
+        let mut contest_files = Vec::new();
+        let mut entries = fs::read_dir(src).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().is_some_and(|ext| ext == "cdf") {
+                contest_files.push(entry.path());
+            }
+        }
+        let [contest_file] = contest_files.as_slice() else {
+            return Err(ConvertError::InvalidPackage(format!(
+                "Expected one contest file, found {}",
+                contest_files.len()
+            )));
+        };
+        let contest: Contest = serde_json::from_slice(&fs::read(contest_file).await?)
+            .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?;
+
+        // Titles name the problem directories, so check them all before writing
+        // anything.
+        let mut titles = HashSet::with_capacity(contest.tasks.len());
+        for task in &contest.tasks {
+            let title = &task.problem_title;
+            if !matches!(
+                Path::new(title).components().collect::<Vec<_>>().as_slice(),
+                [Component::Normal(_)]
+            ) {
+                return Err(ConvertError::InvalidPackage(format!(
+                    "Invalid problem title `{title}`, which must be a single file name"
+                )));
+            }
+            if !titles.insert(title.as_str()) {
+                return Err(ConvertError::InvalidPackage(format!(
+                    "Duplicate problem title `{title}`"
+                )));
+            }
+        }
+
+        let data = src.join(DATA_DIR);
+        let mut problems = Vec::with_capacity(contest.tasks.len());
+        for task in contest.tasks {
+            let dir = dst.join(&task.problem_title);
+            problems.push(import_task(task, &data, dir).await?);
+        }
+        Ok(problems)
+    }
+
+    async fn export(
+        &self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<String>, ConvertError> {
+        if matches!(config.judge, JudgeType::Interactive { .. }) {
+            return Err(ConvertError::Unsupported {
+                format: FORMAT,
+                feature: "Interactive problems".to_owned(),
+            });
+        }
+
+        let mut warnings = export_warnings(config);
+        let plan = config.score_plan()?;
+        let name = dst.file_name().map_or_else(
+            || "problem".to_owned(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let data = dst.join(DATA_DIR);
+        let problem_data = Path::new(&name);
+        let limits = config.resource_limits();
+
+        let mut test_cases = Vec::new();
+        let mut test_case = |cases: &[&Case], score, dependencies| {
+            test_cases.push(TestCase {
+                full_score: score,
+                time_limit: limits.time().as_millis() as u32,
+                memory_limit: (limits.memory() >> 20) as u32,
+                input_files: cases
+                    .iter()
+                    .map(|case| data_path(problem_data, case.input()))
+                    .collect(),
+                output_files: cases
+                    .iter()
+                    .map(|case| data_path(problem_data, case.answer()))
+                    .collect(),
+                dependence_subtask: dependencies,
+            });
+            test_cases.len()
+        };
+        match &config.task {
+            TaskType::Simple { cases } => {
+                let (_, scores) = plan.scores().next().unwrap_or_default();
+                for (case, &score) in cases.iter().zip(scores) {
+                    test_case(&[case], score, Vec::new());
+                }
+            }
+            TaskType::Subtask { subtasks } => {
+                // Test case of each subtask, if not split.
+                let mut indexes = Vec::new();
+                for (number, (subtask, (score, scores))) in subtasks
+                    .iter()
+                    .zip(plan.scores())
+                    .zip(1..)
+                    .map(|(pair, number)| (number, pair))
+                {
+                    let mut dependencies = Vec::new();
+                    for &dependency in &subtask.dependencies {
+                        match indexes.get(dependency - 1).copied().flatten() {
+                            Some(index) => dependencies.push(index),
+                            None => warnings.push(format!(
+                                "Dependency of subtask {number} on subtask {dependency} is dropped"
+                            )),
+                        }
+                    }
+
+                    if subtask.policy == ScorePolicy::Sum && subtask.cases.len() > 1 {
+                        warnings.push(format!("Subtask {number} is split into its cases"));
+                        for (case, &score) in subtask.cases.iter().zip(scores) {
+                            test_case(&[case], score, dependencies.clone());
+                        }
+                        indexes.push(None);
+                    } else {
+                        if subtask.policy == ScorePolicy::AllOrNothing {
+                            warnings.push(format!(
+                                "All-or-nothing subtask {number} is judged by the lowest case score"
+                            ));
+                        }
+                        let cases: Vec<_> = subtask.cases.iter().collect();
+                        indexes.push(Some(test_case(&cases, score, dependencies)));
+                    }
+                }
+            }
+        }
+
+        let cases: Vec<_> = config.cases().collect();
+        for case in cases {
+            for file in [case.input(), case.answer()] {
+                copy_file(src, file, &data, &problem_data.join(file)).await?;
+            }
+        }
+
+        let mut task = Task {
+            problem_title: name.clone(),
+            source_file_name: name.clone(),
+            input_file_name: config
+                .io()
+                .input_file
+                .clone()
+                .unwrap_or_else(|| format!("{name}.in")),
+            output_file_name: config
+                .io()
+                .output_file
+                .clone()
+                .unwrap_or_else(|| format!("{name}.out")),
+            standard_input_check: config.io().input_file.is_none(),
+            standard_output_check: config.io().output_file.is_none(),
+            task_type: if config.is_output_only() {
+                ANSWERS_ONLY
+            } else {
+                TRADITIONAL
+            },
+            comparison_mode: LINE_BY_LINE,
+            special_judge: String::new(),
+            test_cases,
+        };
+        if config.io().stdin {
+            warnings.push("Input on stdin along with the input file is dropped".to_owned());
+        }
+        if let JudgeType::SpecialJudge { checker } = &config.judge {
+            warnings.push(
+                "Checker is used as a Lemon special judge, which has another protocol".to_owned(),
+            );
+            copy_file(src, checker, &data, &problem_data.join(checker)).await?;
+            task.comparison_mode = SPECIAL_JUDGE;
+            task.special_judge = data_path(problem_data, checker);
+        }
+
+        let contest = Contest {
+            version: "1.0".to_owned(),
+            contest_title: name.clone(),
+            tasks: vec![task],
+            contestants: Vec::new(),
+        };
+        fs::create_dir_all(dst).await?;
+        fs::write(
+            dst.join(format!("{name}.cdf")),
+            serde_json::to_vec_pretty(&contest).map_err(std::io::Error::from)?,
+        )
+        .await?;
+        Ok(warnings)
+    }
+}
+
+/// Import a task of a contest with test data in `data` into `dir`.
+async fn import_task(task: Task, data: &Path, dir: PathBuf) -> Result<Converted, ConvertError> {
+    let mut warnings = Vec::new();
+    let title = &task.problem_title;
+
+    let mut output_only = false;
+    let judge = match (task.task_type, task.comparison_mode) {
+        (TRADITIONAL | ANSWERS_ONLY, SPECIAL_JUDGE) => {
+            warnings.push(
+                "Lemon special judge is used as a testlib checker, which has another protocol"
+                    .to_owned(),
+            );
+            let checker = PathBuf::from(&task.special_judge);
+            copy_file(data, &checker, &dir, &checker).await?;
+            JudgeType::SpecialJudge { checker }
+        }
+        (TRADITIONAL | ANSWERS_ONLY, mode) => {
+            if mode != LINE_BY_LINE {
+                warnings.push(format!(
+                    "Comparison mode {mode} is replaced by the default comparison"
+                ));
+            }
+            JudgeType::Classic
+        }
+        (task_type, _) => {
+            return Err(ConvertError::Unsupported {
+                format: "rsjudge",
+                feature: format!("Task type {task_type} of `{title}`"),
+            });
+        }
+    };
+    if task.task_type == ANSWERS_ONLY {
+        output_only = true;
+    }
+
+    let time = task
+        .test_cases
+        .iter()
+        .map(|test_case| test_case.time_limit)
+        .max();
+    let memory = task
+        .test_cases
+        .iter()
+        .map(|test_case| test_case.memory_limit)
+        .max();
+    if task.test_cases.iter().any(|test_case| {
+        Some(test_case.time_limit) != time || Some(test_case.memory_limit) != memory
+    }) {
+        warnings.push("Limits differ among test cases, the largest ones are used".to_owned());
+    }
+
+    let mut subtasks = Vec::with_capacity(task.test_cases.len());
+    for (test_case, number) in task.test_cases.into_iter().zip(1..) {
+        if test_case.input_files.len() != test_case.output_files.len() {
+            return Err(ConvertError::InvalidPackage(format!(
+                "Inputs and outputs of test case {number} in `{title}` do not match"
+            )));
+        }
+        let mut cases = Vec::with_capacity(test_case.input_files.len());
+        for (input, output) in test_case
+            .input_files
+            .into_iter()
+            .zip(test_case.output_files)
+        {
+            for file in [&input, &output] {
+                copy_file(data, Path::new(file), &dir, Path::new(file)).await?;
+            }
+            cases.push(Case::new(input, output));
+        }
+
+        let dependencies = test_case
+            .dependence_subtask
+            .into_iter()
+            .filter(|&dependency| {
+                let valid = (1..number).contains(&dependency);
+                if !valid {
+                    warnings.push(format!(
+                        "Dependency of test case {number} on test case {dependency} is dropped"
+                    ));
+                }
+                valid
+            })
+            .collect();
+
+        subtasks.push(Subtask {
+            cases,
+            score: NonZeroU32::new(test_case.full_score),
+            policy: ScorePolicy::Min,
+            dependencies,
+        });
+    }
+
+    let score = subtasks
+        .iter()
+        .filter_map(|subtask| subtask.score)
+        .map(NonZeroU32::get)
+        .sum();
+    let mut config = CasesConfig::new(
+        NonZeroU32::new(score).unwrap_or(NonZeroU32::new(100).unwrap()),
+        judge,
+        ResourceLimits::new(time.unwrap_or(1000), memory.unwrap_or(256)),
+        TaskType::Subtask { subtasks },
+    );
+    config.output_only = output_only;
+    config.io = IoConfig {
+        input_file: (!task.standard_input_check).then_some(task.input_file_name),
+        output_file: (!task.standard_output_check).then_some(task.output_file_name),
+        stdin: false,
+    };
+
+    write_config(&config, &dir).await?;
+    Ok(Converted {
+        dir,
+        config,
+        warnings,
+    })
+}
+
+/// Get the path of `file` of a problem in the data directory, using `/` as
+/// Lemon does.
+fn data_path(problem_data: &Path, file: &Path) -> String {
+    problem_data.join(file).to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::{Value, json};
+    use tokio::fs;
+
+    use super::Lemon;
+    use crate::judger::convert::{ConvertError, ProblemFormat as _};
+    use crate::judger::request::cases::{ScorePolicy, TaskType};
+
+    #[tokio::test]
+    async fn import_and_export() -> anyhow::Result<()> {
+        let src = tempfile::tempdir()?;
+        let imported = tempfile::tempdir()?;
+        let exported = tempfile::tempdir()?;
+        for name in [
+            "a/a1.in", "a/a1.out", "a/a2.in", "a/a2.out", "a/a3.in", "a/a3.out",
+        ] {
+            let path = src.path().join("data").join(name);
+            fs::create_dir_all(path.parent().unwrap()).await?;
+            fs::write(path, name).await?;
+        }
+        let contest = json!({
+            "version": "1.0",
+            "contestTitle": "day1",
+            "tasks": [{
+                "problemTitle": "a",
+                "sourceFileName": "a",
+                "inputFileName": "a.in",
+                "outputFileName": "a.out",
+                "standardInputCheck": false,
+                "standardOutputCheck": false,
+                "taskType": 0,
+                "comparisonMode": 1,
+                "testCases": [
+                    {
+                        "fullScore": 40,
+                        "timeLimit": 1000,
+                        "memoryLimit": 256,
+                        "inputFiles": ["a/a1.in"],
+                        "outputFiles": ["a/a1.out"]
+                    },
+                    {
+                        "fullScore": 60,
+                        "timeLimit": 1000,
+                        "memoryLimit": 256,
+                        "inputFiles": ["a/a2.in", "a/a3.in"],
+                        "outputFiles": ["a/a2.out", "a/a3.out"],
+                        "dependenceSubtask": [1]
+                    }
+                ]
+            }],
+            "contestants": []
+        });
+        fs::write(src.path().join("day1.cdf"), serde_json::to_vec(&contest)?).await?;
+
+        let converted = Lemon.import(src.path(), imported.path()).await?;
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0].dir, imported.path().join("a"));
+        assert_eq!(
+            converted[0].warnings,
+            ["Comparison mode 1 is replaced by the default comparison"]
+        );
+        let config = &converted[0].config;
+        assert_eq!(config.io().input_file.as_deref(), Some("a.in"));
+        let TaskType::Subtask { subtasks } = &config.task else {
+            panic!("expected subtasks");
+        };
+        assert_eq!(subtasks[1].cases[1].input(), Path::new("a/a3.in"));
+        assert_eq!(subtasks[1].policy, ScorePolicy::Min);
+        assert_eq!(subtasks[1].dependencies, [1]);
+
+        let dst = exported.path().join("b");
+        let warnings = Lemon.export(config, &converted[0].dir, &dst).await?;
+        assert!(warnings.is_empty());
+        let contest: Value = serde_json::from_slice(&fs::read(dst.join("b.cdf")).await?)?;
+        assert_eq!(contest["tasks"][0]["problemTitle"], "b");
+        assert_eq!(
+            contest["tasks"][0]["testCases"][1]["inputFiles"],
+            json!(["b/a/a2.in", "b/a/a3.in"])
+        );
+        assert_eq!(
+            contest["tasks"][0]["testCases"][1]["dependenceSubtask"],
+            json!([1])
+        );
+        assert!(dst.join("data/b/a/a3.out").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_titles() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let src = root.path().join("src");
+        let dst = root.path().join("dst");
+        fs::create_dir_all(&src).await?;
+
+        let task = |title: &str| json!({ "problemTitle": title, "testCases": [] });
+        for tasks in [
+            json!([task("../x")]),
+            json!([task("/x")]),
+            json!([task("")]),
+            json!([task("a"), task("a")]),
+        ] {
+            let contest = json!({ "tasks": tasks });
+            fs::write(src.join("contest.cdf"), serde_json::to_vec(&contest)?).await?;
+            assert!(matches!(
+                Lemon.import(&src, &dst).await,
+                Err(ConvertError::InvalidPackage(_))
+            ));
+        }
+        assert!(!root.path().join("x").exists());
+        assert!(!dst.exists());
+        Ok(())
+    }
+}
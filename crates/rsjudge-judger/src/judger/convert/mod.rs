@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of problems between [`CasesConfig`] and other formats.
+//!
+//! Each format implements [`ProblemFormat`], and is listed in [`Format`] to
+//! be available by name, e.g. in the CLI. Polygon packages are imported with
+//! [`polygon`] instead, as missing tests are produced by programs compiled
+//! with the languages of the judger.
+//!
+//! Features that cannot be represented in the target format are dropped with
+//! a warning, instead of failing the conversion.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use rsjudge_traits::judger::JudgeResult;
+use thiserror::Error;
+use tokio::fs;
+
+use crate::judger::compile::CompileError;
+use crate::judger::request::cases::{CASES_CONFIG_FILE, CasesConfig, CompareConfig, JudgeType};
+use crate::judger::request::source::{Code, SourceError};
+use crate::judger::score::ScoreError;
+
+pub mod domjudge;
+pub mod hydro;
+pub mod lemon;
+pub mod polygon;
+pub mod qduoj;
+
+/// Error converting a problem.
+///
+/// Tests are numbered from 1.
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error("Unknown problem format `{0}`")]
+    UnknownFormat(String),
+    #[error("Invalid package: {0}")]
+    InvalidPackage(String),
+    #[error("{feature} is not supported by {format}")]
+    Unsupported {
+        format: &'static str,
+        feature: String,
+    },
+    #[error(transparent)]
+    Archive(#[from] SourceError),
+    #[error("Missing file `{0}` in package")]
+    MissingFile(String),
+    #[error("Unknown generator `{generator}` of test {test}")]
+    UnknownGenerator { test: usize, generator: String },
+    #[error("Generator `{cmd}` of test {test} failed with {result:?}: {message}")]
+    Generator {
+        test: usize,
+        cmd: String,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error("Unknown language of `{path}` with source type `{source_type}`")]
+    UnknownLanguage { path: String, source_type: String },
+    #[error("Failed to compile `{path}`: {message}")]
+    CompileFailed { path: String, message: String },
+    #[error(transparent)]
+    Compile(#[from] CompileError),
+    #[error("Answer of test {test} is missing, and no solution is provided")]
+    MissingSolution { test: usize },
+    #[error("Solution failed on test {test} with {result:?}: {message}")]
+    Solution {
+        test: usize,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error(transparent)]
+    Score(#[from] ScoreError),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A problem converted into a [`CasesConfig`].
+#[derive(Debug)]
+pub struct Converted {
+    /// The problem directory, holding the config in [`CASES_CONFIG_FILE`].
+    pub dir: PathBuf,
+    pub config: CasesConfig,
+    /// Features of the source format dropped in the conversion.
+    pub warnings: Vec<String>,
+}
+
+/// A problem format converted to and from [`CasesConfig`].
+pub trait ProblemFormat {
+    /// Import the problems at `src` into `dst`, copying the files they use.
+    ///
+    /// Formats holding several problems import each of them into a
+    /// subdirectory of `dst`.
+    fn import(
+        &self,
+        src: &Path,
+        dst: &Path,
+    ) -> impl Future<Output = Result<Vec<Converted>, ConvertError>> + Send;
+
+    /// Export the problem `config` in `src` into `dst`.
+    ///
+    /// Returns warnings of the features dropped.
+    fn export(
+        &self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> impl Future<Output = Result<Vec<String>, ConvertError>> + Send;
+}
+
+/// Problem formats available by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// [`hydro::Hydro`]
+    Hydro,
+    /// [`qduoj::Qduoj`]
+    Qduoj,
+    /// [`domjudge::Domjudge`]
+    Domjudge,
+    /// [`lemon::Lemon`]
+    Lemon,
+}
+
+impl Format {
+    /// Get the name of the format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hydro => "hydro",
+            Self::Qduoj => "qduoj",
+            Self::Domjudge => "domjudge",
+            Self::Lemon => "lemon",
+        }
+    }
+
+    /// Import the problems at `src` into `dst` with [`ProblemFormat::import`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the problems are invalid, or use features that
+    /// cannot be imported.
+    pub async fn import(self, src: &Path, dst: &Path) -> Result<Vec<Converted>, ConvertError> {
+        match self {
+            Self::Hydro => hydro::Hydro.import(src, dst).await,
+            Self::Qduoj => qduoj::Qduoj.import(src, dst).await,
+            Self::Domjudge => domjudge::Domjudge.import(src, dst).await,
+            Self::Lemon => lemon::Lemon.import(src, dst).await,
+        }
+    }
+
+    /// Export the problem `config` in `src` into `dst` with
+    /// [`ProblemFormat::export`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the problem uses features that cannot be exported.
+    pub async fn export(
+        self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<String>, ConvertError> {
+        match self {
+            Self::Hydro => hydro::Hydro.export(config, src, dst).await,
+            Self::Qduoj => qduoj::Qduoj.export(config, src, dst).await,
+            Self::Domjudge => domjudge::Domjudge.export(config, src, dst).await,
+            Self::Lemon => lemon::Lemon.export(config, src, dst).await,
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = ConvertError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        [Self::Hydro, Self::Qduoj, Self::Domjudge, Self::Lemon]
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| ConvertError::UnknownFormat(name.to_owned()))
+    }
+}
+
+/// Extract all files of the zip or tar `archive` into `dir`.
+///
+/// # Errors
+///
+/// Returns an error if the archive is invalid, contains unsafe paths, or is
+/// larger than `limit` bytes when extracted.
+pub(crate) async fn extract(
+    archive: &[u8],
+    limit: u64,
+    dir: &Path,
+) -> Result<HashSet<String>, ConvertError> {
+    let code = Code::from_archive(archive, limit)?;
+    code.write_to(dir, "").await?;
+
+    let Code::Files(files) = code else {
+        unreachable!("archives are extracted into multiple files");
+    };
+    Ok(files.iter().map(|file| file.path().to_owned()).collect())
+}
+
+/// Write `config` into `dir` as [`CASES_CONFIG_FILE`], after validating its
+/// scores.
+///
+/// # Errors
+///
+/// Returns an error if the scores are invalid, or the file cannot be written.
+pub(crate) async fn write_config(config: &CasesConfig, dir: &Path) -> Result<(), ConvertError> {
+    config.score_plan()?;
+    fs::write(
+        dir.join(CASES_CONFIG_FILE),
+        serde_json::to_vec_pretty(config).map_err(io::Error::from)?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Copy the file at `from` in `src` to `to` in `dst`, creating parent
+/// directories.
+///
+/// # Errors
+///
+/// Returns an error if a path is not relative inside its directory, or the
+/// file is missing.
+pub(crate) async fn copy_file(
+    src: &Path,
+    from: &Path,
+    dst: &Path,
+    to: &Path,
+) -> Result<(), ConvertError> {
+    for path in [from, to] {
+        let is_relative = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            return Err(ConvertError::InvalidPackage(format!(
+                "Invalid path `{}`, only relative paths inside the problem are allowed",
+                path.display()
+            )));
+        }
+    }
+
+    let target = dst.join(to);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    match fs::copy(src.join(from), target).await {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Err(ConvertError::MissingFile(
+            from.to_string_lossy().into_owned(),
+        )),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Copy all files used by `config` from `src` to the same paths in `dst`.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be copied.
+pub(crate) async fn copy_problem_files(
+    config: &CasesConfig,
+    src: &Path,
+    dst: &Path,
+) -> Result<(), ConvertError> {
+    let mut files: Vec<&Path> = config
+        .cases()
+        .flat_map(|case| [case.input(), case.answer()])
+        .collect();
+    files.extend(judge_program(&config.judge));
+    for grader in config.graders.values() {
+        files.extend(
+            grader
+                .sources
+                .iter()
+                .chain(&grader.headers)
+                .map(PathBuf::as_path),
+        );
+    }
+    files.extend(config.validator().map(|validator| validator.source()));
+
+    for file in files {
+        copy_file(src, file, dst, file).await?;
+    }
+    Ok(())
+}
+
+/// Get the checker or interactor of a problem, if any.
+pub(crate) fn judge_program(judge: &JudgeType) -> Option<&Path> {
+    match judge {
+        JudgeType::Classic => None,
+        JudgeType::SpecialJudge { checker } => Some(checker),
+        JudgeType::Interactive { interactor } => Some(interactor),
+    }
+}
+
+/// Warn about features of `config` that none of the formats can represent.
+pub(crate) fn export_warnings(config: &CasesConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if config.validator().is_some() {
+        warnings.push("Input validator is dropped".to_owned());
+    }
+    if !config.graders.is_empty() {
+        warnings.push("Graders are dropped".to_owned());
+    }
+    if config.compare != CompareConfig::default() {
+        warnings.push("Comparison options are dropped".to_owned());
+    }
+    if config.resource_limits().has_extra_limits() {
+        warnings.push("Limits other than the time and memory limits are dropped".to_owned());
+    }
+    warnings
+}
@@ -0,0 +1,979 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Import of [Polygon] problem packages.
+//!
+//! A package is extracted into the problem directory, and its `problem.xml`
+//! is mapped into a [`CasesConfig`]:
+//!
+//! - groups of tests into [`Subtask`]s with points,
+//! - the checker into [`JudgeType::SpecialJudge`], and the interactor into
+//!   [`JudgeType::Interactive`],
+//! - time and memory limits into [`ResourceLimits`].
+//!
+//! Generated tests missing from the package are produced by running the
+//! generators of the package, and missing answers by running the main
+//! solution, all as sandboxed [`Program`]s. [`PolygonPackage::compile`]
+//! compiles them as the builder user with the languages of the judger.
+//!
+//! [Polygon]: https://polygon.codeforces.com
+
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU32;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use roxmltree::{Document, Node};
+use rsjudge_traits::language::config::LanguageDef;
+use rsjudge_traits::language::template::TemplateVars;
+use rsjudge_traits::resource::ResourceLimit;
+
+use crate::judger::compile::{CompileError, compile_source};
+use crate::judger::convert::{ConvertError, Converted, extract, write_config};
+use crate::judger::request::cases::{
+    Case, CasesConfig, InputValidator, IoConfig, JudgeType, ResourceLimits, ScorePolicy, Subtask,
+    TaskType,
+};
+use crate::judger::run::{Program, stdin};
+use crate::judger::score::ScoreError;
+
+/// Name of the problem descriptor in a package.
+const PROBLEM_XML: &str = "problem.xml";
+
+/// A source file in a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolygonSource {
+    /// Path relative to the problem directory.
+    pub path: String,
+    /// Polygon source type, e.g. `cpp.g++17`.
+    pub source_type: String,
+}
+
+impl PolygonSource {
+    /// Get the name of the source, i.e. its file stem, as used in generator
+    /// commands.
+    pub fn name(&self) -> &str {
+        Path::new(&self.path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+    }
+
+    /// Get the name of the language of the source, if known.
+    pub fn language(&self) -> Option<&'static str> {
+        let source_type = self.source_type.as_str();
+        let (family, _) = source_type.split_once('.').unwrap_or((source_type, ""));
+        Some(match family {
+            "c" => "C",
+            "cpp" => "C++",
+            "csharp" => "C#",
+            "go" => "Go",
+            "java8" | "java11" | "java17" | "java21" | "java" => "Java",
+            "kotlin" => "Kotlin",
+            "python" | "python2" | "python3" | "pypy3" => "Python",
+            "rust" => "Rust",
+            _ => return None,
+        })
+    }
+}
+
+/// Compiled programs of a package, producing tests missing from it.
+///
+/// The programs come from the untrusted package, so they must be sandboxed,
+/// as those from [`PolygonPackage::compile`] are.
+#[derive(Debug, Clone, Copy)]
+pub struct PolygonPrograms<'a> {
+    /// Generators by [name](PolygonSource::name).
+    pub generators: &'a HashMap<String, Program>,
+    /// The main solution, whose output is taken as the answer.
+    pub solution: Option<&'a Program>,
+}
+
+/// Programs compiled by [`PolygonPackage::compile`].
+#[derive(Debug, Default)]
+pub struct CompiledPrograms {
+    /// Generators by [name](PolygonSource::name).
+    pub generators: HashMap<String, Program>,
+    /// The main solution.
+    pub solution: Option<Program>,
+}
+
+impl CompiledPrograms {
+    /// Borrow the programs for [`PolygonPackage::import`].
+    pub fn programs(&self) -> PolygonPrograms<'_> {
+        PolygonPrograms {
+            generators: &self.generators,
+            solution: self.solution.as_ref(),
+        }
+    }
+}
+
+/// Import the package zip `archive` into `problem_dir` end to end, compiling
+/// its programs with [`PolygonPackage::compile`] if any test or answer is
+/// missing from it.
+///
+/// # Errors
+///
+/// See [`PolygonPackage::extract`], [`PolygonPackage::compile`] and
+/// [`PolygonPackage::import`].
+pub async fn import_package(
+    archive: &[u8],
+    size_limit: u64,
+    problem_dir: &Path,
+    languages: &HashMap<String, LanguageDef>,
+    vars: &TemplateVars,
+    build_dir: &Path,
+    limit: ResourceLimit,
+) -> Result<Converted, ConvertError> {
+    let package = PolygonPackage::extract(archive, size_limit, problem_dir).await?;
+    let compiled = if package.needs_programs() {
+        package.compile(languages, vars, build_dir, limit).await?
+    } else {
+        CompiledPrograms::default()
+    };
+    package.import(compiled.programs()).await
+}
+
+/// A test in `problem.xml`.
+#[derive(Debug)]
+struct Test {
+    /// Generator command, for generated tests.
+    cmd: Option<String>,
+    group: Option<String>,
+    points: u32,
+}
+
+/// A group of tests in `problem.xml`.
+#[derive(Debug)]
+struct Group {
+    name: String,
+    points: u32,
+    /// Whether points are given only if all tests pass.
+    complete: bool,
+    dependencies: Vec<String>,
+}
+
+/// An extracted Polygon package.
+#[derive(Debug)]
+pub struct PolygonPackage {
+    dir: PathBuf,
+    files: HashSet<String>,
+    time_limit: u32,
+    memory_limit: u32,
+    input_file: Option<String>,
+    output_file: Option<String>,
+    input_pattern: String,
+    answer_pattern: String,
+    tests: Vec<Test>,
+    groups: Vec<Group>,
+    checker: Option<PolygonSource>,
+    interactor: Option<PolygonSource>,
+    validators: Vec<PolygonSource>,
+    executables: Vec<PolygonSource>,
+    main_solution: Option<PolygonSource>,
+    warnings: Vec<String>,
+}
+
+impl PolygonPackage {
+    /// Extract the package zip `archive` into `problem_dir`, and read its
+    /// `problem.xml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive is invalid or larger than `limit`
+    /// bytes when extracted, or `problem.xml` is missing or invalid.
+    pub async fn extract(
+        archive: &[u8],
+        limit: u64,
+        problem_dir: &Path,
+    ) -> Result<Self, ConvertError> {
+        let files = extract(archive, limit, problem_dir).await?;
+        if !files.contains(PROBLEM_XML) {
+            return Err(ConvertError::MissingFile(PROBLEM_XML.to_owned()));
+        }
+        let xml = tokio::fs::read_to_string(problem_dir.join(PROBLEM_XML)).await?;
+        Self::parse(&xml, problem_dir.to_path_buf(), files)
+    }
+
+    fn parse(xml: &str, dir: PathBuf, files: HashSet<String>) -> Result<Self, ConvertError> {
+        let document = Document::parse(xml)
+            .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?;
+        let problem = document.root_element();
+        let judging = child(problem, "judging")?;
+        let mut warnings = Vec::new();
+
+        let testsets: Vec<_> = judging
+            .children()
+            .filter(|node| node.has_tag_name("testset"))
+            .collect();
+        let testset = testsets
+            .iter()
+            .find(|testset| testset.attribute("name") == Some("tests"))
+            .or_else(|| testsets.first())
+            .copied()
+            .ok_or_else(|| missing("testset"))?;
+        if testsets.len() > 1 {
+            warnings.push(format!(
+                "Only testset `{}` is imported",
+                testset.attribute("name").unwrap_or_default()
+            ));
+        }
+
+        let time_limit = parse::<u32>("time-limit", child_text(testset, "time-limit")?)?;
+        let memory_limit =
+            parse::<u64>("memory-limit", child_text(testset, "memory-limit")?)?.div_ceil(1 << 20);
+        if let Some(run_count) = judging.attribute("run-count").filter(|&count| count != "1") {
+            warnings.push(format!("Run count {run_count} is ignored"));
+        }
+
+        let tests = match testset.children().find(|node| node.has_tag_name("tests")) {
+            Some(tests) => tests
+                .children()
+                .filter(|node| node.has_tag_name("test"))
+                .map(|test| {
+                    let generated = test.attribute("method") == Some("generated");
+                    Ok(Test {
+                        cmd: generated
+                            .then(|| test.attribute("cmd").map(str::to_owned))
+                            .flatten(),
+                        group: test.attribute("group").map(str::to_owned),
+                        points: points(test, &mut warnings)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, ConvertError>>()?,
+            None => Vec::new(),
+        };
+
+        let groups = match testset.children().find(|node| node.has_tag_name("groups")) {
+            Some(groups) => groups
+                .children()
+                .filter(|node| node.has_tag_name("group"))
+                .map(|group| {
+                    Ok(Group {
+                        name: group.attribute("name").unwrap_or_default().to_owned(),
+                        points: points(group, &mut warnings)?,
+                        complete: group.attribute("points-policy") == Some("complete-group"),
+                        dependencies: group
+                            .descendants()
+                            .filter(|node| node.has_tag_name("dependency"))
+                            .filter_map(|node| node.attribute("group"))
+                            .map(str::to_owned)
+                            .collect(),
+                    })
+                })
+                .collect::<Result<Vec<_>, ConvertError>>()?,
+            None => Vec::new(),
+        };
+
+        let assets = problem.children().find(|node| node.has_tag_name("assets"));
+        let asset = |name: &str| {
+            assets
+                .and_then(|assets| assets.children().find(|node| node.has_tag_name(name)))
+                .and_then(source)
+        };
+        let validators = assets
+            .into_iter()
+            .flat_map(|assets| assets.descendants())
+            .filter(|node| node.has_tag_name("validator"))
+            .filter_map(source)
+            .collect();
+        let main_solution = assets
+            .into_iter()
+            .flat_map(|assets| assets.descendants())
+            .filter(|node| node.has_tag_name("solution"))
+            .find(|node| node.attribute("tag") == Some("main"))
+            .and_then(source);
+        let executables = problem
+            .descendants()
+            .filter(|node| node.has_tag_name("executable"))
+            .filter_map(source)
+            .collect();
+
+        let input_pattern = child_text(testset, "input-path-pattern")?;
+        let answer_pattern = child_text(testset, "answer-path-pattern")?;
+        for (name, pattern) in [
+            ("input-path-pattern", input_pattern),
+            ("answer-path-pattern", answer_pattern),
+        ] {
+            relative_path(name, &format_index(pattern, 1))?;
+        }
+
+        let package = Self {
+            dir,
+            files,
+            time_limit,
+            memory_limit: u32::try_from(memory_limit).unwrap_or(u32::MAX),
+            input_file: judging
+                .attribute("input-file")
+                .filter(|file| !file.is_empty())
+                .map(str::to_owned),
+            output_file: judging
+                .attribute("output-file")
+                .filter(|file| !file.is_empty())
+                .map(str::to_owned),
+            input_pattern: input_pattern.to_owned(),
+            answer_pattern: answer_pattern.to_owned(),
+            tests,
+            groups,
+            checker: asset("checker"),
+            interactor: asset("interactor"),
+            validators,
+            executables,
+            main_solution,
+            warnings,
+        };
+        for source in package.sources() {
+            relative_path("source path", &source.path)?;
+        }
+        Ok(package)
+    }
+
+    /// Iterate over all sources of the package.
+    fn sources(&self) -> impl Iterator<Item = &PolygonSource> {
+        self.checker
+            .iter()
+            .chain(&self.interactor)
+            .chain(&self.validators)
+            .chain(&self.executables)
+            .chain(&self.main_solution)
+    }
+
+    /// Get the executables of the package, including generators.
+    pub fn executables(&self) -> &[PolygonSource] {
+        &self.executables
+    }
+
+    /// Get the main solution of the package, if any.
+    pub fn main_solution(&self) -> Option<&PolygonSource> {
+        self.main_solution.as_ref()
+    }
+
+    /// Whether tests or answers missing from the package need to be produced
+    /// by [`PolygonPrograms`].
+    pub fn needs_programs(&self) -> bool {
+        (1..=self.tests.len()).any(|test| {
+            !self
+                .files
+                .contains(&format_index(&self.input_pattern, test))
+                || !self
+                    .files
+                    .contains(&format_index(&self.answer_pattern, test))
+        })
+    }
+
+    /// Compile the executables and the main solution of the package as the
+    /// builder user, each in its own directory in `build_dir` named after its
+    /// path, into sandboxed programs running with `limit`.
+    ///
+    /// `languages` are the languages of the judger by name, and `vars` are
+    /// the template variables of the language options, e.g. `flags`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if two executables have the same
+    /// [name](PolygonSource::name), the language of a source is unknown, or a
+    /// source fails to compile.
+    pub async fn compile(
+        &self,
+        languages: &HashMap<String, LanguageDef>,
+        vars: &TemplateVars,
+        build_dir: &Path,
+        limit: ResourceLimit,
+    ) -> Result<CompiledPrograms, ConvertError> {
+        let mut compiled = CompiledPrograms::default();
+        let mut names = HashSet::with_capacity(self.executables.len());
+        for source in &self.executables {
+            if !names.insert(source.name()) {
+                return Err(ConvertError::InvalidPackage(format!(
+                    "Duplicate generator `{}`",
+                    source.name()
+                )));
+            }
+        }
+        for source in &self.executables {
+            let program = self
+                .compile_source(source, languages, vars, build_dir, limit)
+                .await?;
+            compiled
+                .generators
+                .insert(source.name().to_owned(), program);
+        }
+        if let Some(solution) = &self.main_solution {
+            compiled.solution = Some(
+                self.compile_source(solution, languages, vars, build_dir, limit)
+                    .await?,
+            );
+        }
+        Ok(compiled)
+    }
+
+    /// Compile `source` into `build_dir/<path>/<name>`.
+    async fn compile_source(
+        &self,
+        source: &PolygonSource,
+        languages: &HashMap<String, LanguageDef>,
+        vars: &TemplateVars,
+        build_dir: &Path,
+        limit: ResourceLimit,
+    ) -> Result<Program, ConvertError> {
+        let unknown = || ConvertError::UnknownLanguage {
+            path: source.path.clone(),
+            source_type: source.source_type.clone(),
+        };
+        let language = source
+            .language()
+            .and_then(|name| languages.get(name))
+            .ok_or_else(unknown)?;
+        let file_name = Path::new(&source.path).file_name().ok_or_else(unknown)?;
+
+        let name = source.name();
+        let work_dir = build_dir.join(&source.path);
+        tokio::fs::create_dir_all(&work_dir).await?;
+        tokio::fs::copy(self.dir.join(&source.path), work_dir.join(file_name)).await?;
+
+        let mut vars = vars.clone();
+        vars.set("src_file", file_name.to_string_lossy())
+            .set("out_file", name)
+            .set("grader_files", "")
+            .set_limits(&limit);
+        let info =
+            compile_source(language, false, &vars, &work_dir, &[Path::new(name)], None).await?;
+        if let Some(info) = info.filter(|info| !info.is_success()) {
+            return Err(ConvertError::CompileFailed {
+                path: source.path.clone(),
+                message: format!(
+                    "{}\n{}",
+                    info.status(),
+                    String::from_utf8_lossy(info.stderr())
+                ),
+            });
+        }
+
+        Ok(Program::from_language(language, &vars, work_dir, limit).map_err(CompileError::from)?)
+    }
+
+    /// Produce tests missing from the package with `programs`, and write the
+    /// resulting [`CasesConfig`] into the problem directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a test cannot be produced, or the points are
+    /// inconsistent.
+    pub async fn import(self, programs: PolygonPrograms<'_>) -> Result<Converted, ConvertError> {
+        let mut warnings = self.warnings;
+        let mut cases = Vec::with_capacity(self.tests.len());
+
+        for (index, test) in self.tests.iter().enumerate() {
+            let id = index + 1;
+            let input = format_index(&self.input_pattern, id);
+            let answer = format_index(&self.answer_pattern, id);
+            let input_path = self.dir.join(&input);
+            let answer_path = self.dir.join(&answer);
+            if let Some(parent) = input_path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+
+            if !self.files.contains(&input) {
+                let Some(cmd) = &test.cmd else {
+                    return Err(ConvertError::MissingFile(input));
+                };
+                let mut args = cmd.split_whitespace().map(str::to_owned);
+                let name = args.next().unwrap_or_default();
+                let Some(generator) = programs.generators.get(&name) else {
+                    return Err(ConvertError::UnknownGenerator {
+                        test: id,
+                        generator: name,
+                    });
+                };
+                let info = generator
+                    .run(&args.collect::<Vec<_>>(), Stdio::null(), &input_path)
+                    .await?;
+                if let Some(result) = info.result() {
+                    return Err(ConvertError::Generator {
+                        test: id,
+                        cmd: cmd.clone(),
+                        result,
+                        message: info.message(),
+                    });
+                }
+            }
+
+            if !self.files.contains(&answer) {
+                let Some(solution) = programs.solution else {
+                    return Err(ConvertError::MissingSolution { test: id });
+                };
+                let info = solution
+                    .run(&[], stdin(&input_path).await?, &answer_path)
+                    .await?;
+                if let Some(result) = info.result() {
+                    return Err(ConvertError::Solution {
+                        test: id,
+                        result,
+                        message: info.message(),
+                    });
+                }
+            }
+
+            cases.push(Case::new(input, answer));
+        }
+
+        let judge = match (self.interactor, self.checker) {
+            (Some(interactor), checker) => {
+                if checker.is_some() {
+                    warnings.push("Checker of the interactive problem is ignored".to_owned());
+                }
+                JudgeType::Interactive {
+                    interactor: interactor.path.into(),
+                }
+            }
+            (None, Some(checker)) => JudgeType::SpecialJudge {
+                checker: checker.path.into(),
+            },
+            (None, None) => JudgeType::Classic,
+        };
+
+        let (score, task) = task(&self.tests, &self.groups, cases, &mut warnings)?;
+        let mut config = CasesConfig::new(
+            score,
+            judge,
+            ResourceLimits::new(self.time_limit, self.memory_limit),
+            task,
+        );
+        config.io = IoConfig {
+            input_file: self.input_file,
+            output_file: self.output_file,
+            stdin: false,
+        };
+
+        let mut validators = self.validators.into_iter();
+        if let Some(validator) = validators.next() {
+            match validator.language() {
+                Some(language) => {
+                    config.validator = Some(InputValidator {
+                        source: validator.path.into(),
+                        language: language.to_owned(),
+                    });
+                }
+                None => warnings.push(format!(
+                    "Validator `{}` of unknown type `{}` is ignored",
+                    validator.path, validator.source_type
+                )),
+            }
+        }
+        for validator in validators {
+            warnings.push(format!("Extra validator `{}` is ignored", validator.path));
+        }
+
+        write_config(&config, &self.dir).await?;
+        Ok(Converted {
+            dir: self.dir,
+            config,
+            warnings,
+        })
+    }
+}
+
+/// Map tests into cases, grouped into subtasks if any test has a group.
+///
+/// Returns the total score and the task. Tests and groups without points are
+/// dropped if others have points, as zero scores cannot be represented.
+///
+/// Returns an error if the points overflow.
+fn task(
+    tests: &[Test],
+    groups: &[Group],
+    cases: Vec<Case>,
+    warnings: &mut Vec<String>,
+) -> Result<(NonZeroU32, TaskType), ConvertError> {
+    let scored = tests.iter().any(|test| test.points > 0)
+        || groups
+            .iter()
+            .any(|group| group.complete && group.points > 0);
+    let total = |score: u32| NonZeroU32::new(score).unwrap_or(NonZeroU32::new(100).unwrap());
+
+    if tests.iter().all(|test| test.group.is_none()) {
+        if !scored {
+            return Ok((total(0), TaskType::Simple { cases }));
+        }
+        let mut kept = Vec::with_capacity(cases.len());
+        for ((mut case, test), id) in cases.into_iter().zip(tests).zip(1..) {
+            case.score = NonZeroU32::new(test.points);
+            match case.score {
+                Some(_) => kept.push(case),
+                None => warnings.push(format!("Test {id} without points is dropped")),
+            }
+        }
+        let score = tests
+            .iter()
+            .try_fold(0, |sum: u32, test| sum.checked_add(test.points))
+            .ok_or(ScoreError::Overflow)?;
+        return Ok((total(score), TaskType::Simple { cases: kept }));
+    }
+
+    // Groups in order of their first test.
+    let mut names = Vec::<&str>::new();
+    let mut members = HashMap::<&str, Vec<(Case, u32, usize)>>::new();
+    for ((case, test), id) in cases.into_iter().zip(tests).zip(1..) {
+        let name = test.group.as_deref().unwrap_or_default();
+        if !members.contains_key(name) {
+            names.push(name);
+        }
+        members
+            .entry(name)
+            .or_default()
+            .push((case, test.points, id));
+    }
+
+    let mut indexes = HashMap::<&str, usize>::new();
+    let mut subtasks = Vec::new();
+    let mut score = 0u32;
+    for name in names {
+        let group = groups.iter().find(|group| group.name == name);
+        let complete = group.is_some_and(|group| group.complete);
+        let members = members.remove(name).unwrap_or_default();
+        let points = if complete {
+            group.map_or(0, |group| group.points)
+        } else {
+            members
+                .iter()
+                .try_fold(0, |sum: u32, &(_, points, _)| sum.checked_add(points))
+                .ok_or(ScoreError::Overflow)?
+        };
+        if scored && points == 0 {
+            warnings.push(format!("Group `{name}` without points is dropped"));
+            continue;
+        }
+
+        let mut cases = Vec::with_capacity(members.len());
+        for (mut case, points, id) in members {
+            if scored && !complete {
+                case.score = NonZeroU32::new(points);
+                if case.score.is_none() {
+                    warnings.push(format!("Test {id} without points is dropped"));
+                    continue;
+                }
+            }
+            cases.push(case);
+        }
+
+        let mut dependencies = Vec::new();
+        for dependency in group.iter().flat_map(|group| &group.dependencies) {
+            match indexes.get(dependency.as_str()) {
+                Some(&index) => dependencies.push(index),
+                None => warnings.push(format!(
+                    "Dependency of group `{name}` on group `{dependency}` is dropped"
+                )),
+            }
+        }
+
+        score = score.checked_add(points).ok_or(ScoreError::Overflow)?;
+        subtasks.push(Subtask {
+            cases,
+            score: NonZeroU32::new(points).filter(|_| scored),
+            policy: if complete || !scored {
+                ScorePolicy::AllOrNothing
+            } else {
+                ScorePolicy::Sum
+            },
+            dependencies,
+        });
+        indexes.insert(name, subtasks.len());
+    }
+
+    Ok((total(score), TaskType::Subtask { subtasks }))
+}
+
+/// Format a Polygon path pattern like `tests/%02d` with `index`.
+fn format_index(pattern: &str, index: usize) -> String {
+    let Some(start) = pattern.find('%') else {
+        return pattern.to_owned();
+    };
+    let Some(end) = pattern[start..].find('d').map(|end| start + end) else {
+        return pattern.to_owned();
+    };
+    let spec = &pattern[start + 1..end];
+    let Ok(width) = (if spec.is_empty() {
+        Ok(0)
+    } else {
+        spec.parse::<usize>()
+    }) else {
+        return pattern.to_owned();
+    };
+    let number = if spec.starts_with('0') {
+        format!("{index:0width$}")
+    } else {
+        format!("{index:width$}")
+    };
+    format!("{}{number}{}", &pattern[..start], &pattern[end + 1..])
+}
+
+/// Read the `points` of a test or group, rounded to an integer.
+fn points(node: Node<'_, '_>, warnings: &mut Vec<String>) -> Result<u32, ConvertError> {
+    let Some(points) = node.attribute("points") else {
+        return Ok(0);
+    };
+    let value = parse::<f64>("points", points)?;
+    if value.fract() != 0.0 {
+        warnings.push(format!("Points {points} are rounded"));
+    }
+    Ok(value.round().max(0.0) as u32)
+}
+
+/// Read the `<source>` of an asset.
+fn source(node: Node<'_, '_>) -> Option<PolygonSource> {
+    let source = node.children().find(|node| node.has_tag_name("source"))?;
+    Some(PolygonSource {
+        path: source.attribute("path")?.to_owned(),
+        source_type: source.attribute("type").unwrap_or_default().to_owned(),
+    })
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Result<Node<'a, 'input>, ConvertError> {
+    node.children()
+        .find(|node| node.has_tag_name(name))
+        .ok_or_else(|| missing(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, ConvertError> {
+    Ok(child(node, name)?.text().unwrap_or_default().trim())
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConvertError> {
+    value
+        .parse()
+        .map_err(|_| ConvertError::InvalidPackage(format!("Invalid {name} `{value}`")))
+}
+
+/// Check that `path` is relative and inside the package.
+fn relative_path(name: &str, path: &str) -> Result<(), ConvertError> {
+    let path = Path::new(path);
+    let is_relative = path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if is_relative {
+        Ok(())
+    } else {
+        Err(ConvertError::InvalidPackage(format!(
+            "Invalid {name} `{}`, only relative paths inside the package are allowed",
+            path.display()
+        )))
+    }
+}
+
+fn missing(name: &str) -> ConvertError {
+    ConvertError::InvalidPackage(format!("Missing `<{name}>` in {PROBLEM_XML}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::io::{Cursor, Write as _};
+    use std::path::Path;
+
+    use tokio::fs;
+
+    use rsjudge_traits::language::template::TemplateVars;
+
+    use super::{PolygonPackage, PolygonPrograms, Test, format_index, import_package, task};
+    use crate::judger::convert::ConvertError;
+    use crate::judger::request::cases::{Case, JudgeType, ScorePolicy, TaskType};
+    use crate::judger::run::{test_limit, test_program};
+    use crate::judger::score::ScoreError;
+
+    const PROBLEM_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<problem revision="3" short-name="a-plus-b">
+  <judging input-file="" output-file="" run-count="1">
+    <testset name="tests">
+      <time-limit>2000</time-limit>
+      <memory-limit>268435456</memory-limit>
+      <test-count>4</test-count>
+      <input-path-pattern>tests/%02d</input-path-pattern>
+      <answer-path-pattern>tests/%02d.a</answer-path-pattern>
+      <tests>
+        <test method="manual" sample="true" group="samples"/>
+        <test cmd="gen 1 2" method="generated" group="small"/>
+        <test cmd="gen 3 4" method="generated" group="small"/>
+        <test method="manual" group="large" points="60"/>
+      </tests>
+      <groups>
+        <group name="samples" points="0" points-policy="each-test"/>
+        <group name="small" points="40" points-policy="complete-group">
+          <dependencies><dependency group="samples"/></dependencies>
+        </group>
+        <group name="large" points-policy="each-test">
+          <dependencies><dependency group="small"/></dependencies>
+        </group>
+      </groups>
+    </testset>
+  </judging>
+  <files>
+    <executables>
+      <executable><source path="files/gen.cpp" type="cpp.g++17"/></executable>
+    </executables>
+  </files>
+  <assets>
+    <checker name="std::ncmp.cpp" type="testlib">
+      <source path="files/check.cpp" type="cpp.g++17"/>
+    </checker>
+    <validators>
+      <validator><source path="files/val.cpp" type="cpp.g++17"/></validator>
+    </validators>
+    <solutions>
+      <solution tag="main"><source path="solutions/sol.cpp" type="cpp.g++17"/></solution>
+    </solutions>
+  </assets>
+</problem>
+"#;
+
+    #[test]
+    fn format_patterns() {
+        assert_eq!(format_index("tests/%02d", 3), "tests/03");
+        assert_eq!(format_index("tests/%d.a", 12), "tests/12.a");
+        assert_eq!(format_index("tests/input", 1), "tests/input");
+    }
+
+    #[test]
+    fn reject_overflowing_points() {
+        let tests = [u32::MAX, 1].map(|points| Test {
+            cmd: None,
+            group: None,
+            points,
+        });
+        let cases = vec![Case::new("1", "1.a"), Case::new("2", "2.a")];
+        let error = task(&tests, &[], cases, &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, ConvertError::Score(ScoreError::Overflow)));
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_paths() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let parse =
+            |xml: &str| PolygonPackage::parse(xml, dir.path().to_path_buf(), HashSet::new());
+
+        for (from, to) in [
+            ("tests/%02d.a", "../%02d.a"),
+            ("tests/%02d<", "/tmp/%02d<"),
+            ("files/gen.cpp", "files/../../gen.cpp"),
+        ] {
+            let error = parse(&PROBLEM_XML.replace(from, to)).unwrap_err();
+            assert!(matches!(error, ConvertError::InvalidPackage(_)), "{to}");
+        }
+
+        let xml = PROBLEM_XML.replace(
+            "</executables>",
+            r#"<executable><source path="solutions/gen.py" type="python.3"/></executable>
+    </executables>"#,
+        );
+        let error = parse(&xml)?
+            .compile(
+                &HashMap::new(),
+                &TemplateVars::new(),
+                dir.path(),
+                test_limit(),
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, ConvertError::InvalidPackage(message) if message.contains("`gen`"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_with_programs() -> anyhow::Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let problem_dir = tempfile::tempdir()?;
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in [
+            ("problem.xml", PROBLEM_XML),
+            ("tests/01", "1 1\n"),
+            ("tests/01.a", "2\n"),
+            ("tests/04", "5 6\n"),
+            ("files/gen.cpp", ""),
+            ("files/check.cpp", ""),
+            ("files/val.cpp", ""),
+            ("solutions/sol.cpp", ""),
+        ] {
+            zip.start_file(path, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(content.as_bytes())?;
+        }
+        let archive = zip.finish()?.into_inner();
+
+        let package = PolygonPackage::extract(&archive, 1 << 20, problem_dir.path()).await?;
+        assert!(package.needs_programs());
+        assert_eq!(package.executables()[0].name(), "gen");
+        assert_eq!(package.main_solution().unwrap().language(), Some("C++"));
+
+        // Programs are compiled before anything runs, so unknown languages
+        // fail early.
+        let other_dir = tempfile::tempdir()?;
+        let error = import_package(
+            &archive,
+            1 << 20,
+            other_dir.path(),
+            &HashMap::new(),
+            &TemplateVars::new(),
+            work_dir.path(),
+            test_limit(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            ConvertError::UnknownLanguage { path, .. } if path == "files/gen.cpp"
+        ));
+
+        let program = |command: &str| test_program(command, work_dir.path());
+        let generators = HashMap::from([("gen".to_owned(), program("printf '%s %s\\n'"))]);
+        let solution = program("read a b; echo $((a + b))");
+
+        let error = PolygonPackage::extract(&archive, 1 << 20, problem_dir.path())
+            .await?
+            .import(PolygonPrograms {
+                generators: &generators,
+                solution: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ConvertError::MissingSolution { test: 2 }));
+
+        let converted = package
+            .import(PolygonPrograms {
+                generators: &generators,
+                solution: Some(&solution),
+            })
+            .await?;
+        assert_eq!(
+            converted.warnings,
+            [
+                "Group `samples` without points is dropped",
+                "Dependency of group `small` on group `samples` is dropped",
+            ]
+        );
+
+        let config = converted.config;
+        assert!(matches!(
+            &config.judge,
+            JudgeType::SpecialJudge { checker } if checker == Path::new("files/check.cpp")
+        ));
+        assert_eq!(config.resource_limits().memory(), 256 << 20);
+        assert_eq!(config.validator().unwrap().language(), "C++");
+        let TaskType::Subtask { subtasks } = &config.task else {
+            panic!("expected subtasks");
+        };
+        assert_eq!(subtasks.len(), 2);
+        assert_eq!(subtasks[0].policy, ScorePolicy::AllOrNothing);
+        assert_eq!(subtasks[0].dependencies, Vec::<usize>::new());
+        assert_eq!(subtasks[1].policy, ScorePolicy::Sum);
+        assert_eq!(subtasks[1].dependencies, [1]);
+        assert_eq!(config.score_plan()?.case_count(), 3);
+
+        let read = |path: &str| fs::read_to_string(problem_dir.path().join(path));
+        assert_eq!(read("tests/02").await?, "1 2\n");
+        assert_eq!(read("tests/03.a").await?, "7\n");
+        assert_eq!(read("tests/04.a").await?, "11\n");
+        assert!(read("cases.json").await.is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of [QDUOJ] test cases, described by the `info` JSON file.
+//!
+//! Limits and scores of QDUOJ problems are stored with the problem instead of
+//! the test cases, so defaults are used on import, and they are dropped on
+//! export.
+//!
+//! [QDUOJ]: https://github.com/QingdaoU/OnlineJudge
+
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use md5::{Digest as _, Md5};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::judger::convert::{
+    ConvertError, Converted, ProblemFormat, copy_file, export_warnings, write_config,
+};
+use crate::judger::request::cases::{Case, CasesConfig, JudgeType, ResourceLimits, TaskType};
+
+const INFO_FILE: &str = "info";
+const FORMAT: &str = "QDUOJ";
+
+/// Time limit used on import **in milliseconds**.
+const DEFAULT_TIME: u32 = 1000;
+/// Memory limit used on import **in MiB**.
+const DEFAULT_MEMORY: u32 = 256;
+
+/// QDUOJ test cases, with `info` in the test case directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Qduoj;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Info {
+    test_case_number: usize,
+    #[serde(default)]
+    spj: bool,
+    /// Test cases keyed by their number, starting from 1.
+    test_cases: BTreeMap<String, InfoCase>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct InfoCase {
+    input_name: String,
+    input_size: u64,
+    /// Missing for special judge problems.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_size: Option<u64>,
+    /// MD5 of the output with trailing whitespace removed, in hex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stripped_output_md5: Option<String>,
+}
+
+impl ProblemFormat for Qduoj {
+    async fn import(&self, src: &Path, dst: &Path) -> Result<Vec<Converted>, ConvertError> {
+        let info = fs::read(src.join(INFO_FILE)).await.map_err(|error| {
+            if error.kind() == std::io::ErrorKind::NotFound {
+                ConvertError::MissingFile(INFO_FILE.to_owned())
+            } else {
+                error.into()
+            }
+        })?;
+        let info: Info = serde_json::from_slice(&info)
+            .map_err(|error| ConvertError::InvalidPackage(error.to_string()))?;
+        if info.spj {
+            return Err(ConvertError::Unsupported {
+                format: "rsjudge",
+                feature: "Special judge test cases without answers".to_owned(),
+            });
+        }
+
+        let mut test_cases: Vec<_> = info.test_cases.into_iter().collect();
+        test_cases.sort_by_key(|(number, _)| number.parse::<u64>().unwrap_or(u64::MAX));
+        let mut cases = Vec::with_capacity(test_cases.len());
+        for (number, test_case) in test_cases {
+            let output = test_case.output_name.ok_or_else(|| {
+                ConvertError::InvalidPackage(format!("Missing output of test case {number}"))
+            })?;
+            for file in [&test_case.input_name, &output] {
+                copy_file(src, Path::new(file), dst, Path::new(file)).await?;
+            }
+            cases.push(Case::new(test_case.input_name, output));
+        }
+
+        let config = CasesConfig::new(
+            NonZeroU32::new(100).unwrap(),
+            JudgeType::Classic,
+            ResourceLimits::new(DEFAULT_TIME, DEFAULT_MEMORY),
+            TaskType::Simple { cases },
+        );
+        write_config(&config, dst).await?;
+        Ok(vec![Converted {
+            dir: dst.to_path_buf(),
+            config,
+            warnings: vec![format!(
+                "Limits and scores are not stored in {FORMAT} test cases, so defaults are used"
+            )],
+        }])
+    }
+
+    async fn export(
+        &self,
+        config: &CasesConfig,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<Vec<String>, ConvertError> {
+        if !matches!(config.judge, JudgeType::Classic) || config.is_output_only() {
+            return Err(ConvertError::Unsupported {
+                format: FORMAT,
+                feature: "Judging other than comparing outputs".to_owned(),
+            });
+        }
+
+        let mut warnings = export_warnings(config);
+        warnings.push(format!(
+            "Limits and scores are not stored in {FORMAT} test cases, and need to be set with the problem"
+        ));
+        if matches!(config.task, TaskType::Subtask { .. }) {
+            warnings.push("Subtasks are flattened".to_owned());
+        }
+        if !config.io().is_stdio() {
+            warnings.push("Input and output files are dropped".to_owned());
+        }
+
+        let mut test_cases = BTreeMap::new();
+        let cases: Vec<_> = config.cases().collect();
+        for (case, number) in cases.into_iter().zip(1..) {
+            let input_name = format!("{number}.in");
+            let output_name = format!("{number}.out");
+            copy_file(src, case.input(), dst, Path::new(&input_name)).await?;
+            copy_file(src, case.answer(), dst, Path::new(&output_name)).await?;
+
+            let output = fs::read(dst.join(&output_name)).await?;
+            let stripped = output.trim_ascii_end();
+            test_cases.insert(
+                number.to_string(),
+                InfoCase {
+                    input_size: fs::metadata(dst.join(&input_name)).await?.len(),
+                    input_name,
+                    output_size: Some(output.len() as u64),
+                    output_name: Some(output_name),
+                    stripped_output_md5: Some(hex(&Md5::digest(stripped))),
+                },
+            );
+        }
+
+        let info = Info {
+            test_case_number: test_cases.len(),
+            spj: false,
+            test_cases,
+        };
+        fs::write(
+            dst.join(INFO_FILE),
+            serde_json::to_vec_pretty(&info).map_err(std::io::Error::from)?,
+        )
+        .await?;
+        Ok(warnings)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+    use tokio::fs;
+
+    use super::Qduoj;
+    use crate::judger::convert::{ConvertError, ProblemFormat as _};
+
+    #[tokio::test]
+    async fn import_and_export() -> anyhow::Result<()> {
+        let src = tempfile::tempdir()?;
+        let imported = tempfile::tempdir()?;
+        let exported = tempfile::tempdir()?;
+        for (name, content) in [
+            ("1.in", "1 2\n"),
+            ("1.out", "3\n"),
+            ("10.in", ""),
+            ("10.out", ""),
+        ] {
+            fs::write(src.path().join(name), content).await?;
+        }
+        let info = json!({
+            "test_case_number": 2,
+            "spj": false,
+            "test_cases": {
+                "10": { "input_name": "10.in", "input_size": 0, "output_name": "10.out" },
+                "1": { "input_name": "1.in", "input_size": 4, "output_name": "1.out" }
+            }
+        });
+        fs::write(src.path().join("info"), serde_json::to_vec(&info)?).await?;
+
+        let converted = Qduoj.import(src.path(), imported.path()).await?;
+        let config = &converted[0].config;
+        let inputs: Vec<_> = config.cases().map(|case| case.input()).collect();
+        assert_eq!(inputs, ["1.in", "10.in"].map(std::path::Path::new));
+        assert_eq!(converted[0].warnings.len(), 1);
+
+        Qduoj
+            .export(config, imported.path(), exported.path())
+            .await?;
+        let info: Value = serde_json::from_slice(&fs::read(exported.path().join("info")).await?)?;
+        assert_eq!(info["test_case_number"], 2);
+        assert_eq!(
+            info["test_cases"]["1"],
+            json!({
+                "input_name": "1.in",
+                "input_size": 4,
+                "output_name": "1.out",
+                "output_size": 2,
+                // MD5 of "3".
+                "stripped_output_md5": "eccbc87e4b5ce2fe28308fd9f2a7baf3"
+            })
+        );
+        assert_eq!(fs::read_to_string(exported.path().join("2.in")).await?, "");
+
+        fs::write(
+            src.path().join("info"),
+            serde_json::to_vec(&json!({ "test_case_number": 0, "spj": true, "test_cases": {} }))?,
+        )
+        .await?;
+        assert!(matches!(
+            Qduoj.import(src.path(), imported.path()).await,
+            Err(ConvertError::Unsupported { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structured diagnostics parsed from compiler output.
+//!
+//! JSON output is parsed line by line, so that plain text mixed in, e.g. from
+//! the linker, is still parsed with the text patterns.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rsjudge_traits::language::config::DiagnosticFormat;
+use serde::Deserialize;
+
+/// Severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Notes and help messages attached to other diagnostics.
+    Note,
+}
+
+impl Severity {
+    fn parse(severity: &str) -> Self {
+        match severity.to_ascii_lowercase().as_str() {
+            "warning" => Self::Warning,
+            "note" | "help" | "failure-note" => Self::Note,
+            _ => Self::Error,
+        }
+    }
+}
+
+/// A diagnostic reported by the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The file the diagnostic refers to, if any.
+    pub file: Option<String>,
+    /// 1-based line number.
+    pub line: Option<u32>,
+    /// 1-based column number.
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Parse diagnostics of `format` from the compiler stdout and stderr.
+pub fn parse_diagnostics(
+    format: DiagnosticFormat,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for output in [stdout, stderr] {
+        for line in String::from_utf8_lossy(output).lines() {
+            let parsed = match format {
+                DiagnosticFormat::Text => false,
+                DiagnosticFormat::GccJson => parse_gcc_json(line, &mut diagnostics),
+                DiagnosticFormat::RustcJson => parse_rustc_json(line, &mut diagnostics),
+            };
+            if !parsed {
+                diagnostics.extend(parse_text(line));
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// `file:line[:column]: [severity:] message`, used by GCC, Clang, javac, go,
+/// kotlinc and others.
+static COLON_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?<file>[^\s:]+):(?<line>\d+):(?:(?<column>\d+):)?\s*(?:(?<severity>fatal error|error|warning|note)\s*:)?\s*(?<message>.+)$",
+    )
+    .unwrap()
+});
+
+/// `file(line,column): severity code: message`, used by tsc and C# compilers.
+static PAREN_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?<file>[^\s(]+)\((?<line>\d+),(?<column>\d+)\):\s*(?<severity>error|warning)\s*(?:\w+\s*)?:\s*(?<message>.+)$",
+    )
+    .unwrap()
+});
+
+fn parse_text(line: &str) -> Option<Diagnostic> {
+    let captures = COLON_PATTERN
+        .captures(line)
+        .or_else(|| PAREN_PATTERN.captures(line))?;
+
+    Some(Diagnostic {
+        file: Some(captures["file"].to_owned()),
+        line: captures["line"].parse().ok(),
+        column: captures
+            .name("column")
+            .and_then(|column| column.as_str().parse().ok()),
+        severity: captures
+            .name("severity")
+            .map_or(Severity::Error, |severity| {
+                Severity::parse(severity.as_str())
+            }),
+        message: captures["message"].trim().to_owned(),
+    })
+}
+
+#[derive(Deserialize)]
+struct GccDiagnostic {
+    kind: String,
+    message: String,
+    #[serde(default)]
+    locations: Vec<GccLocation>,
+    #[serde(default)]
+    children: Vec<GccDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct GccLocation {
+    caret: GccPosition,
+}
+
+#[derive(Deserialize)]
+struct GccPosition {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+fn parse_gcc_json(line: &str, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    fn push(diagnostic: GccDiagnostic, diagnostics: &mut Vec<Diagnostic>) {
+        let caret = diagnostic
+            .locations
+            .into_iter()
+            .next()
+            .map(|location| location.caret);
+        diagnostics.push(Diagnostic {
+            file: caret.as_ref().map(|caret| caret.file.clone()),
+            line: caret.as_ref().map(|caret| caret.line),
+            column: caret.as_ref().map(|caret| caret.column),
+            severity: Severity::parse(&diagnostic.kind),
+            message: diagnostic.message,
+        });
+        for child in diagnostic.children {
+            push(child, diagnostics);
+        }
+    }
+
+    let Ok(parsed) = serde_json::from_str::<Vec<GccDiagnostic>>(line) else {
+        return false;
+    };
+    for diagnostic in parsed {
+        push(diagnostic, diagnostics);
+    }
+    true
+}
+
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    level: String,
+    #[serde(default)]
+    spans: Vec<RustcSpan>,
+    #[serde(default)]
+    children: Vec<RustcDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: u32,
+    column_start: u32,
+    is_primary: bool,
+}
+
+fn parse_rustc_json(line: &str, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    fn push(diagnostic: RustcDiagnostic, diagnostics: &mut Vec<Diagnostic>) {
+        let span = diagnostic
+            .spans
+            .iter()
+            .find(|span| span.is_primary)
+            .or(diagnostic.spans.first());
+        diagnostics.push(Diagnostic {
+            file: span.map(|span| span.file_name.clone()),
+            line: span.map(|span| span.line_start),
+            column: span.map(|span| span.column_start),
+            severity: Severity::parse(&diagnostic.level),
+            message: diagnostic.message,
+        });
+        for child in diagnostic.children {
+            push(child, diagnostics);
+        }
+    }
+
+    let Ok(parsed) = serde_json::from_str::<RustcDiagnostic>(line) else {
+        return false;
+    };
+    push(parsed, diagnostics);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::language::config::DiagnosticFormat;
+
+    use super::{Diagnostic, Severity, parse_diagnostics};
+
+    fn diagnostic(
+        file: &str,
+        line: u32,
+        column: Option<u32>,
+        severity: Severity,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            file: Some(file.to_owned()),
+            line: Some(line),
+            column,
+            severity,
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_text() {
+        let javac =
+            b"Main.java:3: error: ';' expected\n        int x = 1\n                 ^\n1 error\n";
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::Text, b"", javac),
+            [diagnostic(
+                "Main.java",
+                3,
+                None,
+                Severity::Error,
+                "';' expected"
+            )]
+        );
+
+        let go = b"# command-line-arguments\n./main.go:5:2: declared and not used: x\n";
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::Text, b"", go),
+            [diagnostic(
+                "./main.go",
+                5,
+                Some(2),
+                Severity::Error,
+                "declared and not used: x"
+            )]
+        );
+
+        let gcc = b"a.c: In function 'main':\na.c:2:5: warning: unused variable 'x' [-Wunused-variable]\n";
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::Text, b"", gcc),
+            [diagnostic(
+                "a.c",
+                2,
+                Some(5),
+                Severity::Warning,
+                "unused variable 'x' [-Wunused-variable]"
+            )]
+        );
+
+        let mcs = b"main.cs(3,9): error CS1525: Unexpected symbol `}'\n";
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::Text, mcs, b""),
+            [diagnostic(
+                "main.cs",
+                3,
+                Some(9),
+                Severity::Error,
+                "Unexpected symbol `}'"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_gcc_json() {
+        let stderr = br#"[{"kind": "error", "message": "expected ';' before '}' token", "children": [{"kind": "note", "message": "declared here", "locations": [{"caret": {"file": "a.c", "line": 1, "column": 5}}]}], "locations": [{"caret": {"file": "a.c", "line": 3, "display-column": 10, "column": 10}}]}]
+/usr/bin/ld: cannot find -lfoo
+"#;
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::GccJson, b"", stderr),
+            [
+                diagnostic(
+                    "a.c",
+                    3,
+                    Some(10),
+                    Severity::Error,
+                    "expected ';' before '}' token"
+                ),
+                diagnostic("a.c", 1, Some(5), Severity::Note, "declared here"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_rustc_json() {
+        let stderr = br#"{"$message_type":"diagnostic","message":"cannot find value `x` in this scope","level":"error","spans":[{"file_name":"main.rs","line_start":2,"column_start":20,"is_primary":true}],"children":[{"message":"a local variable with a similar name exists","level":"help","spans":[],"children":[]}],"rendered":"error[E0425]: ..."}
+{"$message_type":"diagnostic","message":"aborting due to 1 previous error","level":"error","spans":[],"children":[],"rendered":"..."}
+"#;
+        assert_eq!(
+            parse_diagnostics(DiagnosticFormat::RustcJson, b"", stderr),
+            [
+                diagnostic(
+                    "main.rs",
+                    2,
+                    Some(20),
+                    Severity::Error,
+                    "cannot find value `x` in this scope"
+                ),
+                Diagnostic {
+                    file: None,
+                    line: None,
+                    column: None,
+                    severity: Severity::Note,
+                    message: "a local variable with a similar name exists".to_owned(),
+                },
+                Diagnostic {
+                    file: None,
+                    line: None,
+                    column: None,
+                    severity: Severity::Error,
+                    message: "aborting due to 1 previous error".to_owned(),
+                },
+            ]
+        );
+    }
+}
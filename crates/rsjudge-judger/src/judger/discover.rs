@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Discovery of cases from numbered test files, for problems without a
+//! [`CasesConfig`].
+//!
+//! Inputs are found by glob patterns, and paired with answers of the same name
+//! with another extension. Cases are ordered by natural sort, so that `10.in`
+//! comes after `2.in`. Files named like `sub1_03.in` are grouped into subtasks
+//! by the number after the prefix.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+
+use crate::judger::request::cases::{
+    CASES_CONFIG_FILE, Case, CasesConfig, JudgeType, ResourceLimits, Subtask, TaskType,
+};
+use crate::judger::score::ScoreError;
+
+/// Error discovering the cases of a problem.
+#[derive(Debug, Error)]
+pub enum DiscoverError {
+    #[error("Invalid input pattern: {0}")]
+    InvalidPattern(#[from] globset::Error),
+    #[error("Invalid subtask pattern: {0}")]
+    InvalidSubtaskPattern(#[from] regex::Error),
+    #[error("No input file found")]
+    NoCases,
+    #[error("Missing answer of input `{0}`")]
+    MissingAnswer(PathBuf),
+    #[error("Input `{0}` is not in any subtask, while other inputs are")]
+    Ungrouped(PathBuf),
+    #[error("Invalid cases config: {0}")]
+    Config(#[from] serde_json::Error),
+    #[error(transparent)]
+    Score(#[from] ScoreError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Configuration of the discovery.
+///
+/// Defaults to `*.in` and `data/*.in` inputs with `.out` or `.ans` answers,
+/// worth 100 in total, judged by [`JudgeType::Classic`] in 1000 ms and
+/// 256 MiB.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoverConfig {
+    /// Glob patterns of input files, relative to the cases directory.
+    #[serde(default = "default_inputs")]
+    pub(crate) inputs: Vec<String>,
+    /// Extensions of answer files, tried in order.
+    #[serde(default = "default_answer_extensions")]
+    pub(crate) answer_extensions: Vec<String>,
+    /// Regex matching the file stem of inputs in a subtask, capturing the
+    /// number of the subtask.
+    #[serde(default = "default_subtask_pattern")]
+    pub(crate) subtask_pattern: String,
+    #[serde(default = "default_score")]
+    pub(crate) score: NonZeroU32,
+    #[serde(default = "default_judge")]
+    pub(crate) judge: JudgeType,
+    #[serde(default = "default_resource_limits")]
+    pub(crate) resource_limits: ResourceLimits,
+}
+
+fn default_inputs() -> Vec<String> {
+    vec!["*.in".to_owned(), "data/*.in".to_owned()]
+}
+
+fn default_answer_extensions() -> Vec<String> {
+    vec!["out".to_owned(), "ans".to_owned()]
+}
+
+fn default_subtask_pattern() -> String {
+    r"^sub(?:task)?(\d+)[_-]".to_owned()
+}
+
+fn default_score() -> NonZeroU32 {
+    NonZeroU32::new(100).unwrap()
+}
+
+fn default_judge() -> JudgeType {
+    JudgeType::Classic
+}
+
+fn default_resource_limits() -> ResourceLimits {
+    ResourceLimits::new(1000, 256)
+}
+
+impl Default for DiscoverConfig {
+    fn default() -> Self {
+        Self {
+            inputs: default_inputs(),
+            answer_extensions: default_answer_extensions(),
+            subtask_pattern: default_subtask_pattern(),
+            score: default_score(),
+            judge: default_judge(),
+            resource_limits: default_resource_limits(),
+        }
+    }
+}
+
+/// Load the [`CasesConfig`] in `cases_dir`, or discover one with `config` if
+/// there is no [`CASES_CONFIG_FILE`].
+///
+/// # Errors
+///
+/// Returns an error if the config is invalid, or cannot be discovered.
+pub async fn load_or_discover(
+    cases_dir: &Path,
+    config: DiscoverConfig,
+) -> Result<CasesConfig, DiscoverError> {
+    match fs::read(cases_dir.join(CASES_CONFIG_FILE)).await {
+        Ok(cases) => Ok(serde_json::from_slice(&cases)?),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => discover(cases_dir, config).await,
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Discover the cases in `cases_dir` with `config`.
+///
+/// # Errors
+///
+/// Returns an error if a pattern is invalid, no input is found, an input has
+/// no answer, or the scores cannot be shared by the cases.
+pub async fn discover(
+    cases_dir: &Path,
+    config: DiscoverConfig,
+) -> Result<CasesConfig, DiscoverError> {
+    let mut patterns = GlobSetBuilder::new();
+    for pattern in &config.inputs {
+        patterns.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    let patterns = patterns.build()?;
+    let subtask_pattern = Regex::new(&config.subtask_pattern)?;
+
+    let files = list_files(cases_dir).await?;
+    let mut inputs: Vec<&PathBuf> = files
+        .iter()
+        .filter(|path| patterns.is_match(path))
+        .collect();
+    inputs.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    if inputs.is_empty() {
+        return Err(DiscoverError::NoCases);
+    }
+
+    let mut cases = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let answer = config
+            .answer_extensions
+            .iter()
+            .map(|extension| input.with_extension(extension))
+            .find(|answer| files.contains(answer))
+            .ok_or_else(|| DiscoverError::MissingAnswer(input.clone()))?;
+        cases.push(Case::new(input.clone(), answer));
+    }
+
+    let subtask_of = |case: &Case| {
+        let stem = case.input().file_stem()?.to_str()?;
+        subtask_pattern
+            .captures(stem)?
+            .get(1)?
+            .as_str()
+            .parse::<u64>()
+            .ok()
+    };
+    let task = if cases.iter().any(|case| subtask_of(case).is_some()) {
+        let mut subtasks = BTreeMap::<u64, Vec<Case>>::new();
+        for case in cases {
+            let Some(subtask) = subtask_of(&case) else {
+                return Err(DiscoverError::Ungrouped(case.input().to_path_buf()));
+            };
+            subtasks.entry(subtask).or_default().push(case);
+        }
+        TaskType::Subtask {
+            subtasks: subtasks
+                .into_values()
+                .map(|cases| Subtask {
+                    cases,
+                    score: None,
+                    policy: Default::default(),
+                    dependencies: Vec::new(),
+                })
+                .collect(),
+        }
+    } else {
+        TaskType::Simple { cases }
+    };
+
+    let cases_config = CasesConfig::new(config.score, config.judge, config.resource_limits, task);
+    cases_config.score_plan()?;
+    Ok(cases_config)
+}
+
+/// List all regular files in `dir` recursively, relative to `dir`.
+pub(crate) async fn list_files(dir: &Path) -> io::Result<HashSet<PathBuf>> {
+    let mut files = HashSet::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let mut entries = fs::read_dir(dir.join(&relative)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = relative.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.insert(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Compare strings in natural order, where runs of digits are compared by
+/// their numeric value.
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_rest, mut b_rest) = (a, b);
+    loop {
+        let (Some(a_char), Some(b_char)) = (a_rest.chars().next(), b_rest.chars().next()) else {
+            break a_rest.len().cmp(&b_rest.len()).then_with(|| a.cmp(b));
+        };
+
+        let ordering = if a_char.is_ascii_digit() && b_char.is_ascii_digit() {
+            let a_len = a_rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(a_rest.len());
+            let b_len = b_rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(b_rest.len());
+            let a_digits = a_rest[..a_len].trim_start_matches('0');
+            let b_digits = b_rest[..b_len].trim_start_matches('0');
+            a_rest = &a_rest[a_len..];
+            b_rest = &b_rest[b_len..];
+            a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits))
+        } else {
+            a_rest = &a_rest[a_char.len_utf8()..];
+            b_rest = &b_rest[b_char.len_utf8()..];
+            a_char.cmp(&b_char)
+        };
+
+        if ordering.is_ne() {
+            break ordering;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{DiscoverConfig, DiscoverError, discover, load_or_discover, natural_cmp};
+    use crate::judger::request::cases::{CASES_CONFIG_FILE, TaskType};
+
+    #[test]
+    fn natural_order() {
+        let mut names = ["10.in", "2.in", "1.in", "a02.in", "a1.in", "01.in"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["01.in", "1.in", "2.in", "10.in", "a1.in", "a02.in"]);
+    }
+
+    #[tokio::test]
+    async fn discover_cases() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in [
+            "1.in",
+            "1.out",
+            "2.in",
+            "2.out",
+            "10.in",
+            "10.out",
+            "notes.txt",
+        ] {
+            fs::write(dir.path().join(name), "").await?;
+        }
+
+        let config = load_or_discover(dir.path(), DiscoverConfig::default()).await?;
+        let inputs: Vec<_> = config.cases().map(|case| case.input()).collect();
+        assert_eq!(
+            inputs,
+            [Path::new("1.in"), Path::new("2.in"), Path::new("10.in")]
+        );
+        assert_eq!(config.score_plan()?.case_count(), 3);
+
+        fs::remove_file(dir.path().join("10.out")).await?;
+        assert!(matches!(
+            discover(dir.path(), DiscoverConfig::default()).await,
+            Err(DiscoverError::MissingAnswer(input)) if input == Path::new("10.in")
+        ));
+
+        // An explicit config takes precedence.
+        fs::write(
+            dir.path().join(CASES_CONFIG_FILE),
+            serde_json::to_vec(&json!({
+                "score": 100,
+                "judge": { "judgeType": "classic" },
+                "resourceLimits": { "time": 2000, "memory": 512 },
+                "task": {
+                    "taskType": "simple",
+                    "cases": [{ "input": "1.in", "answer": "1.out" }]
+                }
+            }))?,
+        )
+        .await?;
+        let config = load_or_discover(dir.path(), DiscoverConfig::default()).await?;
+        assert_eq!(config.cases().count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn discover_subtasks() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir(dir.path().join("data")).await?;
+        for name in ["sub2_01", "sub1_02", "sub1_01", "sub10_01", "sub2_02"] {
+            fs::write(dir.path().join("data").join(format!("{name}.in")), "").await?;
+            fs::write(dir.path().join("data").join(format!("{name}.ans")), "").await?;
+        }
+
+        let config = discover(
+            dir.path(),
+            serde_json::from_value::<DiscoverConfig>(json!({ "score": 90 }))?,
+        )
+        .await?;
+        let TaskType::Subtask { subtasks } = &config.task else {
+            panic!("expected subtasks");
+        };
+        let sizes: Vec<_> = subtasks.iter().map(|subtask| subtask.cases.len()).collect();
+        assert_eq!(sizes, [2, 2, 1]);
+        assert_eq!(subtasks[0].cases[1].answer(), Path::new("data/sub1_02.ans"));
+        assert_eq!(subtasks[2].cases[0].input(), Path::new("data/sub10_01.in"));
+
+        fs::write(dir.path().join("data/extra.in"), "").await?;
+        fs::write(dir.path().join("data/extra.ans"), "").await?;
+        assert!(matches!(
+            discover(dir.path(), DiscoverConfig::default()).await,
+            Err(DiscoverError::Ungrouped(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! File-based I/O of problems configured with an [`IoConfig`].
+//!
+//! The input file is placed into the working directory before each run, and
+//! the output file is collected after the program exits, so that comparers and
+//! checkers read it just like the captured stdout.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use rsjudge_traits::judger::JudgeResult;
+use thiserror::Error;
+use tokio::fs::{self, File};
+
+use crate::judger::request::cases::IoConfig;
+
+/// Error collecting the output of a run.
+#[derive(Debug, Error)]
+pub enum OutputError {
+    #[error("Output file `{0}` is not created")]
+    Missing(String),
+    #[error("Output file `{0}` is not a regular file")]
+    NotRegular(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl OutputError {
+    /// Get the judge result of the case, or `None` if the error is not caused
+    /// by the submission.
+    pub fn result(&self) -> Option<JudgeResult> {
+        match self {
+            Self::Missing(_) | Self::NotRegular(_) => Some(JudgeResult::OutputFileMissing),
+            Self::Io(_) => None,
+        }
+    }
+}
+
+impl IoConfig {
+    /// Whether the problem uses stdin and stdout only.
+    pub fn is_stdio(&self) -> bool {
+        self.input_file.is_none() && self.output_file.is_none()
+    }
+
+    /// Place `input` into `work_dir` if the problem reads an input file, and
+    /// get the stdin of the run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input file name is invalid, or the input cannot
+    /// be copied or opened.
+    pub async fn prepare_input(&self, input: &Path, work_dir: &Path) -> io::Result<Stdio> {
+        let Some(name) = &self.input_file else {
+            return Ok(File::open(input).await?.into_std().await.into());
+        };
+
+        fs::copy(input, file_path(work_dir, name)?).await?;
+        if self.stdin {
+            Ok(File::open(input).await?.into_std().await.into())
+        } else {
+            Ok(Stdio::null())
+        }
+    }
+
+    /// Open the output of a run in `work_dir`, with the captured stdout at
+    /// `stdout`.
+    ///
+    /// The captured stdout is ignored if the problem writes an output file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output file is missing or not a regular file,
+    /// or the output cannot be opened.
+    pub async fn open_output(&self, work_dir: &Path, stdout: &Path) -> Result<File, OutputError> {
+        let Some(name) = &self.output_file else {
+            return Ok(File::open(stdout).await?);
+        };
+
+        let path = file_path(work_dir, name)?;
+        // Don't follow symlinks, which may point to files of the judger.
+        match fs::symlink_metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => Ok(File::open(path).await?),
+            Ok(_) => Err(OutputError::NotRegular(name.clone())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Err(OutputError::Missing(name.clone()))
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Remove the input and output files from `work_dir`, so that the next
+    /// case starts clean.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file exists but cannot be removed.
+    pub async fn clean(&self, work_dir: &Path) -> io::Result<()> {
+        for name in self.input_file.iter().chain(&self.output_file) {
+            match fs::remove_file(file_path(work_dir, name)?).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the path of file `name` in `work_dir`, which must be a plain file name.
+fn file_path(work_dir: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(work_dir.join(name)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid I/O file name `{name}`"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::judger::JudgeResult;
+    use tokio::fs;
+
+    use super::OutputError;
+    use crate::comparer::{CompareResult, Comparer as _, DefaultComparer};
+    use crate::judger::request::cases::IoConfig;
+
+    #[tokio::test]
+    async fn file_io() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let work_dir = tempfile::tempdir()?;
+        let input = cases_dir.path().join("1.in");
+        let answer = cases_dir.path().join("1.ans");
+        let stdout = cases_dir.path().join("stdout");
+        fs::write(&input, "1 2\n").await?;
+        fs::write(&answer, "3\n").await?;
+        fs::write(&stdout, "").await?;
+
+        let config = IoConfig {
+            input_file: Some("input.txt".to_owned()),
+            output_file: Some("output.txt".to_owned()),
+            stdin: false,
+        };
+        assert!(!config.is_stdio());
+
+        config.prepare_input(&input, work_dir.path()).await?;
+        assert_eq!(
+            fs::read_to_string(work_dir.path().join("input.txt")).await?,
+            "1 2\n"
+        );
+
+        let missing = config.open_output(work_dir.path(), &stdout).await;
+        assert!(matches!(&missing, Err(OutputError::Missing(name)) if name == "output.txt"));
+        assert_eq!(
+            missing.unwrap_err().result(),
+            Some(JudgeResult::OutputFileMissing)
+        );
+
+        // As if written by the program.
+        fs::write(work_dir.path().join("output.txt"), "3\n").await?;
+        let output = config.open_output(work_dir.path(), &stdout).await?;
+        assert_eq!(
+            DefaultComparer::common()
+                .compare(output, fs::File::open(&answer).await?)
+                .await?,
+            CompareResult::Accepted
+        );
+
+        config.clean(work_dir.path()).await?;
+        assert!(!fs::try_exists(work_dir.path().join("input.txt")).await?);
+        assert!(!fs::try_exists(work_dir.path().join("output.txt")).await?);
+
+        let config = IoConfig {
+            output_file: Some("../output.txt".to_owned()),
+            ..IoConfig::default()
+        };
+        assert!(matches!(
+            config.open_output(work_dir.path(), &stdout).await,
+            Err(OutputError::Io(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Linking submissions with a problem-provided [`Grader`].
+//!
+//! Grader files are copied next to the submission for the compile phase only,
+//! and removed before the program runs, so that it can't read them.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use rsjudge_traits::language::template::TemplateVars;
+use thiserror::Error;
+use tokio::fs;
+
+use crate::judger::request::cases::Grader;
+
+/// Error installing a grader.
+#[derive(Debug, Error)]
+pub enum GraderError {
+    #[error("Invalid grader file path `{}`", .0.display())]
+    InvalidPath(PathBuf),
+    #[error("Submitted file `{0}` conflicts with the grader")]
+    Conflict(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Grader files installed into a work directory.
+#[derive(Debug)]
+#[must_use = "grader files must be removed before running the program"]
+pub struct InstalledGrader {
+    files: Vec<PathBuf>,
+    identity: Vec<u8>,
+}
+
+impl Grader {
+    /// Copy the grader files from `cases_dir` into `work_dir`, and set
+    /// `{grader_files}` to the space-separated names of the source files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a grader file is not inside `cases_dir`, conflicts
+    /// with a submitted file, or cannot be copied.
+    pub async fn install(
+        &self,
+        cases_dir: &Path,
+        work_dir: &Path,
+        vars: &mut TemplateVars,
+    ) -> Result<InstalledGrader, GraderError> {
+        let mut installed = InstalledGrader {
+            files: Vec::new(),
+            identity: Vec::new(),
+        };
+        let mut sources = Vec::new();
+        let result = async {
+            for (path, is_source) in self
+                .sources
+                .iter()
+                .map(|path| (path, true))
+                .chain(self.headers.iter().map(|path| (path, false)))
+            {
+                let name = installed.copy(path, cases_dir, work_dir).await?;
+                if is_source {
+                    sources.push(name);
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        if let Err(error) = result {
+            // Remove the files copied so far, submitted files are untouched.
+            installed.remove().await?;
+            return Err(error);
+        }
+        vars.set("grader_files", sources.join(" "));
+        Ok(installed)
+    }
+}
+
+/// Install `grader` if the problem has one, otherwise set `{grader_files}`
+/// to an empty string, so that compile templates can refer to it anyway.
+///
+/// # Errors
+///
+/// See [`Grader::install`].
+pub async fn install_grader(
+    grader: Option<&Grader>,
+    cases_dir: &Path,
+    work_dir: &Path,
+    vars: &mut TemplateVars,
+) -> Result<Option<InstalledGrader>, GraderError> {
+    match grader {
+        Some(grader) => Ok(Some(grader.install(cases_dir, work_dir, vars).await?)),
+        None => {
+            vars.set("grader_files", "");
+            Ok(None)
+        }
+    }
+}
+
+impl InstalledGrader {
+    /// Copy `path` in `cases_dir` into `work_dir`, returning its file name.
+    async fn copy(
+        &mut self,
+        path: &Path,
+        cases_dir: &Path,
+        work_dir: &Path,
+    ) -> Result<String, GraderError> {
+        let is_inside = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        let Some(name) = path.file_name().filter(|_| is_inside) else {
+            return Err(GraderError::InvalidPath(path.to_path_buf()));
+        };
+        let name = name.to_string_lossy().into_owned();
+
+        let target = work_dir.join(&name);
+        if fs::try_exists(&target).await? {
+            return Err(GraderError::Conflict(name));
+        }
+        let content = fs::read(cases_dir.join(path)).await?;
+        fs::write(&target, &content).await?;
+        self.files.push(target);
+
+        for field in [name.as_bytes(), &content] {
+            self.identity
+                .extend_from_slice(&(field.len() as u64).to_le_bytes());
+            self.identity.extend_from_slice(field);
+        }
+        Ok(name)
+    }
+
+    /// Get bytes identifying the grader files, passed to [`CacheKey::new`] as
+    /// `extra` by [`CacheLookup::new`], so that changing the grader
+    /// invalidates cached compilations.
+    ///
+    /// [`CacheKey::new`]: crate::judger::cache::CacheKey::new
+    /// [`CacheLookup::new`]: crate::judger::compile::CacheLookup::new
+    pub fn identity(&self) -> &[u8] {
+        &self.identity
+    }
+
+    /// Remove the grader files from the work directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be removed.
+    pub async fn remove(self) -> io::Result<()> {
+        for file in self.files {
+            fs::remove_file(file).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rsjudge_traits::language::template::TemplateVars;
+    use tokio::fs;
+
+    use super::{GraderError, install_grader};
+    use crate::judger::request::cases::Grader;
+
+    #[tokio::test]
+    async fn install_and_remove() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let work_dir = tempfile::tempdir()?;
+        fs::create_dir(cases_dir.path().join("grader")).await?;
+        fs::write(cases_dir.path().join("grader/grader.cpp"), "int main() {}").await?;
+        fs::write(cases_dir.path().join("grader/solve.h"), "int solve();").await?;
+
+        let grader = Grader {
+            sources: vec![PathBuf::from("grader/grader.cpp")],
+            headers: vec![PathBuf::from("grader/solve.h")],
+        };
+        let mut vars = TemplateVars::new();
+        vars.set("src_file", "main.cpp");
+
+        let installed = grader
+            .install(cases_dir.path(), work_dir.path(), &mut vars)
+            .await?;
+        assert_eq!(
+            vars.render("g++ {src_file} {grader_files}")?,
+            "g++ main.cpp grader.cpp"
+        );
+        assert_eq!(
+            fs::read_to_string(work_dir.path().join("solve.h")).await?,
+            "int solve();"
+        );
+
+        installed.remove().await?;
+        assert!(!fs::try_exists(work_dir.path().join("grader.cpp")).await?);
+        assert!(!fs::try_exists(work_dir.path().join("solve.h")).await?);
+
+        fs::write(work_dir.path().join("solve.h"), "").await?;
+        assert!(matches!(
+            grader
+                .install(cases_dir.path(), work_dir.path(), &mut vars)
+                .await,
+            Err(GraderError::Conflict(name)) if name == "solve.h"
+        ));
+        assert!(!fs::try_exists(work_dir.path().join("grader.cpp")).await?);
+
+        assert!(
+            install_grader(None, cases_dir.path(), work_dir.path(), &mut vars)
+                .await?
+                .is_none()
+        );
+        assert_eq!(
+            vars.render("g++ {src_file} {grader_files}")?,
+            "g++ main.cpp "
+        );
+
+        let grader = Grader {
+            sources: vec![PathBuf::from("../grader.cpp")],
+            headers: Vec::new(),
+        };
+        assert!(matches!(
+            grader
+                .install(cases_dir.path(), work_dir.path(), &mut vars)
+                .await,
+            Err(GraderError::InvalidPath(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hacking a submission with a custom input, as in Codeforces.
+//!
+//! The input, given directly or printed by a generator, is checked by the
+//! validator first. Both the target submission and the reference solution then
+//! run on it, and the output of the target is checked against the output of
+//! the reference with the comparer or checker of the problem.
+
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+
+use rsjudge_traits::judger::JudgeResult;
+use thiserror::Error;
+use tokio::fs::{self, File};
+
+use crate::comparer::{CompareResult, Comparer};
+use crate::judger::request::cases::JudgeType;
+use crate::judger::run::{Program, stdin};
+
+/// Error running a hack.
+#[derive(Debug, Error)]
+pub enum HackError {
+    #[error("Checker of the problem is not given")]
+    MissingChecker,
+    #[error("Interactive problems cannot be hacked")]
+    Interactive,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+}
+
+/// Input of a hack, with the generator compiled.
+#[derive(Debug, Clone, Copy)]
+pub enum HackData<'a> {
+    /// The input itself.
+    Input(&'a [u8]),
+    /// A generator printing the input, and its arguments.
+    Generator {
+        program: &'a Program,
+        args: &'a [String],
+    },
+}
+
+/// Compiled programs taking part in a hack.
+#[derive(Debug, Clone, Copy)]
+pub struct HackPrograms<'a> {
+    /// The input validator, exiting successfully for valid input.
+    pub validator: &'a Program,
+    /// The submission being hacked.
+    pub target: &'a Program,
+    /// The reference solution, whose output is taken as the answer.
+    pub reference: &'a Program,
+    /// The checker, for special judge problems.
+    pub checker: Option<&'a Program>,
+}
+
+/// Outcome of a hack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HackOutcome {
+    /// The generator failed with the result, and its stderr.
+    GeneratorFailed {
+        result: JudgeResult,
+        message: String,
+    },
+    /// The validator rejected the input, with its stderr.
+    InvalidInput(String),
+    /// The reference solution failed on the input, which is an issue of the
+    /// problem rather than the hack.
+    ReferenceFailed(JudgeResult),
+    /// The checker failed on the output of the target, with its stderr, which
+    /// is also an issue of the problem.
+    CheckerFailed(String),
+    /// The target submission failed on the input with the result.
+    Succeeded(JudgeResult),
+    /// The target submission passed the input.
+    Failed,
+}
+
+impl HackOutcome {
+    /// Whether the hack succeeded.
+    pub fn is_successful(&self) -> bool {
+        matches!(self, Self::Succeeded(_))
+    }
+}
+
+/// Hack the target submission with `input`.
+///
+/// The output of the target is compared with `comparer` for
+/// [`JudgeType::Classic`] problems, and checked by the checker of `programs`
+/// for [`JudgeType::SpecialJudge`] problems, as in judging.
+///
+/// Files of the hack are kept in `dir`, which must be readable by the runner
+/// user: the input as `input`, the reference output as `answer`, and the
+/// target output as `output`.
+///
+/// # Errors
+///
+/// Returns an error if the problem is interactive, the checker is not given
+/// for a problem with one, a program cannot be run, or the outputs cannot be
+/// compared.
+pub async fn hack<C>(
+    input: HackData<'_>,
+    programs: HackPrograms<'_>,
+    judge: &JudgeType,
+    comparer: &C,
+    dir: &Path,
+) -> Result<HackOutcome, HackError>
+where
+    C: Comparer + Sync,
+{
+    let checker = match judge {
+        JudgeType::Classic => None,
+        JudgeType::SpecialJudge { .. } => Some(programs.checker.ok_or(HackError::MissingChecker)?),
+        JudgeType::Interactive { .. } => return Err(HackError::Interactive),
+    };
+    let input_path = dir.join("input");
+    let answer_path = dir.join("answer");
+    let output_path = dir.join("output");
+
+    match input {
+        HackData::Input(input) => fs::write(&input_path, input).await?,
+        HackData::Generator { program, args } => {
+            let info = program.run(args, Stdio::null(), &input_path).await?;
+            if let Some(result) = info.result() {
+                return Ok(HackOutcome::GeneratorFailed {
+                    result,
+                    message: info.message(),
+                });
+            }
+        }
+    }
+
+    let info = programs
+        .validator
+        .run(&[], stdin(&input_path).await?, &dir.join("validator.out"))
+        .await?;
+    if !info.is_success() {
+        return Ok(HackOutcome::InvalidInput(info.message()));
+    }
+
+    let info = programs
+        .reference
+        .run(&[], stdin(&input_path).await?, &answer_path)
+        .await?;
+    if let Some(result) = info.result() {
+        return Ok(HackOutcome::ReferenceFailed(result));
+    }
+
+    let info = programs
+        .target
+        .run(&[], stdin(&input_path).await?, &output_path)
+        .await?;
+    if let Some(result) = info.result() {
+        return Ok(HackOutcome::Succeeded(result));
+    }
+
+    let result = match checker {
+        Some(checker) => {
+            let info = checker
+                .check(&input_path, &output_path, &answer_path, dir)
+                .await?;
+            match info.verdict() {
+                Some(result) => result,
+                None => return Ok(HackOutcome::CheckerFailed(info.message())),
+            }
+        }
+        None => {
+            let compared = comparer
+                .compare(
+                    File::open(&output_path).await?,
+                    File::open(&answer_path).await?,
+                )
+                .await?;
+            match compared {
+                CompareResult::Accepted => JudgeResult::Accepted,
+                CompareResult::WrongAnswer => JudgeResult::WrongAnswer,
+                CompareResult::PresentationError => JudgeResult::PresentationError,
+            }
+        }
+    };
+    Ok(match result {
+        JudgeResult::Accepted => HackOutcome::Failed,
+        result => HackOutcome::Succeeded(result),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::judger::JudgeResult;
+
+    use super::{HackData, HackError, HackOutcome, HackPrograms, hack};
+    use crate::comparer::DefaultComparer;
+    use crate::judger::request::cases::JudgeType;
+    use crate::judger::run::test_program;
+
+    #[tokio::test]
+    async fn hack_submission() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let program = |command: &str| test_program(command, dir.path());
+
+        let generator = program("printf '%s\\n'");
+        let validator =
+            program("read n; [ \"$n\" -le 5 ] || { echo 'n is too large' >&2; exit 1; }");
+        let reference = program("read n; echo $((n * 2))");
+        let target = program("read n; if [ \"$n\" -gt 2 ]; then echo 0; else echo $((n * 2)); fi");
+        let programs = HackPrograms {
+            validator: &validator,
+            target: &target,
+            reference: &reference,
+            checker: None,
+        };
+        let comparer = DefaultComparer::common();
+        let judge = JudgeType::Classic;
+
+        let outcome = hack(
+            HackData::Generator {
+                program: &generator,
+                args: &["3".to_owned()],
+            },
+            programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert_eq!(outcome, HackOutcome::Succeeded(JudgeResult::WrongAnswer));
+        assert!(outcome.is_successful());
+
+        let outcome = hack(
+            HackData::Input(b"1\n"),
+            programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert_eq!(outcome, HackOutcome::Failed);
+
+        let outcome = hack(
+            HackData::Input(b"9\n"),
+            programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert_eq!(
+            outcome,
+            HackOutcome::InvalidInput("n is too large\n".to_owned())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hack_with_checker() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let program = |command: &str| test_program(command, dir.path());
+
+        let validator = program("true");
+        let reference = program("read n; echo $((n * 2))");
+        // Leading zeros are accepted by the checker, but not by the comparer.
+        let target = program("read n; if [ \"$n\" -gt 2 ]; then echo 0; else echo 0$((n * 2)); fi");
+        let checker =
+            program("read n < \"$1\"; read out < \"$2\"; [ \"$out\" -eq $((n * 2)) ] || exit 1");
+        let broken = program("exit 3");
+        let programs = HackPrograms {
+            validator: &validator,
+            target: &target,
+            reference: &reference,
+            checker: Some(&checker),
+        };
+        let comparer = DefaultComparer::common();
+        let judge = JudgeType::SpecialJudge {
+            checker: "checker.sh".into(),
+        };
+
+        let outcome = hack(
+            HackData::Input(b"1\n"),
+            programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert_eq!(outcome, HackOutcome::Failed);
+
+        let outcome = hack(
+            HackData::Input(b"3\n"),
+            programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert_eq!(outcome, HackOutcome::Succeeded(JudgeResult::WrongAnswer));
+
+        let broken_programs = HackPrograms {
+            checker: Some(&broken),
+            ..programs
+        };
+        let outcome = hack(
+            HackData::Input(b"1\n"),
+            broken_programs,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await?;
+        assert!(matches!(outcome, HackOutcome::CheckerFailed(_)));
+
+        let no_checker = HackPrograms {
+            checker: None,
+            ..programs
+        };
+        let error = hack(
+            HackData::Input(b"1\n"),
+            no_checker,
+            &judge,
+            &comparer,
+            dir.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, HackError::MissingChecker));
+        Ok(())
+    }
+}
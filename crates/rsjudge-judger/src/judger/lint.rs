@@ -0,0 +1,378 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lint of a problem directory and its [`CasesConfig`], so that a malformed
+//! problem is reported before any submission is judged with it.
+//!
+//! Findings are serializable, to be consumed by tools.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use rsjudge_traits::language::config::CompileLimits;
+use rsjudge_traits::language::template::TemplateVars;
+use rsjudge_traits::resource::ResourceLimit;
+use serde::Serialize;
+use tokio::fs;
+use tokio::process::Command;
+
+use crate::judger::compile::compile;
+use crate::judger::request::cases::{CASES_CONFIG_FILE, CasesConfig, JudgeType};
+
+/// CPU time limits above this are likely a mistake of the unit.
+const MAX_SANE_TIME: Duration = Duration::from_secs(60);
+/// Memory limits below this are likely a mistake of the unit.
+const MIN_SANE_MEMORY: u64 = 16 << 20;
+/// Memory limits above this are likely a mistake of the unit.
+const MAX_SANE_MEMORY: u64 = 4 << 30;
+
+/// Severity of a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// The problem cannot be judged correctly.
+    Error,
+    /// The problem can be judged, but is likely misconfigured.
+    Warning,
+}
+
+/// Kind of a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintKind {
+    /// The config cannot be read or parsed.
+    InvalidConfig,
+    /// A referenced file does not exist.
+    MissingFile,
+    /// A referenced file is outside the problem directory.
+    OutsideRoot,
+    /// Scores are inconsistent.
+    InvalidScore,
+    /// A checker or interactor fails to compile.
+    CompileFailed,
+    /// Compilation of a checker or interactor is not checked.
+    CompileSkipped,
+    /// A resource limit is invalid or suspicious.
+    InvalidLimit,
+}
+
+/// A problem found by the lint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub level: LintLevel,
+    pub kind: LintKind,
+    pub message: String,
+    /// The file the finding refers to, relative to the problem directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl Finding {
+    fn error(kind: LintKind, message: impl Into<String>) -> Self {
+        Self {
+            level: LintLevel::Error,
+            kind,
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    fn warning(kind: LintKind, message: impl Into<String>) -> Self {
+        Self {
+            level: LintLevel::Warning,
+            ..Self::error(kind, message)
+        }
+    }
+
+    fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+}
+
+/// Options of the lint.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    /// Command template checking that checker and interactor sources compile,
+    /// with the source file as `{src_file}`, e.g. `g++ -fsyntax-only
+    /// {src_file}`.
+    ///
+    /// Compilation is not checked if not set, which is reported as a
+    /// [`LintKind::CompileSkipped`] warning.
+    pub compile_command: Option<String>,
+}
+
+/// Lint the problem in `cases_dir`, reading its config from
+/// [`CASES_CONFIG_FILE`].
+///
+/// # Errors
+///
+/// Returns an error if a check cannot be run, e.g. the compiler cannot be
+/// spawned.
+pub async fn lint_problem(cases_dir: &Path, options: &LintOptions) -> io::Result<Vec<Finding>> {
+    let config_path = Path::new(CASES_CONFIG_FILE);
+    let config = match fs::read(cases_dir.join(config_path)).await {
+        Ok(config) => config,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(vec![
+                Finding::error(LintKind::MissingFile, "Missing cases config")
+                    .with_path(config_path),
+            ]);
+        }
+        Err(error) => return Err(error),
+    };
+
+    match serde_json::from_slice::<CasesConfig>(&config) {
+        Ok(config) => lint_config(&config, cases_dir, options).await,
+        Err(error) => Ok(vec![
+            Finding::error(LintKind::InvalidConfig, error.to_string()).with_path(config_path),
+        ]),
+    }
+}
+
+/// Lint `config` of the problem in `cases_dir`.
+///
+/// # Errors
+///
+/// Returns an error if a check cannot be run, e.g. the compiler cannot be
+/// spawned.
+pub async fn lint_config(
+    config: &CasesConfig,
+    cases_dir: &Path,
+    options: &LintOptions,
+) -> io::Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let root = fs::canonicalize(cases_dir).await?;
+
+    let mut files: Vec<&Path> = config
+        .cases()
+        .flat_map(|case| [case.input(), case.answer()])
+        .collect();
+    let program = match &config.judge {
+        JudgeType::Classic => None,
+        JudgeType::SpecialJudge { checker } => Some(checker.as_path()),
+        JudgeType::Interactive { interactor } => Some(interactor.as_path()),
+    };
+    files.extend(program);
+    for grader in config.graders.values() {
+        files.extend(
+            grader
+                .sources
+                .iter()
+                .chain(&grader.headers)
+                .map(PathBuf::as_path),
+        );
+    }
+    files.extend(config.validator().map(|validator| validator.source()));
+
+    for path in files {
+        findings.extend(check_file(&root, path).await?);
+    }
+
+    if let Err(error) = config.score_plan() {
+        findings.push(Finding::error(LintKind::InvalidScore, error.to_string()));
+    }
+
+    findings.extend(check_limits(&ResourceLimit::from(config.resource_limits())));
+
+    match (program, &options.compile_command) {
+        (Some(program), Some(template)) => {
+            findings.extend(check_compile(&root, program, template).await?);
+        }
+        (Some(program), None) => findings.push(
+            Finding::warning(
+                LintKind::CompileSkipped,
+                "Compilation is not checked, as no compile command is given",
+            )
+            .with_path(program),
+        ),
+        (None, _) => {}
+    }
+
+    Ok(findings)
+}
+
+/// Check that `path` exists inside `root`, following symlinks.
+async fn check_file(root: &Path, path: &Path) -> io::Result<Option<Finding>> {
+    let is_relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    let outside = || {
+        Finding::error(
+            LintKind::OutsideRoot,
+            "File is outside the problem directory",
+        )
+        .with_path(path)
+    };
+    if !is_relative {
+        return Ok(Some(outside()));
+    }
+
+    match fs::canonicalize(root.join(path)).await {
+        Ok(canonical) if canonical.starts_with(root) => Ok(None),
+        Ok(_) => Ok(Some(outside())),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Some(
+            Finding::error(LintKind::MissingFile, "File does not exist").with_path(path),
+        )),
+        Err(error) => Err(error),
+    }
+}
+
+fn check_limits(limit: &ResourceLimit) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let time = limit.cpu_time_limit().unwrap_or_default();
+    let memory = limit.memory_limit().unwrap_or_default();
+
+    if time.is_zero() {
+        findings.push(Finding::error(LintKind::InvalidLimit, "Time limit is zero"));
+    } else if time > MAX_SANE_TIME {
+        findings.push(Finding::warning(
+            LintKind::InvalidLimit,
+            format!("Time limit {time:?} is too long, the unit is milliseconds"),
+        ));
+    }
+    if limit
+        .wall_time_limit()
+        .is_some_and(|wall_time| wall_time < time)
+    {
+        findings.push(Finding::error(
+            LintKind::InvalidLimit,
+            "Wall time limit is shorter than the time limit",
+        ));
+    }
+
+    if memory == 0 {
+        findings.push(Finding::error(
+            LintKind::InvalidLimit,
+            "Memory limit is zero",
+        ));
+    } else if !(MIN_SANE_MEMORY..=MAX_SANE_MEMORY).contains(&memory) {
+        findings.push(Finding::warning(
+            LintKind::InvalidLimit,
+            format!(
+                "Memory limit of {} MiB is suspicious, the unit is MiB",
+                memory >> 20
+            ),
+        ));
+    }
+    if limit.stack_limit().is_some_and(|stack| stack > memory) {
+        findings.push(Finding::warning(
+            LintKind::InvalidLimit,
+            "Stack limit exceeds the memory limit",
+        ));
+    }
+
+    findings
+}
+
+/// Check that the checker or interactor at `path` compiles.
+async fn check_compile(root: &Path, path: &Path, template: &str) -> io::Result<Option<Finding>> {
+    let mut vars = TemplateVars::new();
+    vars.set("src_file", root.join(path).to_string_lossy());
+    let script = match vars.render(template) {
+        Ok(script) => script,
+        Err(error) => {
+            return Ok(Some(Finding::error(
+                LintKind::CompileFailed,
+                error.to_string(),
+            )));
+        }
+    };
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).current_dir(root);
+    let info = compile(command, &CompileLimits::default(), &[])
+        .await
+        .map_err(io::Error::other)?;
+
+    Ok((!info.is_success()).then(|| {
+        Finding::error(
+            LintKind::CompileFailed,
+            format!(
+                "{}\n{}",
+                info.status(),
+                String::from_utf8_lossy(info.stderr())
+            ),
+        )
+        .with_path(path)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{LintKind, LintLevel, LintOptions, lint_problem};
+    use crate::judger::request::cases::CASES_CONFIG_FILE;
+
+    #[tokio::test]
+    async fn lint_problem_dir() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = LintOptions {
+            compile_command: Some("sh -n {src_file}".to_owned()),
+        };
+
+        let findings = lint_problem(dir.path(), &options).await?;
+        assert_eq!(findings[0].kind, LintKind::MissingFile);
+
+        fs::write(dir.path().join("1.in"), "").await?;
+        fs::write(dir.path().join("checker.sh"), "if then").await?;
+        let config = json!({
+            "score": 100,
+            "judge": { "judgeType": "special-judge", "checker": "checker.sh" },
+            "resourceLimits": { "time": 1000000, "memory": 256 },
+            "task": {
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans", "score": 60 },
+                    { "input": "../2.in", "answer": "2.ans", "score": 60 }
+                ]
+            }
+        });
+        fs::write(
+            dir.path().join(CASES_CONFIG_FILE),
+            serde_json::to_vec(&config)?,
+        )
+        .await?;
+
+        let findings = lint_problem(dir.path(), &options).await?;
+        let kinds: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.kind, finding.path.as_deref()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (LintKind::MissingFile, Some(Path::new("1.ans"))),
+                (LintKind::OutsideRoot, Some(Path::new("../2.in"))),
+                (LintKind::MissingFile, Some(Path::new("2.ans"))),
+                (LintKind::InvalidScore, None),
+                (LintKind::InvalidLimit, None),
+                (LintKind::CompileFailed, Some(Path::new("checker.sh"))),
+            ]
+        );
+        assert_eq!(findings[4].level, LintLevel::Warning);
+
+        let findings = lint_problem(dir.path(), &LintOptions::default()).await?;
+        let skipped = findings.last().unwrap();
+        assert_eq!(skipped.kind, LintKind::CompileSkipped);
+        assert_eq!(skipped.level, LintLevel::Warning);
+        assert_eq!(skipped.path.as_deref(), Some(Path::new("checker.sh")));
+
+        let serialized = serde_json::to_value(&findings[0])?;
+        assert_eq!(
+            serialized,
+            json!({
+                "level": "error",
+                "kind": "missing-file",
+                "message": "File does not exist",
+                "path": "1.ans"
+            })
+        );
+        Ok(())
+    }
+}
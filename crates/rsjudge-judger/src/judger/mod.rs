@@ -1,3 +1,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod cache;
+pub mod calibrate;
+pub mod compile;
+pub mod convert;
+pub mod diagnostic;
+pub mod discover;
+pub mod file_io;
+pub mod grader;
+pub mod hack;
+pub mod lint;
+pub mod output_only;
+pub mod prepare;
 pub mod request;
+pub mod run;
+pub mod score;
+pub mod stress;
+pub mod validate;
@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Judging of output-only problems, where submissions are the output files of
+//! all cases instead of a program.
+//!
+//! Nothing is compiled or run. Each submitted file is matched with a [`Case`]
+//! by its [output name], and compared with the answer or checked by the
+//! checker of the problem, so a missing file only fails its own case.
+//!
+//! [`Case`]: crate::judger::request::cases::Case
+//! [output name]: crate::judger::request::cases::Case::output_name
+
+use std::io;
+use std::path::Path;
+
+use rsjudge_traits::judger::JudgeResult;
+use thiserror::Error;
+use tokio::fs::{self, File};
+
+use crate::comparer::{CompareResult, Comparer};
+use crate::judger::request::cases::{Case, CasesConfig, JudgeType};
+use crate::judger::request::source::Code;
+use crate::judger::run::Program;
+use crate::judger::score::{CaseOutcome, ScorePlan, ScoreSummary};
+
+/// Error judging the outputs of an output-only problem.
+#[derive(Debug, Error)]
+pub enum OutputOnlyError {
+    #[error("Checker of the problem is not given")]
+    MissingChecker,
+    #[error("Interactive problems cannot be output-only")]
+    Interactive,
+    #[error("Checker failed on case {case}: {message}")]
+    CheckerFailed { case: u32, message: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+}
+
+/// Judge the submitted `outputs` against the answers in `cases_dir`.
+///
+/// Outputs of [`JudgeType::Classic`] problems are compared with `comparer`,
+/// and those of [`JudgeType::SpecialJudge`] problems are checked by the
+/// compiled `checker` as `checker <input> <output> <answer>`, with each output
+/// written into `dir`, which must be readable by the runner user. A single
+/// file submission is taken as the output of a problem with a single case.
+///
+/// # Errors
+///
+/// Returns an error if the problem is interactive, the checker is not given
+/// for a problem with one, the checker fails, or a file cannot be read.
+pub async fn judge_outputs<C>(
+    config: &CasesConfig,
+    plan: &ScorePlan,
+    cases_dir: &Path,
+    outputs: &Code,
+    comparer: &C,
+    checker: Option<&Program>,
+    dir: &Path,
+) -> Result<ScoreSummary, OutputOnlyError>
+where
+    C: Comparer + Sync,
+{
+    let checker = match config.judge {
+        JudgeType::Classic => None,
+        JudgeType::SpecialJudge { .. } => Some(checker.ok_or(OutputOnlyError::MissingChecker)?),
+        JudgeType::Interactive { .. } => return Err(OutputOnlyError::Interactive),
+    };
+    let mut scheduler = plan.scheduler();
+
+    while let Some(case_ref) = scheduler.next_case() {
+        let Some(case) = config.case(case_ref) else {
+            break;
+        };
+
+        let output = match outputs {
+            Code::Single(code) if plan.case_count() == 1 => Some(code),
+            Code::Single(_) => None,
+            Code::Files(files) => {
+                let name = case.output_name();
+                files
+                    .iter()
+                    .find(|file| file.path() == name)
+                    .map(|file| file.content())
+            }
+        };
+
+        let result = match (output, checker) {
+            (None, _) => JudgeResult::WrongAnswer,
+            (Some(output), None) => {
+                let answer = File::open(cases_dir.join(case.answer())).await?;
+                match comparer.compare(&output[..], answer).await? {
+                    CompareResult::Accepted => JudgeResult::Accepted,
+                    CompareResult::WrongAnswer => JudgeResult::WrongAnswer,
+                    CompareResult::PresentationError => JudgeResult::PresentationError,
+                }
+            }
+            (Some(output), Some(checker)) => {
+                check(checker, case, case_ref.id, cases_dir, output, dir).await?
+            }
+        };
+        scheduler.record(case_ref, CaseOutcome::new(result));
+    }
+
+    Ok(scheduler.finish())
+}
+
+/// Check `output` of case `id` with the testlib `checker`.
+async fn check(
+    checker: &Program,
+    case: &Case,
+    id: u32,
+    cases_dir: &Path,
+    output: &[u8],
+    dir: &Path,
+) -> Result<JudgeResult, OutputOnlyError> {
+    let output_path = dir.join("output");
+    fs::write(&output_path, output).await?;
+
+    let info = checker
+        .check(
+            &cases_dir.join(case.input()),
+            &output_path,
+            &cases_dir.join(case.answer()),
+            dir,
+        )
+        .await?;
+    info.verdict()
+        .ok_or_else(|| OutputOnlyError::CheckerFailed {
+            case: id,
+            message: info.message(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::judger::JudgeResult;
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{OutputOnlyError, judge_outputs};
+    use crate::judger::request::cases::CasesConfig;
+    use crate::judger::request::source::{Code, SourceFile};
+    use crate::judger::run::test_program;
+
+    #[tokio::test]
+    async fn judge_output_files() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        for (name, answer) in [("1.ans", "1\n"), ("2.ans", "2\n"), ("3.ans", "3\n")] {
+            fs::write(cases_dir.path().join(name), answer).await?;
+        }
+
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 90,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans" },
+                    { "input": "2.in", "answer": "2.ans" },
+                    { "input": "3.in", "answer": "3.ans", "output": "third.txt" }
+                ]
+            },
+            "outputOnly": true
+        }))?;
+        assert!(config.is_output_only());
+        let plan = config.score_plan()?;
+
+        let outputs = Code::files(vec![
+            SourceFile::new("1.out", "1\n".into())?,
+            SourceFile::new("third.txt", "3".into())?,
+        ])?;
+        let summary = judge_outputs(
+            &config,
+            &plan,
+            cases_dir.path(),
+            &outputs,
+            &config.comparer(),
+            None,
+            cases_dir.path(),
+        )
+        .await?;
+
+        let results: Vec<_> = summary.subtasks[0]
+            .cases
+            .iter()
+            .map(|case| case.result)
+            .collect();
+        assert_eq!(
+            results,
+            [
+                JudgeResult::Accepted,
+                JudgeResult::WrongAnswer,
+                JudgeResult::Accepted
+            ]
+        );
+        assert_eq!(summary.score, 60);
+        assert_eq!(summary.failed_case, Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn judge_with_checker() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let dir = tempfile::tempdir()?;
+        for (name, content) in [
+            ("1.in", "2\n"),
+            ("1.ans", "4\n"),
+            ("2.in", "3\n"),
+            ("2.ans", "9\n"),
+        ] {
+            fs::write(cases_dir.path().join(name), content).await?;
+        }
+
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "special-judge",
+                "checker": "checker.sh"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans" },
+                    { "input": "2.in", "answer": "2.ans" }
+                ]
+            },
+            "outputOnly": true
+        }))?;
+        let plan = config.score_plan()?;
+        let outputs = Code::files(vec![
+            SourceFile::new("1.out", "4".into())?,
+            SourceFile::new("2.out", "6\n".into())?,
+        ])?;
+        let comparer = config.comparer();
+        // Accept any output with the square of the input, ignoring whitespace.
+        let checker = test_program(
+            r#"i=$(cat "$1"); o=$(cat "$2"); [ "$o" -eq $((i * i)) ] || exit 1"#,
+            cases_dir.path(),
+        );
+        let summary = judge_outputs(
+            &config,
+            &plan,
+            cases_dir.path(),
+            &outputs,
+            &comparer,
+            Some(&checker),
+            dir.path(),
+        )
+        .await?;
+        let results: Vec<_> = summary.subtasks[0]
+            .cases
+            .iter()
+            .map(|case| case.result)
+            .collect();
+        assert_eq!(results, [JudgeResult::Accepted, JudgeResult::WrongAnswer]);
+
+        let missing = judge_outputs(
+            &config,
+            &plan,
+            cases_dir.path(),
+            &outputs,
+            &comparer,
+            None,
+            dir.path(),
+        )
+        .await;
+        assert!(matches!(missing, Err(OutputOnlyError::MissingChecker)));
+
+        let broken = test_program("exit 3", cases_dir.path());
+        let failed = judge_outputs(
+            &config,
+            &plan,
+            cases_dir.path(),
+            &outputs,
+            &comparer,
+            Some(&broken),
+            dir.path(),
+        )
+        .await;
+        assert!(matches!(
+            failed,
+            Err(OutputOnlyError::CheckerFailed { case: 1, .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Problem preparation, producing test data from generators.
+//!
+//! Each test is generated by a generator invocation, checked by the validator,
+//! and answered by the model solution, all running as sandboxed [`Program`]s.
+//! The output is a cases directory with the [`CasesConfig`] of the tests in
+//! [`CASES_CONFIG_FILE`].
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::process::Stdio;
+
+use rsjudge_traits::judger::JudgeResult;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+
+use crate::judger::request::cases::{
+    CASES_CONFIG_FILE, Case, CasesConfig, JudgeType, ResourceLimits, TaskType,
+};
+use crate::judger::run::{Program, stdin};
+
+/// Error preparing a problem.
+///
+/// Tests are numbered from 1.
+#[derive(Debug, Error)]
+pub enum PrepareError {
+    #[error("Unknown generator in `{0}`")]
+    UnknownGenerator(GeneratorCall),
+    #[error("Generator `{call}` of test {test} failed with {result:?}: {message}")]
+    Generator {
+        test: usize,
+        call: GeneratorCall,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error("Input of test {test} generated by `{call}` is invalid: {message}")]
+    InvalidInput {
+        test: usize,
+        call: GeneratorCall,
+        message: String,
+    },
+    #[error("Solution failed on test {test} generated by `{call}` with {result:?}: {message}")]
+    Solution {
+        test: usize,
+        call: GeneratorCall,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Configuration of a preparation.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrepareConfig {
+    pub(crate) score: NonZeroU32,
+    pub(crate) judge: JudgeType,
+    pub(crate) resource_limits: ResourceLimits,
+    /// Generator invocations, one per test in order.
+    pub(crate) tests: Vec<GeneratorCall>,
+}
+
+/// An invocation of a generator, printing the input of a test.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GeneratorCall {
+    /// Name of the generator.
+    pub(crate) generator: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) args: Vec<String>,
+}
+
+impl Display for GeneratorCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.generator)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiled programs preparing a problem.
+#[derive(Debug, Clone, Copy)]
+pub struct PreparePrograms<'a> {
+    /// Generators by name.
+    pub generators: &'a HashMap<String, Program>,
+    /// The input validator, exiting successfully for valid input.
+    pub validator: Option<&'a Program>,
+    /// The model solution, whose output is taken as the answer.
+    pub solution: &'a Program,
+}
+
+/// Generate the tests of `config` into `cases_dir`, as `1.in`, `1.ans`, etc.,
+/// and write the resulting [`CasesConfig`].
+///
+/// # Errors
+///
+/// Returns an error pointing to the generator invocation if a program fails
+/// on a test, or an error if a file cannot be written.
+pub async fn prepare(
+    config: PrepareConfig,
+    programs: PreparePrograms<'_>,
+    cases_dir: &Path,
+) -> Result<CasesConfig, PrepareError> {
+    fs::create_dir_all(cases_dir).await?;
+    let mut cases = Vec::with_capacity(config.tests.len());
+
+    for (index, call) in config.tests.into_iter().enumerate() {
+        let test = index + 1;
+        let input = format!("{test}.in");
+        let answer = format!("{test}.ans");
+        let input_path = cases_dir.join(&input);
+        let answer_path = cases_dir.join(&answer);
+
+        let Some(generator) = programs.generators.get(&call.generator) else {
+            return Err(PrepareError::UnknownGenerator(call));
+        };
+        let info = generator
+            .run(&call.args, Stdio::null(), &input_path)
+            .await?;
+        if let Some(result) = info.result() {
+            return Err(PrepareError::Generator {
+                test,
+                call,
+                result,
+                message: info.message(),
+            });
+        }
+
+        if let Some(validator) = programs.validator {
+            let info = validator
+                .run(
+                    &[],
+                    stdin(&input_path).await?,
+                    &cases_dir.join("validator.out"),
+                )
+                .await?;
+            fs::remove_file(cases_dir.join("validator.out")).await?;
+            if !info.is_success() {
+                return Err(PrepareError::InvalidInput {
+                    test,
+                    call,
+                    message: info.message(),
+                });
+            }
+        }
+
+        let info = programs
+            .solution
+            .run(&[], stdin(&input_path).await?, &answer_path)
+            .await?;
+        if let Some(result) = info.result() {
+            return Err(PrepareError::Solution {
+                test,
+                call,
+                result,
+                message: info.message(),
+            });
+        }
+
+        cases.push(Case::new(input, answer));
+    }
+
+    let cases_config = CasesConfig::new(
+        config.score,
+        config.judge,
+        config.resource_limits,
+        TaskType::Simple { cases },
+    );
+    fs::write(
+        cases_dir.join(CASES_CONFIG_FILE),
+        serde_json::to_vec_pretty(&cases_config).map_err(io::Error::from)?,
+    )
+    .await?;
+
+    Ok(cases_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{PrepareConfig, PrepareError, PreparePrograms, prepare};
+    use crate::judger::request::cases::{CASES_CONFIG_FILE, CasesConfig};
+    use crate::judger::run::test_program;
+
+    #[tokio::test]
+    async fn prepare_problem() -> anyhow::Result<()> {
+        let work_dir = tempfile::tempdir()?;
+        let cases_dir = tempfile::tempdir()?;
+        let program = |command: &str| test_program(command, work_dir.path());
+
+        let generators = HashMap::from([("gen".to_owned(), program("printf '%s %s\\n'"))]);
+        let validator = program("read a b; [ \"$a\" -ge 0 ] || { echo 'a < 0' >&2; exit 1; }");
+        let solution = program("read a b; echo $((a + b))");
+        let programs = PreparePrograms {
+            generators: &generators,
+            validator: Some(&validator),
+            solution: &solution,
+        };
+        let config = |tests| {
+            serde_json::from_value::<PrepareConfig>(json!({
+                "score": 100,
+                "judge": { "judgeType": "classic" },
+                "resourceLimits": { "time": 1000, "memory": 256 },
+                "tests": tests,
+            }))
+        };
+
+        let cases_config = prepare(
+            config(json!([
+                { "generator": "gen", "args": ["1", "2"] },
+                { "generator": "gen", "args": ["3", "4"] },
+            ]))?,
+            programs,
+            cases_dir.path(),
+        )
+        .await?;
+        assert_eq!(cases_config.score_plan()?.case_count(), 2);
+        assert_eq!(
+            fs::read_to_string(cases_dir.path().join("2.in")).await?,
+            "3 4\n"
+        );
+        assert_eq!(
+            fs::read_to_string(cases_dir.path().join("2.ans")).await?,
+            "7\n"
+        );
+        let saved = fs::read(cases_dir.path().join(CASES_CONFIG_FILE)).await?;
+        assert_eq!(
+            serde_json::from_slice::<CasesConfig>(&saved)?
+                .score_plan()?
+                .case_count(),
+            2
+        );
+
+        let error = prepare(
+            config(json!([
+                { "generator": "gen", "args": ["1", "2"] },
+                { "generator": "gen", "args": ["-1", "2"] },
+            ]))?,
+            programs,
+            cases_dir.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, PrepareError::InvalidInput { test: 2, .. }));
+        assert_eq!(
+            error.to_string(),
+            "Input of test 2 generated by `gen -1 2` is invalid: a < 0\n"
+        );
+        Ok(())
+    }
+}
@@ -1,17 +1,138 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use std::num::NonZeroU32;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use rsjudge_traits::language::config::{LimitFactors, deserialize_factor};
+use rsjudge_traits::resource::ResourceLimit;
 use serde::{Deserialize, Serialize};
 
+use crate::comparer::{DefaultComparer, Normalization};
+use crate::judger::score::{CaseRef, ScoreError, ScorePlan};
+
+/// Name of the file holding the [`CasesConfig`] in a cases directory.
+pub const CASES_CONFIG_FILE: &str = "cases.json";
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CasesConfig {
-    score: NonZeroU32,
-    judge: JudgeType,
-    resource_limits: ResourceLimits,
-    task: TaskType,
+    pub(crate) score: NonZeroU32,
+    pub(crate) judge: JudgeType,
+    pub(crate) resource_limits: ResourceLimits,
+    pub(crate) task: TaskType,
+    #[serde(default)]
+    pub(crate) mode: JudgeMode,
+    #[serde(default)]
+    pub(crate) compare: CompareConfig,
+    /// Skip the compile cache for submissions of the problem.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    disable_compile_cache: bool,
+    /// Graders linked with submissions, keyed by language name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) graders: HashMap<String, Grader>,
+    /// Files used instead of stdio, if any.
+    #[serde(default, skip_serializing_if = "IoConfig::is_stdio")]
+    pub(crate) io: IoConfig,
+    /// Whether submissions are output files instead of programs.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) output_only: bool,
+    /// Validator checking the input of all cases when the problem is loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) validator: Option<InputValidator>,
+}
+
+impl CasesConfig {
+    /// Create a config with the defaults of optional settings.
+    pub fn new(
+        score: NonZeroU32,
+        judge: JudgeType,
+        resource_limits: ResourceLimits,
+        task: TaskType,
+    ) -> Self {
+        Self {
+            score,
+            judge,
+            resource_limits,
+            task,
+            mode: JudgeMode::default(),
+            compare: CompareConfig::default(),
+            disable_compile_cache: false,
+            graders: HashMap::new(),
+            io: IoConfig::default(),
+            output_only: false,
+            validator: None,
+        }
+    }
+
+    /// Get the comparer configured for the problem.
+    pub fn comparer(&self) -> DefaultComparer {
+        self.compare.comparer()
+    }
+
+    /// Whether compilation results can be taken from the compile cache.
+    pub fn compile_cache_enabled(&self) -> bool {
+        !self.disable_compile_cache
+    }
+
+    /// Get the grader of `language`, if the problem has one.
+    pub fn grader(&self, language: &str) -> Option<&Grader> {
+        self.graders.get(language)
+    }
+
+    /// Whether submissions are archives of output files, judged without
+    /// compiling or running anything.
+    pub fn is_output_only(&self) -> bool {
+        self.output_only
+    }
+
+    /// Get all cases in order.
+    pub fn cases(&self) -> Box<dyn Iterator<Item = &Case> + '_> {
+        match &self.task {
+            TaskType::Simple { cases } => Box::new(cases.iter()),
+            TaskType::Subtask { subtasks } => {
+                Box::new(subtasks.iter().flat_map(|subtask| &subtask.cases))
+            }
+        }
+    }
+
+    /// Get the case referred to by `case`.
+    pub fn case(&self, case: CaseRef) -> Option<&Case> {
+        match &self.task {
+            TaskType::Simple { cases } if case.subtask == 0 => cases.get(case.case),
+            TaskType::Simple { .. } => None,
+            TaskType::Subtask { subtasks } => subtasks.get(case.subtask)?.cases.get(case.case),
+        }
+    }
+
+    /// Get the input validator of the problem, if any.
+    pub fn validator(&self) -> Option<&InputValidator> {
+        self.validator.as_ref()
+    }
+
+    /// Get how submissions of the problem read input and write output.
+    pub fn io(&self) -> &IoConfig {
+        &self.io
+    }
+
+    /// Get the resource limits of the problem.
+    pub fn resource_limits(&self) -> &ResourceLimits {
+        &self.resource_limits
+    }
+
+    /// Validate the scores of cases and subtasks, and resolve them into a
+    /// [`ScorePlan`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scores do not add up to [`score`], or the
+    /// subtask dependencies are invalid.
+    ///
+    /// [`score`]: CasesConfig::score
+    pub fn score_plan(&self) -> Result<ScorePlan, ScoreError> {
+        ScorePlan::new(self)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,10 +144,274 @@ pub enum JudgeType {
     Interactive { interactor: PathBuf },
 }
 
+/// Grader compiled together with submissions, as in IOI-style problems.
+///
+/// Paths are relative to the cases directory.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Grader {
+    /// Source files, available as `{grader_files}` in compile templates.
+    pub(crate) sources: Vec<PathBuf>,
+    /// Other files needed for compilation, e.g. headers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) headers: Vec<PathBuf>,
+}
+
+/// Files read and written by submissions instead of stdin and stdout, as in
+/// problems reading `input.txt` and writing `output.txt`.
+///
+/// File names are relative to the working directory of the run, without any
+/// directory part.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IoConfig {
+    /// File the input is copied to before the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) input_file: Option<String>,
+    /// File the output is collected from after the run, instead of stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output_file: Option<String>,
+    /// Also provide the input on stdin when it is read from a file.
+    ///
+    /// Otherwise stdin is empty.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) stdin: bool,
+}
+
+/// Input validator of a problem, as in testlib, reading the input from stdin
+/// and exiting successfully if it is valid.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InputValidator {
+    /// Source file, relative to the cases directory.
+    pub(crate) source: PathBuf,
+    /// Language of the source file.
+    pub(crate) language: String,
+}
+
+impl InputValidator {
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+}
+
+/// How cases of a submission are judged.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JudgeMode {
+    /// Judge all cases, except those skipped by subtask policies.
+    #[default]
+    Oi,
+    /// Stop at the first case not accepted, as in ACM/ICPC contests.
+    Icpc,
+}
+
+/// Configuration of the [`DefaultComparer`].
+///
+/// Defaults to [`DefaultComparer::common`] without normalization.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompareConfig {
+    case_sensitive: bool,
+    ignore_trailing_whitespace: bool,
+    ignore_trailing_newline: bool,
+    normalization: Normalization,
+}
+
+impl CompareConfig {
+    fn comparer(&self) -> DefaultComparer {
+        DefaultComparer::new(
+            self.case_sensitive,
+            self.ignore_trailing_whitespace,
+            self.ignore_trailing_newline,
+        )
+        .with_normalization(self.normalization)
+    }
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        Self {
+            case_sensitive: true,
+            ignore_trailing_whitespace: true,
+            ignore_trailing_newline: true,
+            normalization: Normalization::NONE,
+        }
+    }
+}
+
+/// Resource limits of the problem.
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResourceLimits {
+    /// CPU time limit **in milliseconds**.
     time: u32,
+    /// Memory limit **in MiB**.
     memory: u32,
+    /// Wall time limit **in milliseconds**.
+    ///
+    /// Defaults to twice the CPU time limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wall_time: Option<NonZeroU32>,
+    /// Stack size limit **in MiB**.
+    ///
+    /// Defaults to the memory limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stack: Option<NonZeroU32>,
+    /// Max number of processes and threads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    processes: Option<NonZeroU32>,
+    /// Max number of open file descriptors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    open_files: Option<NonZeroU32>,
+    /// Max output size **in bytes**.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<NonZeroU64>,
+    /// Per-language overrides of [`LimitFactors`], keyed by language name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    languages: HashMap<String, LanguageLimits>,
+}
+
+/// Override of [`LimitFactors`] for a language in a problem.
+///
+/// Factors not set here are taken from the language definition.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageLimits {
+    #[serde(
+        default,
+        deserialize_with = "deserialize_factor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    time_factor: Option<f64>,
+    /// Extra time **in milliseconds**.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time_bonus: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_factor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    memory_factor: Option<f64>,
+    /// Extra memory **in MiB**.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory_bonus: Option<u32>,
+}
+
+impl From<LanguageLimits> for LimitFactors {
+    fn from(limits: LanguageLimits) -> Self {
+        Self {
+            time_factor: limits.time_factor,
+            time_bonus: limits.time_bonus,
+            memory_factor: limits.memory_factor,
+            memory_bonus: limits.memory_bonus,
+        }
+    }
+}
+
+/// Resource limits of a problem before and after applying [`LimitFactors`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedLimits {
+    /// Limits as declared by the problem.
+    pub raw: ResourceLimit,
+    /// Limits actually applied to the submission.
+    pub effective: ResourceLimit,
+}
+
+impl ResourceLimits {
+    /// Create limits with the CPU time limit **in milliseconds** and the
+    /// memory limit **in MiB**, using defaults for the others.
+    pub fn new(time: u32, memory: u32) -> Self {
+        Self {
+            time,
+            memory,
+            wall_time: None,
+            stack: None,
+            processes: None,
+            open_files: None,
+            output: None,
+            languages: HashMap::new(),
+        }
+    }
+
+    /// Get the CPU time limit.
+    pub fn time(&self) -> Duration {
+        Duration::from_millis(self.time.into())
+    }
+
+    /// Get the memory limit **in bytes**.
+    pub fn memory(&self) -> u64 {
+        mib(self.memory)
+    }
+
+    /// Whether limits other than the CPU time and memory limits are set.
+    pub(crate) fn has_extra_limits(&self) -> bool {
+        self.wall_time.is_some()
+            || self.stack.is_some()
+            || self.processes.is_some()
+            || self.open_files.is_some()
+            || self.output.is_some()
+            || !self.languages.is_empty()
+    }
+
+    /// Resolve the limits for `language`, applying `factors` from the language
+    /// definition, overridden by the problem.
+    pub fn resolve(&self, language: &str, factors: &LimitFactors) -> ResolvedLimits {
+        let factors = self
+            .languages
+            .get(language)
+            .map_or(*factors, |&limits| factors.merge(limits.into()));
+
+        let raw = ResourceLimit::from(self);
+        let mut effective = raw;
+        effective
+            .set_cpu_time_limit(raw.cpu_time_limit().map(|time| factors.apply_time(time)))
+            .set_wall_time_limit(raw.wall_time_limit().map(|time| factors.apply_time(time)))
+            .set_memory_limit(
+                raw.memory_limit()
+                    .and_then(|memory| NonZeroU64::new(factors.apply_memory(memory))),
+            );
+        // Follow the effective memory limit, unless set explicitly.
+        if self.stack.is_none() {
+            effective.set_stack_limit(None);
+        }
+
+        ResolvedLimits { raw, effective }
+    }
+}
+
+impl From<&ResourceLimits> for ResourceLimit {
+    fn from(limits: &ResourceLimits) -> Self {
+        let wall_time = limits.wall_time.map_or(limits.time() * 2, |wall_time| {
+            Duration::from_millis(wall_time.get().into())
+        });
+
+        let mut limit = Self::new(
+            Some(limits.time()),
+            Some(wall_time),
+            NonZeroU64::new(limits.memory()),
+            None,
+        );
+        limit
+            .set_stack_limit(
+                limits
+                    .stack
+                    .and_then(|stack| NonZeroU64::new(mib(stack.get()))),
+            )
+            .set_process_limit(limits.processes.map(From::from))
+            .set_open_files_limit(limits.open_files.map(From::from))
+            .set_output_limit(limits.output);
+        limit
+    }
+}
+
+/// Convert MiB to bytes.
+fn mib(value: u32) -> u64 {
+    u64::from(value) << 20
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -37,30 +422,110 @@ pub enum TaskType {
     Subtask { subtasks: Vec<Subtask> },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Case {
     input: PathBuf,
     answer: PathBuf,
 
+    /// Name of the submitted output file in output-only problems.
+    ///
+    /// Defaults to the file name of the input with the extension `.out`.
     #[serde(skip_serializing_if = "Option::is_none")]
-    score: Option<NonZeroU32>,
+    output: Option<String>,
+
+    /// Score of the case, only allowed in [`TaskType::Simple`] and
+    /// [`ScorePolicy::Sum`] subtasks.
+    ///
+    /// Cases without a score share the remaining score evenly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) score: Option<NonZeroU32>,
+}
+
+impl Case {
+    /// Create a case with paths relative to the cases directory.
+    pub fn new(input: impl Into<PathBuf>, answer: impl Into<PathBuf>) -> Self {
+        Self {
+            input: input.into(),
+            answer: answer.into(),
+            output: None,
+            score: None,
+        }
+    }
+
+    /// Get the input file, relative to the cases directory.
+    pub fn input(&self) -> &Path {
+        &self.input
+    }
+
+    /// Get the answer file, relative to the cases directory.
+    pub fn answer(&self) -> &Path {
+        &self.answer
+    }
+
+    /// Get the name of the submitted output file in output-only problems.
+    pub fn output_name(&self) -> String {
+        self.output.clone().unwrap_or_else(|| {
+            self.input
+                .with_extension("out")
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        })
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Subtask {
-    cases: Vec<Case>,
+    pub(crate) cases: Vec<Case>,
 
+    /// Score of the subtask.
+    ///
+    /// Subtasks without a score share the remaining score evenly.
     #[serde(skip_serializing_if = "Option::is_none")]
-    score: Option<NonZeroU32>,
+    pub(crate) score: Option<NonZeroU32>,
+
+    #[serde(default)]
+    pub(crate) policy: ScorePolicy,
+
+    /// Subtasks required to get full score before this subtask is judged,
+    /// numbered from 1.
+    ///
+    /// Only earlier subtasks can be depended on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) dependencies: Vec<usize>,
+}
+
+/// How the score of a subtask is computed from its cases.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScorePolicy {
+    /// The subtask score scaled by the lowest case score.
+    ///
+    /// Remaining cases are skipped once a case scores zero.
+    #[default]
+    Min,
+    /// Full score if all cases are accepted, otherwise zero.
+    ///
+    /// Remaining cases are skipped once a case is not accepted.
+    AllOrNothing,
+    /// Sum of the case scores.
+    Sum,
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::num::NonZeroU32;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
+    use rsjudge_traits::language::config::LimitFactors;
+    use rsjudge_traits::resource::ResourceLimit;
     use serde_json::json;
 
-    use super::{Case, CasesConfig, JudgeType, ResourceLimits, TaskType};
+    use super::{
+        Case, CasesConfig, CompareConfig, IoConfig, JudgeMode, JudgeType, ResourceLimits, TaskType,
+    };
+    use crate::comparer::Normalization;
 
     fn non_zero(value: u32) -> NonZeroU32 {
         debug_assert_ne!(value, 0);
@@ -75,21 +540,39 @@ mod tests {
             resource_limits: ResourceLimits {
                 time: 1000,
                 memory: 512,
+                wall_time: None,
+                stack: None,
+                processes: None,
+                open_files: None,
+                output: None,
+                languages: HashMap::new(),
             },
             task: TaskType::Simple {
                 cases: vec![
                     Case {
                         input: "1.in".into(),
                         answer: "1.ans".into(),
+                        output: None,
                         score: None,
                     },
                     Case {
                         input: "2.in".into(),
                         answer: "2.ans".into(),
+                        output: None,
                         score: NonZeroU32::new(60),
                     },
                 ],
             },
+            mode: JudgeMode::Oi,
+            compare: CompareConfig {
+                normalization: Normalization::PORTABLE,
+                ..Default::default()
+            },
+            disable_compile_cache: false,
+            graders: HashMap::new(),
+            io: IoConfig::default(),
+            output_only: false,
+            validator: None,
         })
         .unwrap();
     }
@@ -166,4 +649,243 @@ mod tests {
         }))
         .unwrap();
     }
+
+    #[test]
+    fn deserialize_validator() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            },
+            "validator": {
+                "source": "validator.cpp",
+                "language": "C++"
+            }
+        }))
+        .unwrap();
+
+        let validator = config.validator().unwrap();
+        assert_eq!(validator.source(), PathBuf::from("validator.cpp"));
+        assert_eq!(validator.language(), "C++");
+    }
+
+    #[test]
+    fn deserialize_graders() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            },
+            "graders": {
+                "C++": {
+                    "sources": ["grader.cpp"],
+                    "headers": ["grader.h"]
+                }
+            }
+        }))
+        .unwrap();
+
+        let grader = config.grader("C++").unwrap();
+        assert_eq!(grader.sources, [PathBuf::from("grader.cpp")]);
+        assert_eq!(grader.headers, [PathBuf::from("grader.h")]);
+        assert!(config.grader("C").is_none());
+    }
+
+    #[test]
+    fn deserialize_compare_config() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            },
+            "compare": {
+                "caseSensitive": false,
+                "normalization": {
+                    "crlfToLf": true,
+                    "unicodeCaseFold": true
+                }
+            }
+        }))
+        .unwrap();
+
+        assert!(!config.compare.case_sensitive);
+        assert!(config.compare.ignore_trailing_whitespace);
+        assert_eq!(
+            config.compare.normalization,
+            Normalization {
+                crlf_to_lf: true,
+                unicode_case_fold: true,
+                ..Normalization::NONE
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_compile_cache() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            },
+            "disableCompileCache": true
+        }))
+        .unwrap();
+
+        assert!(!config.compile_cache_enabled());
+    }
+
+    #[test]
+    fn deserialize_io_config() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            },
+            "io": {
+                "inputFile": "input.txt",
+                "outputFile": "output.txt"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.io(),
+            &IoConfig {
+                input_file: Some("input.txt".to_owned()),
+                output_file: Some("output.txt".to_owned()),
+                stdin: false,
+            }
+        );
+    }
+
+    #[test]
+    fn convert_resource_limits() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1500,
+                "memory": 256,
+                "processes": 1,
+                "output": 65536
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            }
+        }))
+        .unwrap();
+
+        let limit = ResourceLimit::from(config.resource_limits());
+        assert_eq!(limit.cpu_time_limit(), Some(Duration::from_millis(1500)));
+        assert_eq!(limit.wall_time_limit(), Some(Duration::from_secs(3)));
+        assert_eq!(limit.memory_limit(), Some(256 << 20));
+        assert_eq!(limit.stack_limit(), Some(256 << 20));
+        assert_eq!(limit.process_limit(), Some(1));
+        assert_eq!(limit.open_files_limit(), None);
+        assert_eq!(limit.output_limit(), Some(65536));
+        assert!(!limit.allow_core_dump());
+    }
+
+    #[test]
+    fn resolve_language_limits() {
+        let config = serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256,
+                "languages": {
+                    "Java": {
+                        "timeFactor": 1.5
+                    }
+                }
+            },
+            "task": {
+                "taskType": "simple",
+                "cases": []
+            }
+        }))
+        .unwrap();
+
+        let factors = LimitFactors {
+            time_factor: Some(2.0),
+            time_bonus: Some(100),
+            memory_factor: None,
+            memory_bonus: Some(64),
+        };
+
+        let cpp = config
+            .resource_limits()
+            .resolve("C++", &LimitFactors::default());
+        assert_eq!(cpp.raw, cpp.effective);
+
+        let java = config.resource_limits().resolve("Java", &factors);
+        assert_eq!(java.raw.cpu_time_limit(), Some(Duration::from_secs(1)));
+        assert_eq!(
+            java.effective.cpu_time_limit(),
+            Some(Duration::from_millis(1600))
+        );
+        assert_eq!(
+            java.effective.wall_time_limit(),
+            Some(Duration::from_millis(3100))
+        );
+        assert_eq!(java.raw.memory_limit(), Some(256 << 20));
+        assert_eq!(java.effective.memory_limit(), Some(320 << 20));
+        assert_eq!(java.effective.stack_limit(), Some(320 << 20));
+        let invalid = serde_json::from_value::<ResourceLimits>(json!({
+            "time": 1000,
+            "memory": 256,
+            "languages": {
+                "Java": {
+                    "memoryFactor": -1.0
+                }
+            }
+        }));
+        assert!(invalid.is_err());
+    }
 }
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use bytes::Bytes;
+
+use crate::judger::request::cases::CasesConfig;
+use crate::judger::request::source::Source;
+
+/// A hack against a submission, as in Codeforces.
+///
+/// The target submission is the source of the [`JudgeRequest`].
+///
+/// [`JudgeRequest`]: crate::judger::request::JudgeRequest
+pub struct HackRequest {
+    pub(crate) cases: Box<CasesConfig>,
+    pub(crate) input: HackInput,
+    pub(crate) validator: Source,
+    pub(crate) reference: Source,
+}
+
+impl HackRequest {
+    pub fn new(cases: CasesConfig, input: HackInput, validator: Source, reference: Source) -> Self {
+        Self {
+            cases: Box::new(cases),
+            input,
+            validator,
+            reference,
+        }
+    }
+
+    pub fn cases(&self) -> &CasesConfig {
+        &self.cases
+    }
+
+    pub fn input(&self) -> &HackInput {
+        &self.input
+    }
+
+    /// Get the input validator, which exits successfully for valid input.
+    pub fn validator(&self) -> &Source {
+        &self.validator
+    }
+
+    /// Get the reference solution, whose output is taken as the answer.
+    pub fn reference(&self) -> &Source {
+        &self.reference
+    }
+}
+
+/// Input of a hack.
+pub enum HackInput {
+    /// The input itself.
+    Input(Bytes),
+    /// A generator printing the input to stdout, and its arguments.
+    Generator {
+        generator: Source,
+        args: Vec<String>,
+    },
+}
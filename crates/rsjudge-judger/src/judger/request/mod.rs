@@ -3,11 +3,13 @@
 use bytes::Bytes;
 
 use crate::judger::request::cases::CasesConfig;
+use crate::judger::request::hack::HackRequest;
 use crate::judger::request::source::Source;
 
-mod source;
+pub mod source;
 
-mod cases;
+pub mod cases;
+pub mod hack;
 
 pub struct JudgeRequest {
     source: Source,
@@ -29,6 +31,18 @@ impl JudgeRequest {
 }
 
 pub enum JudgeType {
-    SelfTest { input: Bytes },
-    Submit { cases: CasesConfig },
+    SelfTest {
+        input: Bytes,
+    },
+    /// Judge against the cases of a problem.
+    ///
+    /// In [output-only] problems, the source files are the outputs of the
+    /// cases, and the language is ignored.
+    ///
+    /// [output-only]: crate::judger::output_only
+    Submit {
+        cases: Box<CasesConfig>,
+    },
+    /// Hack the submission with a custom input, see [`HackRequest`].
+    Hack(Box<HackRequest>),
 }
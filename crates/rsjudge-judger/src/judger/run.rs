@@ -0,0 +1,432 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Run phase of judging.
+//!
+//! A [`Program`] is a compiled submission, or a problem-provided tool like a
+//! generator or a validator, ready to run with its resource limit. It runs as
+//! the runner user without network access, with stdout redirected to a file,
+//! so that the output limit is enforced as a file size limit.
+
+use std::io;
+use std::os::unix::process::ExitStatusExt as _;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+use rsjudge_runner::user::runner;
+use rsjudge_runner::utils::network::DenyNetwork as _;
+use rsjudge_runner::utils::resources::WithResourceLimit as _;
+use rsjudge_runner::utils::resources::rusage::{ResourceUsage, WaitForResourceUsage as _};
+use rsjudge_runner::{Error, RunAs as _};
+use rsjudge_traits::judger::JudgeResult;
+use rsjudge_traits::language::config::{Environment, LanguageDef, Phase};
+use rsjudge_traits::language::template::{TemplateError, TemplateVars};
+use rsjudge_traits::resource::ResourceLimit;
+use tokio::fs::File;
+use tokio::process::Command;
+use tokio::time::Instant;
+
+use crate::judger::compile::read_truncated;
+
+/// Max size of the stderr kept from a run **in bytes**.
+const STDERR_BUDGET: u64 = 64 << 10;
+
+/// Exit codes of a testlib checker.
+const CHECKER_OK: i32 = 0;
+const CHECKER_WRONG_ANSWER: i32 = 1;
+const CHECKER_PRESENTATION_ERROR: i32 = 2;
+
+/// A program ready to run.
+#[derive(Debug, Clone)]
+pub struct Program {
+    command: String,
+    /// Environment of the program, or `None` to inherit that of the judger.
+    environment: Option<Environment>,
+    work_dir: PathBuf,
+    limit: ResourceLimit,
+    sandbox: bool,
+}
+
+impl Program {
+    /// Create a program running the shell `command` in `work_dir`.
+    ///
+    /// Nothing is inherited from the environment of the judger, only `PATH`
+    /// is set to [`DEFAULT_PATH`]. Use [`Program::inherit_environment`] to
+    /// opt in to inheriting it.
+    ///
+    /// [`DEFAULT_PATH`]: rsjudge_traits::language::config::DEFAULT_PATH
+    pub fn new(
+        command: impl Into<String>,
+        work_dir: impl Into<PathBuf>,
+        limit: ResourceLimit,
+    ) -> Self {
+        Self {
+            command: command.into(),
+            environment: Some(Environment::default()),
+            work_dir: work_dir.into(),
+            limit,
+            sandbox: true,
+        }
+    }
+
+    /// Create a program of `language` compiled in `work_dir`, run with the
+    /// environment of its run phase.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the execution command cannot be rendered with
+    /// `vars`.
+    pub fn from_language(
+        language: &LanguageDef,
+        vars: &TemplateVars,
+        work_dir: impl Into<PathBuf>,
+        limit: ResourceLimit,
+    ) -> Result<Self, TemplateError> {
+        Ok(Self {
+            environment: Some(language.environment(Phase::Run)),
+            ..Self::new(
+                vars.render(language.exec_type().execute_command())?,
+                work_dir,
+                limit,
+            )
+        })
+    }
+
+    /// Inherit the environment of the judger, which may hold secrets, instead
+    /// of the cleared one.
+    #[must_use]
+    pub fn inherit_environment(mut self) -> Self {
+        self.environment = None;
+        self
+    }
+
+    /// Run the program as the current user, with network access.
+    #[cfg(test)]
+    pub(crate) fn without_sandbox(mut self) -> Self {
+        self.sandbox = false;
+        self
+    }
+
+    /// Get the working directory of the program.
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+
+    /// Get the resource limit of the program.
+    pub fn limit(&self) -> &ResourceLimit {
+        &self.limit
+    }
+
+    /// Run the program with `args` appended to its command, writing its
+    /// stdout to `stdout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the program cannot be spawned or waited for.
+    pub async fn run(
+        &self,
+        args: &[String],
+        stdin: Stdio,
+        stdout: &Path,
+    ) -> rsjudge_runner::Result<RunInfo> {
+        let script = if args.is_empty() {
+            self.command.clone()
+        } else {
+            format!("{} \"$@\"", self.command)
+        };
+
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .arg("sh")
+            .args(args)
+            .current_dir(&self.work_dir)
+            .stdin(stdin)
+            .stdout(File::create(stdout).await?.into_std().await)
+            .stderr(Stdio::piped())
+            // Allow killing all processes spawned by the program.
+            .process_group(0)
+            .kill_on_drop(true);
+        if let Some(environment) = &self.environment {
+            environment.apply(&mut command);
+        }
+        if self.sandbox {
+            command.run_as(runner().map_err(Error::from)?)?;
+            command.deny_network();
+        }
+
+        let start = Instant::now();
+        // Descendants leaving the process group may hold stderr open, so read
+        // it until the wall time limit at most.
+        let deadline = self
+            .limit
+            .wall_time_limit()
+            .map(|limit| (start + limit).into_std());
+        let mut child = command.with_resource_limit(self.limit).spawn()?;
+        let pid = child.child().id();
+        let stderr = child.child_mut().stderr.take();
+
+        let ((status, wall_time), stderr) = tokio::join!(
+            async {
+                let status = child.wait_for_resource_usage().await;
+                let wall_time = start.elapsed();
+                if let Some(pid) = pid {
+                    // Descendants may still hold stderr open.
+                    let _ = killpg(Pid::from_raw(pid as _), Signal::SIGKILL);
+                }
+                (status, wall_time)
+            },
+            read_truncated(stderr, STDERR_BUDGET, deadline),
+        );
+
+        let (result, status, usage) = match status {
+            Err(Error::TimeLimitExceeded(..)) => (Some(JudgeResult::TimeLimitExceeded), None, None),
+            Err(error) => return Err(error),
+            Ok((status, usage)) => (self.result(status, usage), Some(status), Some(usage)),
+        };
+
+        Ok(RunInfo {
+            result,
+            status,
+            usage,
+            wall_time,
+            stderr: stderr?,
+        })
+    }
+
+    /// Run the program as a testlib checker, i.e. as
+    /// `checker <input> <output> <answer>`, with its stdout written into
+    /// `dir`.
+    ///
+    /// Get the verdict with [`RunInfo::verdict`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checker cannot be spawned or waited for.
+    pub async fn check(
+        &self,
+        input: &Path,
+        output: &Path,
+        answer: &Path,
+        dir: &Path,
+    ) -> rsjudge_runner::Result<RunInfo> {
+        let args = [input, output, answer].map(|path| path.to_string_lossy().into_owned());
+        self.run(&args, Stdio::null(), &dir.join("checker.out"))
+            .await
+    }
+
+    /// Get the judge result of a run exiting with `status`, or `None` if it
+    /// exited successfully within the limits.
+    fn result(&self, status: ExitStatus, usage: ResourceUsage) -> Option<JudgeResult> {
+        let over_time = self
+            .limit
+            .cpu_time_limit()
+            .is_some_and(|limit| usage.cpu_time() >= limit);
+        // The address space limit fails allocations before the resident memory
+        // reaches it, so running out of memory often ends with a crash.
+        let over_memory = self
+            .limit
+            .memory_limit()
+            .is_some_and(|limit| usage.ram_usage() << 10 >= limit);
+
+        // The shell running the command reports a signal killing its child as
+        // exit code `128 + signal`.
+        let signal = status.signal().or_else(|| {
+            status
+                .code()
+                .filter(|&code| code > 128)
+                .map(|code| code - 128)
+        });
+
+        match signal.and_then(|s| Signal::try_from(s).ok()) {
+            Some(Signal::SIGXCPU) => Some(JudgeResult::TimeLimitExceeded),
+            Some(Signal::SIGKILL) if over_time => Some(JudgeResult::TimeLimitExceeded),
+            Some(Signal::SIGXFSZ) => Some(JudgeResult::OutputLimitExceeded),
+            _ if over_memory => Some(JudgeResult::MemoryLimitExceeded),
+            _ if status.success() => None,
+            _ => Some(JudgeResult::RuntimeError),
+        }
+    }
+}
+
+/// Result of running a [`Program`].
+#[derive(Debug)]
+pub struct RunInfo {
+    result: Option<JudgeResult>,
+    status: Option<ExitStatus>,
+    usage: Option<ResourceUsage>,
+    wall_time: Duration,
+    stderr: Vec<u8>,
+}
+
+impl RunInfo {
+    /// Get the judge result if the program failed or exceeded a limit, or
+    /// `None` if the output is to be checked.
+    pub fn result(&self) -> Option<JudgeResult> {
+        self.result
+    }
+
+    /// Whether the program exited successfully within the limits.
+    pub fn is_success(&self) -> bool {
+        self.result.is_none()
+    }
+
+    /// Get the exit status, or `None` if the program was killed for exceeding
+    /// the wall time limit.
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status
+    }
+
+    /// Get the CPU time used, or `None` if the program was killed for
+    /// exceeding the wall time limit.
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.usage.map(|usage| usage.cpu_time())
+    }
+
+    /// Get the wall time elapsed.
+    pub fn wall_time(&self) -> Duration {
+        self.wall_time
+    }
+
+    /// Get the peak memory usage **in bytes**, or `None` if the program was
+    /// killed for exceeding the wall time limit.
+    pub fn memory(&self) -> Option<u64> {
+        self.usage.map(|usage| usage.ram_usage() << 10)
+    }
+
+    /// Get the stderr, truncated if too long.
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Get the stderr as text, e.g. the message of a rejecting validator.
+    pub fn message(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+
+    /// Get the verdict of a run of a testlib checker from its exit code, or
+    /// `None` if the checker itself failed.
+    pub fn verdict(&self) -> Option<JudgeResult> {
+        match self.status?.code()? {
+            CHECKER_OK => Some(JudgeResult::Accepted),
+            CHECKER_WRONG_ANSWER => Some(JudgeResult::WrongAnswer),
+            CHECKER_PRESENTATION_ERROR => Some(JudgeResult::PresentationError),
+            _ => None,
+        }
+    }
+}
+
+/// Get the resource limit of programs in tests.
+#[cfg(test)]
+pub(crate) fn test_limit() -> ResourceLimit {
+    ResourceLimit::new(
+        Some(Duration::from_secs(1)),
+        Some(Duration::from_secs(1)),
+        None,
+        None,
+    )
+}
+
+/// Create a program running `command` in `work_dir` with [`test_limit`],
+/// without the sandbox, for tests.
+#[cfg(test)]
+pub(crate) fn test_program(command: &str, work_dir: &Path) -> Program {
+    Program::new(command, work_dir, test_limit()).without_sandbox()
+}
+
+/// Open `path` as the stdin of a run.
+pub(crate) async fn stdin(path: &Path) -> io::Result<Stdio> {
+    Ok(File::open(path).await?.into_std().await.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    use rsjudge_traits::judger::JudgeResult;
+    use rsjudge_traits::resource::ResourceLimit;
+    use tokio::fs;
+
+    use super::{Program, test_program};
+
+    #[tokio::test]
+    async fn run_program() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let stdout = dir.path().join("stdout");
+        let limit = ResourceLimit::new(
+            Some(Duration::from_secs(1)),
+            Some(Duration::from_millis(500)),
+            None,
+            None,
+        );
+
+        let echo = Program::new("echo", dir.path(), limit).without_sandbox();
+        let info = echo
+            .run(&["1".to_owned(), "2".to_owned()], Stdio::null(), &stdout)
+            .await?;
+        assert!(info.is_success());
+        assert!(info.cpu_time().is_some());
+        assert_eq!(fs::read_to_string(&stdout).await?, "1 2\n");
+
+        let fail = Program::new("echo oops >&2; exit 1", dir.path(), limit).without_sandbox();
+        let info = fail.run(&[], Stdio::null(), &stdout).await?;
+        assert_eq!(info.result(), Some(JudgeResult::RuntimeError));
+        assert_eq!(info.stderr(), b"oops\n");
+
+        let sleep = Program::new("sleep 10", dir.path(), limit).without_sandbox();
+        let info = sleep.run(&[], Stdio::null(), &stdout).await?;
+        assert_eq!(info.result(), Some(JudgeResult::TimeLimitExceeded));
+
+        let mut limit = limit;
+        limit.set_output_limit(std::num::NonZeroU64::new(4));
+        let flood = Program::new("yes", dir.path(), limit).without_sandbox();
+        let info = flood.run(&[], Stdio::null(), &stdout).await?;
+        assert_eq!(info.result(), Some(JudgeResult::OutputLimitExceeded));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_detached_descendant() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let stdout = dir.path().join("stdout");
+
+        let start = std::time::Instant::now();
+        let detach = test_program("setsid sleep 10 & echo done", dir.path());
+        let info = detach.run(&[], Stdio::null(), &stdout).await?;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(info.is_success());
+        assert_eq!(fs::read_to_string(&stdout).await?, "done\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clear_environment() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let stdout = dir.path().join("stdout");
+        let limit = ResourceLimit::new(Some(Duration::from_secs(1)), None, None, None);
+        // `sh` itself may set `PWD` and the like, so check a variable of the
+        // judger only.
+        let print = "printf '%s' \"${HOME-unset}\"";
+
+        let cleared = Program::new(print, dir.path(), limit).without_sandbox();
+        assert!(cleared.run(&[], Stdio::null(), &stdout).await?.is_success());
+        assert_eq!(fs::read_to_string(&stdout).await?, "unset");
+
+        let inherited = cleared.inherit_environment();
+        assert!(
+            inherited
+                .run(&[], Stdio::null(), &stdout)
+                .await?
+                .is_success()
+        );
+        assert_eq!(
+            fs::read_to_string(&stdout).await?,
+            std::env::var("HOME").unwrap_or_else(|_| "unset".to_owned())
+        );
+        Ok(())
+    }
+}
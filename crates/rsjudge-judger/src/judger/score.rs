@@ -0,0 +1,736 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Scoring of test cases, with support for OI-style subtasks.
+//!
+//! A [`ScorePlan`] is resolved from a [`CasesConfig`] once, with all scores
+//! validated. Each submission is then judged with a [`Scheduler`], which
+//! yields the cases to run, skips those whose result is already decided, and
+//! finally sums up the scores.
+//!
+//! In [`JudgeMode::Icpc`], judging stops at the first case not accepted.
+
+use std::num::NonZeroU32;
+
+use rsjudge_traits::judger::JudgeResult;
+use thiserror::Error;
+
+use crate::judger::request::cases::{CasesConfig, JudgeMode, ScorePolicy, TaskType};
+
+/// Error type for inconsistent scores in [`CasesConfig`].
+///
+/// Subtasks are numbered from 1.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ScoreError {
+    /// Explicit scores do not add up to the expected total.
+    #[error("Scores add up to {actual}, but {expected} is expected")]
+    Mismatch {
+        /// The expected total score.
+        expected: u32,
+        /// The sum of explicit scores.
+        actual: u32,
+    },
+
+    /// Remaining score is not enough for every item without explicit score to
+    /// get at least 1.
+    #[error("Remaining score {remaining} cannot be shared by {count} items")]
+    NotEnough {
+        /// The remaining score.
+        remaining: u32,
+        /// Number of items without explicit score.
+        count: usize,
+    },
+
+    /// A subtask has no cases.
+    #[error("Subtask {subtask} has no cases")]
+    EmptySubtask {
+        /// The subtask number.
+        subtask: usize,
+    },
+
+    /// Case scores are set in a subtask not using [`ScorePolicy::Sum`].
+    #[error("Case scores in subtask {subtask} are ignored by its score policy")]
+    CaseScoreIgnored {
+        /// The subtask number.
+        subtask: usize,
+    },
+
+    /// A subtask depends on itself, a later subtask or a missing one.
+    #[error("Subtask {subtask} cannot depend on subtask {dependency}")]
+    InvalidDependency {
+        /// The subtask number.
+        subtask: usize,
+        /// The number of the subtask depended on.
+        dependency: usize,
+    },
+
+    /// Scores or case IDs do not fit in `u32`.
+    #[error("Scores or case IDs overflow")]
+    Overflow,
+}
+
+/// Validated scores of all cases and subtasks.
+#[derive(Debug)]
+pub struct ScorePlan {
+    total: u32,
+    mode: JudgeMode,
+    subtasks: Vec<SubtaskPlan>,
+}
+
+#[derive(Debug)]
+struct SubtaskPlan {
+    score: u32,
+    policy: ScorePolicy,
+    /// Indices of subtasks depended on, starting from 0.
+    dependencies: Vec<usize>,
+    /// Full score of each case, all zeros unless [`ScorePolicy::Sum`] is used.
+    cases: Vec<u32>,
+    /// ID of the first case, starting from 1.
+    first_case_id: u32,
+}
+
+impl ScorePlan {
+    pub(crate) fn new(config: &CasesConfig) -> Result<Self, ScoreError> {
+        let total = config.score.get();
+
+        let subtasks = match &config.task {
+            // A simple task is judged as a single subtask summing up all case
+            // scores.
+            TaskType::Simple { cases } => vec![SubtaskPlan {
+                score: total,
+                policy: ScorePolicy::Sum,
+                dependencies: Vec::new(),
+                cases: distribute(total, cases.iter().map(|case| case.score))?,
+                first_case_id: 1,
+            }],
+            TaskType::Subtask { subtasks } => {
+                let scores = distribute(total, subtasks.iter().map(|subtask| subtask.score))?;
+                let mut first_case_id = 1;
+
+                subtasks
+                    .iter()
+                    .zip(scores)
+                    .enumerate()
+                    .map(|(index, (subtask, score))| {
+                        let number = index + 1;
+
+                        if subtask.cases.is_empty() {
+                            return Err(ScoreError::EmptySubtask { subtask: number });
+                        }
+
+                        let cases = if subtask.policy == ScorePolicy::Sum {
+                            distribute(score, subtask.cases.iter().map(|case| case.score))?
+                        } else if subtask.cases.iter().any(|case| case.score.is_some()) {
+                            return Err(ScoreError::CaseScoreIgnored { subtask: number });
+                        } else {
+                            vec![0; subtask.cases.len()]
+                        };
+
+                        let dependencies = subtask
+                            .dependencies
+                            .iter()
+                            .map(|&dependency| {
+                                if (1..number).contains(&dependency) {
+                                    Ok(dependency - 1)
+                                } else {
+                                    Err(ScoreError::InvalidDependency {
+                                        subtask: number,
+                                        dependency,
+                                    })
+                                }
+                            })
+                            .collect::<Result<_, _>>()?;
+
+                        let plan = SubtaskPlan {
+                            score,
+                            policy: subtask.policy,
+                            dependencies,
+                            cases,
+                            first_case_id,
+                        };
+                        first_case_id = u32::try_from(plan.cases.len())
+                            .ok()
+                            .and_then(|count| first_case_id.checked_add(count))
+                            .ok_or(ScoreError::Overflow)?;
+                        Ok(plan)
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        Ok(Self {
+            total,
+            mode: config.mode,
+            subtasks,
+        })
+    }
+
+    /// Get the total score.
+    #[must_use]
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Get the judge mode.
+    #[must_use]
+    pub fn mode(&self) -> JudgeMode {
+        self.mode
+    }
+
+    /// Get the number of cases.
+    #[must_use]
+    pub fn case_count(&self) -> usize {
+        self.subtasks
+            .iter()
+            .map(|subtask| subtask.cases.len())
+            .sum()
+    }
+
+    /// Get the full score of each subtask, with the full score of each of its
+    /// cases.
+    ///
+    /// Case scores are all zeros unless [`ScorePolicy::Sum`] is used, and a
+    /// simple task is a single subtask.
+    pub fn scores(&self) -> impl Iterator<Item = (u32, &[u32])> {
+        self.subtasks
+            .iter()
+            .map(|subtask| (subtask.score, subtask.cases.as_slice()))
+    }
+
+    /// Start scheduling the cases of a submission.
+    pub fn scheduler(&self) -> Scheduler<'_> {
+        Scheduler {
+            plan: self,
+            outcomes: self
+                .subtasks
+                .iter()
+                .map(|subtask| vec![None; subtask.cases.len()])
+                .collect(),
+            subtask: 0,
+            case: 0,
+            stopped: false,
+        }
+    }
+}
+
+/// Share `total` among items, where items without explicit score get an even
+/// share of the remaining score.
+fn distribute(
+    total: u32,
+    scores: impl Iterator<Item = Option<NonZeroU32>>,
+) -> Result<Vec<u32>, ScoreError> {
+    let scores: Vec<_> = scores.collect();
+    let explicit = scores
+        .iter()
+        .flatten()
+        .try_fold(0u32, |sum, score| sum.checked_add(score.get()))
+        .ok_or(ScoreError::Overflow)?;
+    let count = scores.iter().filter(|score| score.is_none()).count();
+
+    let remaining = total
+        .checked_sub(explicit)
+        .filter(|&remaining| count != 0 || remaining == 0)
+        .ok_or(ScoreError::Mismatch {
+            expected: total,
+            actual: explicit,
+        })?;
+
+    if (remaining as usize) < count {
+        return Err(ScoreError::NotEnough { remaining, count });
+    }
+
+    // `count` is no more than `remaining` here, so it fits in `u32`.
+    let share = remaining.checked_div(count as u32).unwrap_or(0);
+    let mut extra = remaining.checked_rem(count as u32).unwrap_or(0);
+
+    Ok(scores
+        .into_iter()
+        .map(|score| match score {
+            Some(score) => score.get(),
+            None if extra > 0 => {
+                extra -= 1;
+                share + 1
+            }
+            None => share,
+        })
+        .collect())
+}
+
+/// Reference to a case in a [`ScorePlan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaseRef {
+    /// Index of the subtask, starting from 0.
+    pub subtask: usize,
+    /// Index of the case in the subtask, starting from 0.
+    pub case: usize,
+    /// ID of the case among all cases, starting from 1.
+    pub id: u32,
+}
+
+/// The outcome of judging a single case.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaseOutcome {
+    result: JudgeResult,
+    /// Ratio of the case score earned, in `[0, 1]`.
+    ratio: f64,
+}
+
+impl CaseOutcome {
+    /// Create an outcome with full score if accepted, or zero otherwise.
+    #[must_use]
+    pub fn new(result: JudgeResult) -> Self {
+        let ratio = if result == JudgeResult::Accepted {
+            1.0
+        } else {
+            0.0
+        };
+        Self { result, ratio }
+    }
+
+    /// Create an outcome with partial score, e.g. given by a checker.
+    ///
+    /// `ratio` is clamped into `[0, 1]`.
+    #[must_use]
+    pub fn partial(result: JudgeResult, ratio: f64) -> Self {
+        Self {
+            result,
+            ratio: ratio.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Get the judge result.
+    #[must_use]
+    pub fn result(&self) -> JudgeResult {
+        self.result
+    }
+
+    /// Get the ratio of the case score earned.
+    #[must_use]
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+}
+
+/// Schedules the cases of a single submission in order, and collects their
+/// outcomes.
+///
+/// Cases are yielded one at a time by [`next_case`]. The outcome of each case
+/// should be [recorded][record] before asking for the next one, so that
+/// remaining cases of a failed subtask, and subtasks with unmet dependencies,
+/// are skipped. In [`JudgeMode::Icpc`], all cases after the first one not
+/// accepted are skipped.
+///
+/// [`next_case`]: Scheduler::next_case
+/// [record]: Scheduler::record
+#[derive(Debug)]
+pub struct Scheduler<'a> {
+    plan: &'a ScorePlan,
+    outcomes: Vec<Vec<Option<CaseOutcome>>>,
+    /// Index of the current subtask.
+    subtask: usize,
+    /// Index of the next case in the current subtask.
+    case: usize,
+    /// Whether all remaining cases are skipped.
+    stopped: bool,
+}
+
+impl Scheduler<'_> {
+    /// Get the next case to judge, or `None` if all remaining cases are
+    /// skipped.
+    pub fn next_case(&mut self) -> Option<CaseRef> {
+        if self.stopped {
+            return None;
+        }
+
+        loop {
+            let subtask = self.plan.subtasks.get(self.subtask)?;
+
+            let skipped = if self.case == 0 {
+                !subtask.dependencies.iter().all(|&dependency| {
+                    self.subtask_score(dependency) == self.plan.subtasks[dependency].score
+                })
+            } else {
+                self.case == subtask.cases.len() || self.is_failed(self.subtask)
+            };
+
+            if skipped {
+                self.subtask += 1;
+                self.case = 0;
+                continue;
+            }
+
+            let case = CaseRef {
+                subtask: self.subtask,
+                case: self.case,
+                id: subtask.first_case_id + self.case as u32,
+            };
+            self.case += 1;
+            return Some(case);
+        }
+    }
+
+    /// Record the outcome of a case.
+    pub fn record(&mut self, case: CaseRef, outcome: CaseOutcome) {
+        if self.plan.mode == JudgeMode::Icpc && outcome.result != JudgeResult::Accepted {
+            self.stopped = true;
+        }
+        self.outcomes[case.subtask][case.case] = Some(outcome);
+    }
+
+    /// Whether the subtask has already scored zero.
+    fn is_failed(&self, subtask: usize) -> bool {
+        let mut outcomes = self.outcomes[subtask].iter().flatten();
+        match self.plan.subtasks[subtask].policy {
+            ScorePolicy::Min => outcomes.any(|outcome| outcome.ratio == 0.0),
+            ScorePolicy::AllOrNothing => {
+                outcomes.any(|outcome| outcome.result != JudgeResult::Accepted)
+            }
+            ScorePolicy::Sum => false,
+        }
+    }
+
+    /// Score of the subtask, with cases not judged scoring zero.
+    fn subtask_score(&self, subtask: usize) -> u32 {
+        let plan = &self.plan.subtasks[subtask];
+        let outcomes = &self.outcomes[subtask];
+        let ratio = |outcome: &Option<CaseOutcome>| outcome.map_or(0.0, |outcome| outcome.ratio);
+
+        match plan.policy {
+            ScorePolicy::Min => {
+                let min = outcomes.iter().map(ratio).fold(1.0, f64::min);
+                scale(plan.score, min)
+            }
+            ScorePolicy::AllOrNothing => {
+                if outcomes.iter().all(|outcome| {
+                    outcome.is_some_and(|outcome| outcome.result == JudgeResult::Accepted)
+                }) {
+                    plan.score
+                } else {
+                    0
+                }
+            }
+            ScorePolicy::Sum => plan
+                .cases
+                .iter()
+                .zip(outcomes)
+                .map(|(&score, outcome)| scale(score, ratio(outcome)))
+                // Bounded by `plan.score`, as validated by `distribute`.
+                .fold(0, u32::saturating_add),
+        }
+    }
+
+    /// Finish judging, marking all cases not judged as skipped.
+    #[must_use]
+    pub fn finish(self) -> ScoreSummary {
+        let subtasks: Vec<_> = self
+            .plan
+            .subtasks
+            .iter()
+            .enumerate()
+            .map(|(index, plan)| SubtaskReport {
+                score: self.subtask_score(index),
+                cases: self.outcomes[index]
+                    .iter()
+                    .zip(&plan.cases)
+                    .enumerate()
+                    .map(|(case, (outcome, &score))| CaseReport {
+                        id: plan.first_case_id + case as u32,
+                        result: outcome.map_or(JudgeResult::Skipped, |outcome| outcome.result),
+                        score: outcome.map_or(0, |outcome| scale(score, outcome.ratio)),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let failed_case = subtasks
+            .iter()
+            .flat_map(|subtask| &subtask.cases)
+            .find(|case| !matches!(case.result, JudgeResult::Accepted | JudgeResult::Skipped));
+
+        ScoreSummary {
+            result: failed_case.map_or(JudgeResult::Accepted, |case| case.result),
+            failed_case: failed_case.map(|case| case.id),
+            // Bounded by the total score, as validated by `distribute`.
+            score: subtasks
+                .iter()
+                .map(|subtask| subtask.score)
+                .fold(0, u32::saturating_add),
+            subtasks,
+        }
+    }
+}
+
+/// Scale `score` by `ratio`, rounding down.
+fn scale(score: u32, ratio: f64) -> u32 {
+    (f64::from(score) * ratio).floor() as u32
+}
+
+/// Report of a single case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseReport {
+    /// ID of the case, starting from 1.
+    pub id: u32,
+    /// The judge result, [`JudgeResult::Skipped`] if not judged.
+    pub result: JudgeResult,
+    /// Score earned by the case.
+    ///
+    /// Always zero unless the subtask uses [`ScorePolicy::Sum`].
+    pub score: u32,
+}
+
+/// Report of a single subtask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtaskReport {
+    /// Score earned by the subtask.
+    pub score: u32,
+    /// Reports of the cases in the subtask.
+    pub cases: Vec<CaseReport>,
+}
+
+/// Summary of all cases of a submission.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreSummary {
+    /// Result of the first case not accepted, or [`JudgeResult::Accepted`].
+    pub result: JudgeResult,
+    /// ID of the first case not accepted, if any.
+    ///
+    /// In [`JudgeMode::Icpc`], this is the case judging stopped at.
+    pub failed_case: Option<u32>,
+    /// Total score earned.
+    pub score: u32,
+    /// Reports of all subtasks.
+    ///
+    /// A task without subtasks is reported as a single subtask.
+    pub subtasks: Vec<SubtaskReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::judger::JudgeResult;
+    use serde_json::{Value, json};
+
+    use super::{CaseOutcome, ScoreError, ScorePlan, ScoreSummary};
+    use crate::judger::request::cases::CasesConfig;
+
+    fn plan(task: Value) -> Result<ScorePlan, ScoreError> {
+        plan_with_mode(task, "oi")
+    }
+
+    fn plan_with_mode(task: Value, mode: &str) -> Result<ScorePlan, ScoreError> {
+        serde_json::from_value::<CasesConfig>(json!({
+            "score": 100,
+            "judge": {
+                "judgeType": "classic"
+            },
+            "resourceLimits": {
+                "time": 1000,
+                "memory": 256
+            },
+            "task": task,
+            "mode": mode
+        }))
+        .unwrap()
+        .score_plan()
+    }
+
+    fn cases(count: usize) -> Value {
+        (0..count)
+            .map(|i| json!({ "input": format!("{i}.in"), "answer": format!("{i}.ans") }))
+            .collect()
+    }
+
+    /// Judge all cases with the given results, returning the judged case IDs.
+    fn judge(plan: &ScorePlan, results: &[JudgeResult]) -> (Vec<u32>, ScoreSummary) {
+        let mut scheduler = plan.scheduler();
+        let mut judged = Vec::new();
+        while let Some(case) = scheduler.next_case() {
+            judged.push(case.id);
+            scheduler.record(case, CaseOutcome::new(results[case.id as usize - 1]));
+        }
+        (judged, scheduler.finish())
+    }
+
+    #[test]
+    fn validate_scores() {
+        assert_eq!(
+            plan(json!({
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans", "score": 60 },
+                    { "input": "2.in", "answer": "2.ans", "score": 30 }
+                ]
+            }))
+            .unwrap_err(),
+            ScoreError::Mismatch {
+                expected: 100,
+                actual: 90
+            }
+        );
+
+        assert_eq!(
+            plan(json!({
+                "taskType": "subtask",
+                "subtasks": [
+                    { "cases": cases(1), "score": 40 },
+                    { "cases": cases(1), "dependencies": [2] }
+                ]
+            }))
+            .unwrap_err(),
+            ScoreError::InvalidDependency {
+                subtask: 2,
+                dependency: 2
+            }
+        );
+
+        assert_eq!(
+            plan(json!({
+                "taskType": "subtask",
+                "subtasks": [
+                    {
+                        "cases": [{ "input": "1.in", "answer": "1.ans", "score": 10 }],
+                        "policy": "min"
+                    }
+                ]
+            }))
+            .unwrap_err(),
+            ScoreError::CaseScoreIgnored { subtask: 1 }
+        );
+
+        assert_eq!(
+            plan(json!({
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans", "score": u32::MAX },
+                    { "input": "2.in", "answer": "2.ans", "score": 1 }
+                ]
+            }))
+            .unwrap_err(),
+            ScoreError::Overflow
+        );
+    }
+
+    #[test]
+    fn distribute_remaining_score() {
+        let plan = plan(json!({
+            "taskType": "simple",
+            "cases": cases(3)
+        }))
+        .unwrap();
+
+        let (judged, summary) = judge(
+            &plan,
+            &[
+                JudgeResult::Accepted,
+                JudgeResult::WrongAnswer,
+                JudgeResult::Accepted,
+            ],
+        );
+
+        // 100 = 34 + 33 + 33
+        assert_eq!(judged, [1, 2, 3]);
+        assert_eq!(summary.score, 67);
+        assert_eq!(summary.result, JudgeResult::WrongAnswer);
+    }
+
+    #[test]
+    fn skip_failed_subtask() {
+        let plan = plan(json!({
+            "taskType": "subtask",
+            "subtasks": [
+                { "cases": cases(2), "score": 20 },
+                { "cases": cases(3), "score": 30, "policy": "all-or-nothing" },
+                { "cases": cases(2), "score": 50, "policy": "sum" }
+            ]
+        }))
+        .unwrap();
+
+        let (judged, summary) = judge(
+            &plan,
+            &[
+                JudgeResult::Accepted,
+                JudgeResult::Accepted,
+                JudgeResult::TimeLimitExceeded,
+                JudgeResult::Accepted,
+                JudgeResult::Accepted,
+                JudgeResult::WrongAnswer,
+                JudgeResult::Accepted,
+            ],
+        );
+
+        assert_eq!(judged, [1, 2, 3, 6, 7]);
+        assert_eq!(summary.score, 45);
+        assert_eq!(summary.result, JudgeResult::TimeLimitExceeded);
+        assert_eq!(summary.subtasks[1].cases[1].result, JudgeResult::Skipped);
+    }
+
+    #[test]
+    fn skip_unmet_dependencies() {
+        let plan = plan(json!({
+            "taskType": "subtask",
+            "subtasks": [
+                { "cases": cases(1), "score": 20 },
+                { "cases": cases(1), "score": 30 },
+                { "cases": cases(2), "score": 50, "dependencies": [1, 2] }
+            ]
+        }))
+        .unwrap();
+
+        let (judged, summary) = judge(
+            &plan,
+            &[
+                JudgeResult::Accepted,
+                JudgeResult::WrongAnswer,
+                JudgeResult::Accepted,
+                JudgeResult::Accepted,
+            ],
+        );
+
+        assert_eq!(judged, [1, 2]);
+        assert_eq!(summary.score, 20);
+        assert!(
+            summary.subtasks[2]
+                .cases
+                .iter()
+                .all(|case| case.result == JudgeResult::Skipped)
+        );
+
+        let (judged, summary) = judge(&plan, &[JudgeResult::Accepted; 4]);
+        assert_eq!(judged, [1, 2, 3, 4]);
+        assert_eq!(summary.score, 100);
+        assert_eq!(summary.result, JudgeResult::Accepted);
+    }
+
+    #[test]
+    fn stop_at_first_failure_in_icpc_mode() {
+        let plan = plan_with_mode(
+            json!({
+                "taskType": "subtask",
+                "subtasks": [
+                    { "cases": cases(2), "score": 50, "policy": "sum" },
+                    { "cases": cases(2), "score": 50, "policy": "sum" }
+                ]
+            }),
+            "icpc",
+        )
+        .unwrap();
+
+        let (judged, summary) = judge(
+            &plan,
+            &[
+                JudgeResult::Accepted,
+                JudgeResult::Accepted,
+                JudgeResult::RuntimeError,
+                JudgeResult::Accepted,
+            ],
+        );
+
+        assert_eq!(judged, [1, 2, 3]);
+        assert_eq!(summary.result, JudgeResult::RuntimeError);
+        assert_eq!(summary.failed_case, Some(3));
+        assert_eq!(summary.subtasks[1].cases[1].result, JudgeResult::Skipped);
+
+        let (judged, summary) = judge(&plan, &[JudgeResult::Accepted; 4]);
+        assert_eq!(judged, [1, 2, 3, 4]);
+        assert_eq!(summary.result, JudgeResult::Accepted);
+        assert_eq!(summary.failed_case, None);
+    }
+}
@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Stress testing a suspect solution against a brute-force one.
+//!
+//! A generator is run with a new seed in each iteration, and both solutions
+//! run on the generated input, until the output of the suspect solution is
+//! rejected by the comparer, or the budget runs out.
+
+use std::io;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use rsjudge_traits::judger::JudgeResult;
+use rsjudge_traits::language::template::{TemplateError, TemplateVars};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs::{self, File};
+use tokio::time::Instant;
+
+use crate::comparer::{CompareResult, Comparer};
+use crate::judger::run::{Program, stdin};
+
+/// Error stress testing a solution.
+///
+/// Failures of the suspect solution are reported as counterexamples instead.
+#[derive(Debug, Error)]
+pub enum StressError {
+    #[error("Generator failed with seed {seed} with {result:?}: {message}")]
+    Generator {
+        seed: u64,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error("Brute-force solution failed with seed {seed} with {result:?}: {message}")]
+    BruteForce {
+        seed: u64,
+        result: JudgeResult,
+        message: String,
+    },
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Configuration of a stress test.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StressConfig {
+    /// Arguments of the generator, where `{seed}` is replaced by the seed of
+    /// the iteration.
+    #[serde(default = "default_args")]
+    pub(crate) args: Vec<String>,
+    /// Seed of the first iteration, increased by 1 in each iteration.
+    #[serde(default = "default_seed")]
+    pub(crate) first_seed: u64,
+    /// Max number of iterations.
+    pub(crate) iterations: NonZeroU32,
+    /// Time budget of all iterations **in milliseconds**.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) time: Option<NonZeroU32>,
+}
+
+fn default_args() -> Vec<String> {
+    vec!["{seed}".to_owned()]
+}
+
+fn default_seed() -> u64 {
+    1
+}
+
+/// Compiled programs taking part in a stress test.
+#[derive(Debug, Clone, Copy)]
+pub struct StressPrograms<'a> {
+    /// The generator printing the input of each iteration.
+    pub generator: &'a Program,
+    /// The solution suspected to be wrong.
+    pub suspect: &'a Program,
+    /// The brute-force solution, whose output is taken as the answer.
+    pub brute_force: &'a Program,
+}
+
+/// Outcome of a stress test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StressOutcome {
+    /// No counterexample is found in the iterations run.
+    Passed { iterations: u32 },
+    /// The first counterexample found.
+    Counterexample(Box<Counterexample>),
+}
+
+/// An input the suspect solution fails on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    /// Seed generating the input.
+    pub seed: u64,
+    /// Result of the suspect solution.
+    pub result: JudgeResult,
+    pub input: Vec<u8>,
+    /// Output of the suspect solution.
+    pub output: Vec<u8>,
+    /// Output of the brute-force solution.
+    pub answer: Vec<u8>,
+}
+
+/// Stress test the suspect solution, keeping files of the current iteration
+/// in `dir`.
+///
+/// # Errors
+///
+/// Returns an error if the generator or the brute-force solution fails, or a
+/// program cannot be run.
+pub async fn stress<C>(
+    config: &StressConfig,
+    programs: StressPrograms<'_>,
+    comparer: &C,
+    dir: &Path,
+) -> Result<StressOutcome, StressError>
+where
+    C: Comparer + Sync,
+{
+    let deadline = config
+        .time
+        .map(|time| Instant::now() + Duration::from_millis(time.get().into()));
+    let input_path = dir.join("input");
+    let answer_path = dir.join("answer");
+    let output_path = dir.join("output");
+
+    let mut iterations = 0;
+    while iterations < config.iterations.get()
+        && deadline.is_none_or(|deadline| Instant::now() < deadline)
+    {
+        let seed = config.first_seed.wrapping_add(iterations.into());
+        iterations += 1;
+
+        let mut vars = TemplateVars::new();
+        vars.set("seed", seed.to_string());
+        let args = config
+            .args
+            .iter()
+            .map(|arg| vars.render(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let info = programs
+            .generator
+            .run(&args, Stdio::null(), &input_path)
+            .await?;
+        if let Some(result) = info.result() {
+            return Err(StressError::Generator {
+                seed,
+                result,
+                message: info.message(),
+            });
+        }
+
+        let info = programs
+            .brute_force
+            .run(&[], stdin(&input_path).await?, &answer_path)
+            .await?;
+        if let Some(result) = info.result() {
+            return Err(StressError::BruteForce {
+                seed,
+                result,
+                message: info.message(),
+            });
+        }
+
+        let info = programs
+            .suspect
+            .run(&[], stdin(&input_path).await?, &output_path)
+            .await?;
+        let result = match info.result() {
+            Some(result) => result,
+            None => {
+                let compared = comparer
+                    .compare(
+                        File::open(&output_path).await?,
+                        File::open(&answer_path).await?,
+                    )
+                    .await?;
+                match compared {
+                    CompareResult::Accepted => continue,
+                    CompareResult::WrongAnswer => JudgeResult::WrongAnswer,
+                    CompareResult::PresentationError => JudgeResult::PresentationError,
+                }
+            }
+        };
+
+        return Ok(StressOutcome::Counterexample(Box::new(Counterexample {
+            seed,
+            result,
+            input: fs::read(&input_path).await?,
+            output: fs::read(&output_path).await?,
+            answer: fs::read(&answer_path).await?,
+        })));
+    }
+
+    Ok(StressOutcome::Passed { iterations })
+}
+
+#[cfg(test)]
+mod tests {
+    use rsjudge_traits::judger::JudgeResult;
+    use serde_json::json;
+
+    use super::{StressConfig, StressOutcome, StressPrograms, stress};
+    use crate::comparer::DefaultComparer;
+    use crate::judger::run::test_program;
+
+    #[tokio::test]
+    async fn find_counterexample() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let program = |command: &str| test_program(command, dir.path());
+
+        let generator = program("printf '%s\\n'");
+        let brute_force = program("read n; echo $((n * n))");
+        // Wrong for n >= 4.
+        let suspect = program("read n; [ \"$n\" -lt 4 ] && echo $((n * n)) || echo 0");
+        let programs = StressPrograms {
+            generator: &generator,
+            suspect: &suspect,
+            brute_force: &brute_force,
+        };
+        let comparer = DefaultComparer::common();
+
+        let config: StressConfig = serde_json::from_value(json!({ "iterations": 10 }))?;
+        let StressOutcome::Counterexample(counterexample) =
+            stress(&config, programs, &comparer, dir.path()).await?
+        else {
+            panic!("expected a counterexample");
+        };
+        assert_eq!(counterexample.seed, 4);
+        assert_eq!(counterexample.result, JudgeResult::WrongAnswer);
+        assert_eq!(counterexample.input, b"4\n");
+        assert_eq!(counterexample.output, b"0\n");
+        assert_eq!(counterexample.answer, b"16\n");
+
+        let config: StressConfig = serde_json::from_value(json!({
+            "iterations": 3,
+            "time": 10000
+        }))?;
+        assert_eq!(
+            stress(&config, programs, &comparer, dir.path()).await?,
+            StressOutcome::Passed { iterations: 3 }
+        );
+        Ok(())
+    }
+}
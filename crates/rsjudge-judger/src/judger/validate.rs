@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validation of the input of all cases when a problem is loaded.
+//!
+//! The [input validator] of a problem runs on every case input, so that bad
+//! test data is rejected before judging any submission. Results are cached by
+//! `cases_id`, which changes whenever the cases are updated.
+//!
+//! [input validator]: crate::judger::request::cases::InputValidator
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use thiserror::Error;
+
+use crate::judger::request::cases::CasesConfig;
+use crate::judger::run::{Program, stdin};
+
+/// A case whose input is rejected by the validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCase {
+    /// ID of the case, starting from 1.
+    pub case: u32,
+    /// Message of the validator.
+    pub message: String,
+}
+
+/// Cases whose input is rejected by the validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCases(pub Arc<[InvalidCase]>);
+
+impl Display for InvalidCases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid input of {} case(s)", self.0.len())?;
+        for case in self.0.iter() {
+            write!(f, "\ncase {}: {}", case.case, case.message.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Error validating the input of a problem.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("{0}")]
+    Invalid(InvalidCases),
+    #[error(transparent)]
+    Runner(#[from] rsjudge_runner::Error),
+}
+
+/// Run `validator` on the input of all cases in `cases_dir`, writing its
+/// stdout into `dir`.
+///
+/// Returns the cases with invalid input.
+///
+/// # Errors
+///
+/// Returns an error if the validator cannot be run.
+pub async fn validate_inputs(
+    cases: &CasesConfig,
+    cases_dir: &Path,
+    validator: &Program,
+    dir: &Path,
+) -> rsjudge_runner::Result<Vec<InvalidCase>> {
+    let stdout = dir.join("validator.out");
+    let mut invalid = Vec::new();
+
+    for (case, id) in cases.cases().zip(1..) {
+        let info = validator
+            .run(&[], stdin(&cases_dir.join(case.input())).await?, &stdout)
+            .await?;
+        if !info.is_success() {
+            invalid.push(InvalidCase {
+                case: id,
+                message: info.message(),
+            });
+        }
+    }
+
+    Ok(invalid)
+}
+
+/// Cache of validation results, keyed by `cases_id`.
+///
+/// Rejected cases stay rejected until they are updated with a new
+/// `cases_id`.
+#[derive(Debug, Default)]
+pub struct ValidationCache {
+    results: Mutex<HashMap<i32, Arc<[InvalidCase]>>>,
+}
+
+impl ValidationCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate the input of the cases identified by `cases_id`, unless
+    /// validated before.
+    ///
+    /// Problems without a validator are always valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns the per-case messages of the validator if any input is invalid,
+    /// or an error if the validator cannot be run.
+    pub async fn validate(
+        &self,
+        cases_id: i32,
+        cases: &CasesConfig,
+        cases_dir: &Path,
+        validator: Option<&Program>,
+        dir: &Path,
+    ) -> Result<(), ValidationError> {
+        let Some(validator) = validator else {
+            return Ok(());
+        };
+
+        let cached = self.lock().get(&cases_id).cloned();
+        let invalid = match cached {
+            Some(invalid) => invalid,
+            None => {
+                // Cases may be validated concurrently, which is only wasted
+                // work, as the results are the same.
+                let invalid: Arc<[InvalidCase]> = validate_inputs(cases, cases_dir, validator, dir)
+                    .await?
+                    .into();
+                self.lock().insert(cases_id, Arc::clone(&invalid));
+                invalid
+            }
+        };
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::Invalid(InvalidCases(invalid)))
+        }
+    }
+
+    /// Lock the results, recovering from a panic while they were locked, as
+    /// each result is inserted at once.
+    fn lock(&self) -> MutexGuard<'_, HashMap<i32, Arc<[InvalidCase]>>> {
+        self.results.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::fs;
+
+    use super::{InvalidCase, ValidationCache, ValidationError};
+    use crate::judger::request::cases::CasesConfig;
+    use crate::judger::run::test_program;
+
+    #[tokio::test]
+    async fn validate_and_cache() -> anyhow::Result<()> {
+        let cases_dir = tempfile::tempdir()?;
+        let work_dir = tempfile::tempdir()?;
+        for (name, content) in [("1.in", "1\n"), ("2.in", "9\n"), ("3.in", "3\n")] {
+            fs::write(cases_dir.path().join(name), content).await?;
+        }
+        let cases = serde_json::from_value::<CasesConfig>(json!({
+            "score": 90,
+            "judge": { "judgeType": "classic" },
+            "resourceLimits": { "time": 1000, "memory": 256 },
+            "task": {
+                "taskType": "simple",
+                "cases": [
+                    { "input": "1.in", "answer": "1.ans" },
+                    { "input": "2.in", "answer": "2.ans" },
+                    { "input": "3.in", "answer": "3.ans" }
+                ]
+            },
+            "validator": { "source": "validator.sh", "language": "Shell" }
+        }))?;
+        let program = |command: &str| test_program(command, work_dir.path());
+        let strict = program("read n; [ \"$n\" -le 5 ] || { echo \"$n > 5\" >&2; exit 3; }");
+        let lenient = program("true");
+
+        let cache = ValidationCache::new();
+        let validate = |id, validator| {
+            cache.validate(id, &cases, cases_dir.path(), validator, work_dir.path())
+        };
+
+        let Err(ValidationError::Invalid(invalid)) = validate(1, Some(&strict)).await else {
+            panic!("expected invalid input");
+        };
+        assert_eq!(
+            &*invalid.0,
+            [InvalidCase {
+                case: 2,
+                message: "9 > 5\n".to_owned(),
+            }]
+        );
+        assert_eq!(
+            invalid.to_string(),
+            "Invalid input of 1 case(s)\ncase 2: 9 > 5"
+        );
+
+        // Cached by `cases_id`.
+        assert!(validate(1, Some(&lenient)).await.is_err());
+        assert!(validate(2, Some(&lenient)).await.is_ok());
+        assert!(validate(3, None).await.is_ok());
+        Ok(())
+    }
+}
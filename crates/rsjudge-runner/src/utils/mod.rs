@@ -1,4 +1,5 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod cap_handle;
+pub mod network;
 pub mod resources;
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use nix::sched::{CloneFlags, unshare};
+use tokio::process::Command;
+
+/// A trait to cut a [`Command`] off from the network.
+pub trait DenyNetwork {
+    /// Run the command in a new, empty network namespace.
+    ///
+    /// A new user namespace is created as well, so no capability is required.
+    /// Spawning the command fails if namespaces are not available.
+    fn deny_network(&mut self) -> &mut Self;
+}
+
+impl DenyNetwork for Command {
+    fn deny_network(&mut self) -> &mut Self {
+        let unshare_network = || {
+            unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNET)?;
+            Ok(())
+        };
+        unsafe { self.pre_exec(unshare_network) };
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::process::Command;
+
+    use crate::utils::network::DenyNetwork as _;
+
+    #[tokio::test]
+    async fn only_loopback_available() {
+        let output = Command::new("cat")
+            .arg("/proc/net/dev")
+            .deny_network()
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+
+        let interfaces: Vec<_> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .skip(2)
+            .filter_map(|line| line.split(':').next().map(|name| name.trim().to_owned()))
+            .collect();
+        assert_eq!(interfaces, ["lo"]);
+    }
+}
@@ -86,44 +86,44 @@ pub trait WithResourceLimit {
 impl WithResourceLimit for Command {
     fn with_resource_limit(mut self, resource_info: ResourceLimit) -> CommandWithResourceLimit {
         if let Some(cpu_time_limit) = resource_info.cpu_time_limit() {
-            let set_cpu_limit = move || {
-                setrlimit(
-                    Resource::RLIMIT_CPU,
-                    cpu_time_limit.as_secs(),
-                    cpu_time_limit.as_secs(),
-                )?;
-
-                Ok(())
-            };
-            unsafe {
-                self.pre_exec(set_cpu_limit);
-            }
+            set_rlimit(&mut self, Resource::RLIMIT_CPU, cpu_time_limit.as_secs());
         }
 
         if let Some(memory_limit) = resource_info.memory_limit() {
-            let set_memory_limit = move || {
-                setrlimit(Resource::RLIMIT_AS, memory_limit, memory_limit)?;
-
-                Ok(())
-            };
-            unsafe {
-                self.pre_exec(set_memory_limit);
-            }
+            set_rlimit(&mut self, Resource::RLIMIT_AS, memory_limit);
         }
 
-        if let Some(max_file_size_limit) = resource_info.max_file_size_limit() {
-            let set_max_file_size_limit = move || {
-                setrlimit(
-                    Resource::RLIMIT_FSIZE,
-                    max_file_size_limit,
-                    max_file_size_limit,
-                )?;
-
-                Ok(())
-            };
-            unsafe {
-                self.pre_exec(set_max_file_size_limit);
-            }
+        if let Some(stack_limit) = resource_info.stack_limit() {
+            set_rlimit(&mut self, Resource::RLIMIT_STACK, stack_limit);
+        }
+
+        // Output is usually redirected to a file, so the output limit is
+        // applied as a file size limit as well.
+        //
+        // Output written to a pipe must be counted by the reader.
+        let max_file_size_limit = match (
+            resource_info.max_file_size_limit(),
+            resource_info.output_limit(),
+        ) {
+            (Some(file_size), Some(output)) => Some(file_size.min(output)),
+            (file_size, output) => file_size.or(output),
+        };
+        if let Some(max_file_size_limit) = max_file_size_limit {
+            set_rlimit(&mut self, Resource::RLIMIT_FSIZE, max_file_size_limit);
+        }
+
+        // Note: `RLIMIT_NPROC` counts all processes of the real user ID,
+        // not only the descendants of the command.
+        if let Some(process_limit) = resource_info.process_limit() {
+            set_rlimit(&mut self, Resource::RLIMIT_NPROC, process_limit);
+        }
+
+        if let Some(open_files_limit) = resource_info.open_files_limit() {
+            set_rlimit(&mut self, Resource::RLIMIT_NOFILE, open_files_limit);
+        }
+
+        if !resource_info.allow_core_dump() {
+            set_rlimit(&mut self, Resource::RLIMIT_CORE, 0);
         }
 
         CommandWithResourceLimit {
@@ -146,6 +146,19 @@ impl WithResourceLimit for Command {
     }
 }
 
+/// Set both the soft and hard limit of `resource` before executing the
+/// command.
+fn set_rlimit(command: &mut Command, resource: Resource, limit: u64) {
+    let set_limit = move || {
+        setrlimit(resource, limit, limit)?;
+
+        Ok(())
+    };
+    unsafe {
+        command.pre_exec(set_limit);
+    }
+}
+
 #[derive(Debug)]
 pub struct ChildWithDeadline {
     child: Child,
@@ -42,7 +42,7 @@ pub trait Judger {
     ) -> impl Future<Output = Result<(Output, JudgeResult), Self::Error>> + Send;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// The result of a judge.
 ///
 /// This enum represents the result of a judge.
@@ -65,4 +65,9 @@ pub enum JudgeResult {
     MemoryLimitExceeded,
     /// The code's output size exceeded the specified output limit.
     OutputLimitExceeded,
+    /// The code didn't create the output file required by the problem.
+    OutputFileMissing,
+    /// The case is not judged, as the result is already decided by other
+    /// cases.
+    Skipped,
 }
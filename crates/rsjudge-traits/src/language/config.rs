@@ -2,8 +2,18 @@
 
 //! Language representation from configuration file.
 
+use std::num::{NonZeroU32, NonZeroU64};
+use std::time::Duration;
+
 use indexmap::IndexMap;
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::process::Command;
+
+use crate::resource::ResourceLimit;
+
+/// `PATH` used if not set by the language.
+pub const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
 
 /// Language definition from configuration file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,9 +24,336 @@ pub struct LanguageDef {
     #[serde(default)]
     options: IndexMap<String, ConfigDef>,
     version: Option<String>,
+
+    #[serde(flatten)]
+    limit_factors: LimitFactors,
+
+    /// `PATH` of both phases, defaults to [`DEFAULT_PATH`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// Environment variables of both phases.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    env: IndexMap<String, String>,
+    /// Environment variables of the compile phase, overriding `env`.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    compile_env: IndexMap<String, String>,
+    /// Environment variables of the run phase, overriding `env`.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    run_env: IndexMap<String, String>,
+
+    /// Resource limits of the compile phase.
+    #[serde(default)]
+    compile_limits: CompileLimits,
+
+    /// Format of the compiler diagnostics.
+    #[serde(default)]
+    diagnostics: DiagnosticFormat,
+
+    /// How to build multi-file submissions, which are rejected if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<ProjectDef>,
+}
+
+impl LanguageDef {
+    /// Get the execution type of the language.
+    #[must_use]
+    pub fn exec_type(&self) -> &ExecType {
+        &self.exec_type
+    }
+
+    /// Get the resource limit adjustment of the language.
+    #[must_use]
+    pub fn limit_factors(&self) -> &LimitFactors {
+        &self.limit_factors
+    }
+
+    /// Get the environment of the given phase.
+    ///
+    /// Nothing is inherited from the environment of the judger.
+    #[must_use]
+    pub fn environment(&self, phase: Phase) -> Environment {
+        let phase_env = match phase {
+            Phase::Compile => &self.compile_env,
+            Phase::Run => &self.run_env,
+        };
+
+        let mut vars = IndexMap::new();
+        vars.insert(
+            "PATH".to_owned(),
+            self.path.as_deref().unwrap_or(DEFAULT_PATH).to_owned(),
+        );
+        vars.extend(
+            self.env
+                .iter()
+                .chain(phase_env)
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        Environment { vars }
+    }
+
+    /// Get the resource limits of the compile phase.
+    #[must_use]
+    pub fn compile_limits(&self) -> &CompileLimits {
+        &self.compile_limits
+    }
+
+    /// Get the toolchain version, a shell string like `$(gcc --version)`, if
+    /// any.
+    #[must_use]
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Get the format of the compiler diagnostics.
+    #[must_use]
+    pub fn diagnostics(&self) -> DiagnosticFormat {
+        self.diagnostics
+    }
+
+    /// Get how to build multi-file submissions, if supported.
+    #[must_use]
+    pub fn project(&self) -> Option<&ProjectDef> {
+        self.project.as_ref()
+    }
+
+    /// Get the command template run in the compile phase.
+    ///
+    /// For multi-file submissions, [`ProjectDef::compile`] takes precedence.
+    #[must_use]
+    pub fn compile_command(&self, project: bool) -> Option<&str> {
+        self.project
+            .as_ref()
+            .filter(|_| project)
+            .and_then(|def| def.compile.as_deref())
+            .or_else(|| self.exec_type.compile_command())
+    }
+}
+
+/// Build definition of multi-file submissions.
+///
+/// The submitted files are placed in the work directory, keeping their
+/// relative paths.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectDef {
+    /// Build entry point required in the submission, e.g. `Main.java`,
+    /// `Makefile` or `Cargo.toml`, available as `{entry}` in templates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    /// Glob matching the source files, e.g. `**/*.java`, available as
+    /// `{...sources}` in templates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<String>,
+    /// Compile command of multi-file submissions, defaults to the one of the
+    /// execution type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile: Option<String>,
+}
+
+/// Format of the compiler diagnostics, for parsing them into structured
+/// diagnostics.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticFormat {
+    /// Plain text in the `file:line:column: severity: message` format, used by
+    /// most compilers.
+    #[default]
+    Text,
+    /// JSON output of GCC, enabled by `-fdiagnostics-format=json`.
+    ///
+    /// The compiler output shown to users is then JSON as well, which is hard
+    /// to read, especially when truncated.
+    GccJson,
+    /// JSON output of rustc, enabled by `--error-format=json`.
+    ///
+    /// The same caveat as [`GccJson`](Self::GccJson) applies.
+    RustcJson,
+}
+
+/// Resource limits of the compile phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileLimits {
+    /// CPU time limit **in milliseconds**.
+    pub time: NonZeroU32,
+    /// Wall time limit **in milliseconds**, defaults to twice the CPU time
+    /// limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wall_time: Option<NonZeroU32>,
+    /// Memory limit **in MiB**.
+    pub memory: NonZeroU32,
+    /// Max size of each of the compiler stdout and stderr **in bytes**.
+    ///
+    /// Exceeding output is discarded, instead of failing the compilation.
+    pub output: u32,
+    /// Max size of the produced artifacts **in MiB**.
+    pub artifact_size: NonZeroU32,
+    /// Whether the compiler can access the network.
+    pub network: bool,
+}
+
+impl Default for CompileLimits {
+    fn default() -> Self {
+        Self {
+            time: NonZeroU32::new(10_000).unwrap(),
+            wall_time: None,
+            memory: NonZeroU32::new(1024).unwrap(),
+            output: 64 * 1024,
+            artifact_size: NonZeroU32::new(64).unwrap(),
+            network: false,
+        }
+    }
+}
+
+impl CompileLimits {
+    /// Get the wall time limit.
+    #[must_use]
+    pub fn wall_time(&self) -> Duration {
+        let wall_time = self
+            .wall_time
+            .map_or(2 * u64::from(self.time.get()), |time| u64::from(time.get()));
+        Duration::from_millis(wall_time)
+    }
+
+    /// Get the max artifact size **in bytes**.
+    #[must_use]
+    pub fn artifact_size(&self) -> u64 {
+        u64::from(self.artifact_size.get()) << 20
+    }
+
+    /// Convert to a [`ResourceLimit`] for the compiler.
+    ///
+    /// The artifact size is applied as the max file size, plus one byte, so
+    /// that hitting the limit can be told apart from an artifact of exactly
+    /// the max size.
+    #[must_use]
+    pub fn resource_limit(&self) -> ResourceLimit {
+        ResourceLimit::new(
+            Some(Duration::from_millis(self.time.get().into())),
+            Some(self.wall_time()),
+            NonZeroU64::new(u64::from(self.memory.get()) << 20),
+            NonZeroU64::new(self.artifact_size() + 1),
+        )
+    }
+}
+
+/// Phase of judging a submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Compiling or checking the source code.
+    Compile,
+    /// Running the program against test cases.
+    Run,
+}
+
+/// Environment variables of a sandboxed command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    vars: IndexMap<String, String>,
+}
+
+impl Default for Environment {
+    /// Environment with only `PATH` set to [`DEFAULT_PATH`].
+    fn default() -> Self {
+        let mut vars = IndexMap::new();
+        vars.insert("PATH".to_owned(), DEFAULT_PATH.to_owned());
+        Self { vars }
+    }
+}
+
+impl Environment {
+    /// Get the environment variables.
+    #[must_use]
+    pub fn vars(&self) -> &IndexMap<String, String> {
+        &self.vars
+    }
+
+    /// Clear the environment of `command`, and set the variables instead.
+    pub fn apply<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        command.env_clear().envs(&self.vars)
+    }
+}
+
+/// Adjustment of the time and memory limits of a problem for a language.
+///
+/// The effective limit is `limit * factor + bonus`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LimitFactors {
+    /// Multiplier of the time limit, defaults to 1.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_factor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time_factor: Option<f64>,
+    /// Extra time **in milliseconds**, defaults to 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_bonus: Option<u32>,
+    /// Multiplier of the memory limit, defaults to 1.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_factor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory_factor: Option<f64>,
+    /// Extra memory **in MiB**, defaults to 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bonus: Option<u32>,
+}
+
+impl LimitFactors {
+    /// Override the factors set in `other`, keeping the rest.
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            time_factor: other.time_factor.or(self.time_factor),
+            time_bonus: other.time_bonus.or(self.time_bonus),
+            memory_factor: other.memory_factor.or(self.memory_factor),
+            memory_bonus: other.memory_bonus.or(self.memory_bonus),
+        }
+    }
+
+    /// Apply the factors to a time limit, saturating at [`Duration::MAX`].
+    #[must_use]
+    pub fn apply_time(&self, time: Duration) -> Duration {
+        let factor = self.time_factor.unwrap_or(1.0);
+        Duration::try_from_secs_f64(time.as_secs_f64() * factor)
+            .unwrap_or(Duration::MAX)
+            .saturating_add(Duration::from_millis(self.time_bonus.unwrap_or(0).into()))
+    }
+
+    /// Apply the factors to a memory limit **in bytes**, saturating at
+    /// [`u64::MAX`].
+    #[must_use]
+    pub fn apply_memory(&self, memory: u64) -> u64 {
+        ((memory as f64 * self.memory_factor.unwrap_or(1.0)) as u64)
+            .saturating_add(u64::from(self.memory_bonus.unwrap_or(0)) << 20)
+    }
+}
+
+/// Deserialize a factor of [`LimitFactors`], which must be finite and
+/// positive.
+///
+/// # Errors
+///
+/// Returns an error if the factor is zero, negative, infinite or NaN.
+pub fn deserialize_factor<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let factor = Option::<f64>::deserialize(deserializer)?;
+    match factor {
+        Some(value) if !(value.is_finite() && value > 0.0) => Err(D::Error::custom(format!(
+            "invalid factor {value}, expected a finite positive number"
+        ))),
+        _ => Ok(factor),
+    }
 }
 
 /// Execution type of the language.
+///
+/// Commands are templates, see [`template`](super::template) for the
+/// available variables.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "exec_type")]
 pub enum ExecType {
@@ -42,6 +379,31 @@ pub enum ExecType {
     },
 }
 
+impl ExecType {
+    /// Get the command run in the compile phase, i.e. the compilation command,
+    /// or the syntax check command of [`ExecType::SourceCode`].
+    ///
+    /// Returns `None` if there is nothing to run.
+    #[must_use]
+    pub fn compile_command(&self) -> Option<&str> {
+        match self {
+            Self::Binary { compile } | Self::ByteCode { compile, .. } => Some(compile),
+            Self::SourceCode { check, .. } => check.as_deref(),
+        }
+    }
+
+    /// Get the command run in the run phase.
+    ///
+    /// Binaries are executed directly as `./{out_file}`.
+    #[must_use]
+    pub fn execute_command(&self) -> &str {
+        match self {
+            Self::Binary { .. } => "./{out_file}",
+            Self::ByteCode { execute, .. } | Self::SourceCode { execute, .. } => execute,
+        }
+    }
+}
+
 /// Additional dynamic configuration definition for the language.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
@@ -72,11 +434,16 @@ mod tests {
     use std::collections::HashMap;
     use std::fs::File;
     use std::io::Read;
+    use std::time::Duration;
 
     use indexmap::{IndexMap, indexmap};
+    use tokio::process::Command;
     use toml::toml;
 
-    use super::{ConfigDef, ExecType, LanguageDef};
+    use super::{
+        CompileLimits, ConfigDef, DEFAULT_PATH, DiagnosticFormat, ExecType, LanguageDef,
+        LimitFactors, Phase,
+    };
 
     #[test]
     fn test_language() {
@@ -101,6 +468,14 @@ mod tests {
                 }
             },
             version: Some("$(gcc --version)".into()),
+            limit_factors: LimitFactors::default(),
+            path: None,
+            env: IndexMap::new(),
+            compile_env: IndexMap::new(),
+            run_env: IndexMap::new(),
+            compile_limits: CompileLimits::default(),
+            diagnostics: DiagnosticFormat::default(),
+            project: None,
         };
 
         let languages = HashMap::from([("C".to_string(), c_def)]);
@@ -143,7 +518,30 @@ mod tests {
 
             ["C++"]
             exec_type = "binary"
-            compile = "g++ {...flags} -o {out_file} {src_file}"
+            compile = "g++ {...flags} -fdiagnostics-format=json -o {out_file} {src_file}"
+            diagnostics = "gcc_json"
+
+            [Java]
+            exec_type = "byte_code"
+            compile = "javac -d {tmp_dir} Main.java && jar -cvfe {out_file} Main -C {tmp_dir} *"
+            execute = "java -jar {out_file}"
+            time_factor = 2.0
+            memory_bonus = 64
+
+            [Java.env]
+            LANG = "C.UTF-8"
+
+            [Java.compile_limits]
+            time = 20000
+            memory = 2048
+
+            [Java.project]
+            sources = "**/*.java"
+            compile = "javac -d {tmp_dir} {...sources} && jar -cvfe {out_file} Main -C {tmp_dir} *"
+
+            [Java.run_env]
+            LANG = "en_US.UTF-8"
+            HOME = "/tmp"
 
             ["C++".options.O2]
             type = "bool"
@@ -167,6 +565,100 @@ mod tests {
         let languages: HashMap<String, LanguageDef> = toml::from_str(&toml.to_string()).unwrap();
 
         println!("{languages:#?}");
+
+        assert_eq!(languages["C"].limit_factors(), &LimitFactors::default());
+        assert_eq!(languages["C"].diagnostics(), DiagnosticFormat::Text);
+        assert_eq!(languages["C++"].diagnostics(), DiagnosticFormat::GccJson);
+        assert_eq!(languages["C"].project(), None);
+        assert_eq!(
+            languages["Java"].compile_command(true),
+            Some("javac -d {tmp_dir} {...sources} && jar -cvfe {out_file} Main -C {tmp_dir} *")
+        );
+        assert_eq!(
+            languages["Java"].compile_command(false),
+            Some("javac -d {tmp_dir} Main.java && jar -cvfe {out_file} Main -C {tmp_dir} *")
+        );
+        assert_eq!(
+            languages["Java"].limit_factors(),
+            &LimitFactors {
+                time_factor: Some(2.0),
+                memory_bonus: Some(64),
+                ..Default::default()
+            }
+        );
+
+        let java = &languages["Java"];
+        assert_eq!(languages["C"].compile_limits(), &CompileLimits::default());
+        assert_eq!(
+            java.compile_limits().resource_limit().wall_time_limit(),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(
+            java.compile_limits().resource_limit().memory_limit(),
+            Some(2048 << 20)
+        );
+        assert_eq!(
+            java.environment(Phase::Compile).vars(),
+            &indexmap! {
+                "PATH".to_owned() => DEFAULT_PATH.to_owned(),
+                "LANG".to_owned() => "C.UTF-8".to_owned(),
+            }
+        );
+        assert_eq!(
+            java.environment(Phase::Run).vars(),
+            &indexmap! {
+                "PATH".to_owned() => DEFAULT_PATH.to_owned(),
+                "LANG".to_owned() => "en_US.UTF-8".to_owned(),
+                "HOME".to_owned() => "/tmp".to_owned(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_environment() {
+        let mut command = Command::new("env");
+        python_def().environment(Phase::Run).apply(&mut command);
+        let output = command.output().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("PATH={DEFAULT_PATH}\nPYTHONDONTWRITEBYTECODE=1\n")
+        );
+    }
+
+    fn python_def() -> LanguageDef {
+        toml::from_str(
+            r#"
+            exec_type = "source_code"
+            execute = "python3 {src_file}"
+
+            [env]
+            PYTHONDONTWRITEBYTECODE = "1"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_limit_factors() {
+        for factor in ["0.0", "-1.0", "nan", "inf"] {
+            let toml =
+                format!("exec_type = \"source_code\"\nexecute = \"sh\"\ntime_factor = {factor}");
+            assert!(toml::from_str::<LanguageDef>(&toml).is_err(), "{factor}");
+        }
+        let language: LanguageDef =
+            toml::from_str("exec_type = \"source_code\"\nexecute = \"sh\"\nmemory_factor = 1.5")
+                .unwrap();
+        assert_eq!(language.limit_factors().memory_factor, Some(1.5));
+
+        let factors = LimitFactors {
+            time_factor: Some(1e300),
+            time_bonus: Some(1),
+            memory_factor: Some(1e300),
+            memory_bonus: Some(1),
+        };
+        assert_eq!(factors.apply_time(Duration::from_secs(1)), Duration::MAX);
+        assert_eq!(factors.apply_memory(1 << 20), u64::MAX);
     }
 
     #[test]
@@ -177,5 +669,10 @@ mod tests {
         let output = toml::from_str::<IndexMap<String, LanguageDef>>(&input).unwrap();
 
         println!("{output:#?}");
+
+        // Compiler output is shown to users, so keep it human-readable.
+        for language in ["C", "C++", "Rust"] {
+            assert_eq!(output[language].diagnostics(), DiagnosticFormat::Text);
+        }
     }
 }
@@ -5,3 +5,4 @@
 pub mod config;
 pub mod info;
 pub mod option;
+pub mod template;
@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Templates of compilation and execution commands.
+//!
+//! A template refers to a variable with `{name}`, and to a list variable with
+//! `{...name}`, which expands to its items separated by spaces. Braces not
+//! forming a variable reference, e.g. `${HOME}` or `{a,b}` in shell scripts,
+//! are kept as is.
+//!
+//! Besides `src_file`, `out_file` and friends, the following variables are set
+//! from the resource limits by [`TemplateVars::set_limits`]:
+//!
+//! - `memory_limit_mb`: memory limit in MiB;
+//! - `memory_limit_kb`: memory limit in KiB;
+//! - `time_limit_ms`: CPU time limit in milliseconds;
+//! - `stack_limit_mb`: stack size limit in MiB.
+//!
+//! Problems with a grader set `grader_files` to the grader source files to
+//! compile with the submission, which is empty for other problems.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::resource::ResourceLimit;
+
+/// Error rendering a template.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The template refers to a variable which is not set.
+    #[error("Undefined variable `{0}` in template")]
+    Undefined(String),
+    /// A list variable is referred to as a single value, or vice versa.
+    #[error("Variable `{0}` referred to as the wrong kind")]
+    KindMismatch(String),
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Single(String),
+    List(Vec<String>),
+}
+
+/// Variables available to a template.
+#[derive(Debug, Default, Clone)]
+pub struct TemplateVars {
+    vars: HashMap<String, Value>,
+}
+
+impl TemplateVars {
+    /// Create an empty set of variables.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a variable, referred to as `{name}`.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.vars.insert(name.into(), Value::Single(value.into()));
+        self
+    }
+
+    /// Set a list variable, referred to as `{...name}`.
+    pub fn set_list<I>(&mut self, name: impl Into<String>, values: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.vars.insert(
+            name.into(),
+            Value::List(values.into_iter().map(Into::into).collect()),
+        );
+        self
+    }
+
+    /// Set the variables derived from `limit`.
+    ///
+    /// Variables of unset limits are left undefined.
+    pub fn set_limits(&mut self, limit: &ResourceLimit) -> &mut Self {
+        if let Some(memory) = limit.memory_limit() {
+            self.set("memory_limit_mb", (memory >> 20).to_string());
+            self.set("memory_limit_kb", (memory >> 10).to_string());
+        }
+        if let Some(time) = limit.cpu_time_limit() {
+            self.set("time_limit_ms", time.as_millis().to_string());
+        }
+        if let Some(stack) = limit.stack_limit() {
+            self.set("stack_limit_mb", (stack >> 20).to_string());
+        }
+        self
+    }
+
+    /// Render `template` with the variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template refers to an undefined variable, or
+    /// refers to a variable as the wrong kind.
+    pub fn render(&self, template: &str) -> Result<String, TemplateError> {
+        let mut output = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some((spread, name, len)) = parse_reference(rest) else {
+                output.push('{');
+                rest = &rest[1..];
+                continue;
+            };
+
+            match (self.vars.get(name), spread) {
+                (None, _) => return Err(TemplateError::Undefined(name.to_owned())),
+                (Some(Value::Single(value)), false) => output.push_str(value),
+                (Some(Value::List(values)), true) => output.push_str(&values.join(" ")),
+                (Some(_), _) => return Err(TemplateError::KindMismatch(name.to_owned())),
+            }
+            rest = &rest[len..];
+        }
+        output.push_str(rest);
+
+        Ok(output)
+    }
+}
+
+/// Parse a variable reference at the start of `input`.
+///
+/// Returns whether it is a list reference, the variable name and the length of
+/// the reference.
+fn parse_reference(input: &str) -> Option<(bool, &str, usize)> {
+    let end = input.find('}')?;
+    let inner = &input[1..end];
+    let (spread, name) = match inner.strip_prefix("...") {
+        Some(name) => (true, name),
+        None => (false, inner),
+    };
+
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    is_identifier.then_some((spread, name, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use super::{TemplateError, TemplateVars};
+    use crate::resource::ResourceLimit;
+
+    #[test]
+    fn render_template() {
+        let mut vars = TemplateVars::new();
+        vars.set("src_file", "main.c")
+            .set("out_file", "main")
+            .set_list("flags", ["-O2", "-std=c99"]);
+
+        assert_eq!(
+            vars.render("gcc {...flags} -o {out_file} {src_file}"),
+            Ok("gcc -O2 -std=c99 -o main main.c".to_owned())
+        );
+        assert_eq!(
+            vars.render("echo ${HOME} {a,b} {} {"),
+            Ok("echo ${HOME} {a,b} {} {".to_owned())
+        );
+        assert_eq!(
+            vars.render("{tmp_dir}"),
+            Err(TemplateError::Undefined("tmp_dir".to_owned()))
+        );
+        assert_eq!(
+            vars.render("{flags}"),
+            Err(TemplateError::KindMismatch("flags".to_owned()))
+        );
+    }
+
+    #[test]
+    fn render_limits() {
+        let mut limit = ResourceLimit::new(
+            Some(Duration::from_millis(1500)),
+            None,
+            NonZeroU64::new(256 << 20),
+            None,
+        );
+        limit.set_stack_limit(NonZeroU64::new(64 << 20));
+
+        let mut vars = TemplateVars::new();
+        vars.set("out_file", "Main.jar").set_limits(&limit);
+
+        assert_eq!(
+            vars.render(
+                "java -Xmx{memory_limit_mb}m -Xss{stack_limit_mb}m -jar {out_file} \
+                 {memory_limit_kb} {time_limit_ms}"
+            ),
+            Ok("java -Xmx256m -Xss64m -jar Main.jar 262144 1500".to_owned())
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::num::NonZeroU64;
 use std::time::Duration;
 
 /// Resource limit for judging code.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct ResourceLimit {
     /// CPU time limit.
     cpu_time_limit: Option<Duration>,
@@ -21,6 +21,20 @@ pub struct ResourceLimit {
     memory_limit: Option<NonZeroU64>,
     /// Max file size limit **in bytes**.
     max_file_size_limit: Option<NonZeroU64>,
+    /// The stack size limit **in bytes**.
+    ///
+    /// Defaults to the memory limit if not set.
+    stack_limit: Option<NonZeroU64>,
+    /// Max number of processes and threads.
+    process_limit: Option<NonZeroU64>,
+    /// Max number of open file descriptors.
+    open_files_limit: Option<NonZeroU64>,
+    /// Max output size **in bytes**.
+    output_limit: Option<NonZeroU64>,
+    /// Whether core dumps are allowed.
+    ///
+    /// Core dumps are disabled by default.
+    allow_core_dump: bool,
 }
 
 impl ResourceLimit {
@@ -37,6 +51,7 @@ impl ResourceLimit {
             wall_time_limit,
             memory_limit,
             max_file_size_limit,
+            ..Self::default()
         }
     }
 
@@ -64,6 +79,36 @@ impl ResourceLimit {
         self.max_file_size_limit.map(From::from)
     }
 
+    /// Get the stack size limit, falling back to the memory limit.
+    #[must_use]
+    pub fn stack_limit(&self) -> Option<u64> {
+        self.stack_limit.or(self.memory_limit).map(From::from)
+    }
+
+    /// Get the max number of processes and threads.
+    #[must_use]
+    pub fn process_limit(&self) -> Option<u64> {
+        self.process_limit.map(From::from)
+    }
+
+    /// Get the max number of open file descriptors.
+    #[must_use]
+    pub fn open_files_limit(&self) -> Option<u64> {
+        self.open_files_limit.map(From::from)
+    }
+
+    /// Get the max output size.
+    #[must_use]
+    pub fn output_limit(&self) -> Option<u64> {
+        self.output_limit.map(From::from)
+    }
+
+    /// Get whether core dumps are allowed.
+    #[must_use]
+    pub fn allow_core_dump(&self) -> bool {
+        self.allow_core_dump
+    }
+
     /// Set the CPU time limit.
     pub fn set_cpu_time_limit(&mut self, cpu_time_limit: Option<Duration>) -> &mut Self {
         self.cpu_time_limit = cpu_time_limit;
@@ -90,4 +135,34 @@ impl ResourceLimit {
         self.max_file_size_limit = max_file_size_limit;
         self
     }
+
+    /// Set the stack size limit.
+    pub fn set_stack_limit(&mut self, stack_limit: Option<NonZeroU64>) -> &mut Self {
+        self.stack_limit = stack_limit;
+        self
+    }
+
+    /// Set the max number of processes and threads.
+    pub fn set_process_limit(&mut self, process_limit: Option<NonZeroU64>) -> &mut Self {
+        self.process_limit = process_limit;
+        self
+    }
+
+    /// Set the max number of open file descriptors.
+    pub fn set_open_files_limit(&mut self, open_files_limit: Option<NonZeroU64>) -> &mut Self {
+        self.open_files_limit = open_files_limit;
+        self
+    }
+
+    /// Set the max output size.
+    pub fn set_output_limit(&mut self, output_limit: Option<NonZeroU64>) -> &mut Self {
+        self.output_limit = output_limit;
+        self
+    }
+
+    /// Set whether core dumps are allowed.
+    pub fn set_allow_core_dump(&mut self, allow_core_dump: bool) -> &mut Self {
+        self.allow_core_dump = allow_core_dump;
+        self
+    }
 }
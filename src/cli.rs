@@ -2,7 +2,7 @@
 
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Debug, Parser)]
 #[command(author, about, version)]
@@ -10,4 +10,45 @@ pub struct Args {
     #[arg(short, long, default_value = "./config", value_name = "DIR")]
     /// Specify the configuration directory
     pub config_dir: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Lint a problem directory, printing findings as JSON
+    Lint {
+        /// The problem directory containing `cases.json`
+        #[arg(value_name = "DIR")]
+        problem_dir: PathBuf,
+
+        #[arg(long, value_name = "COMMAND")]
+        /// Check that the checker or interactor compiles with this command,
+        /// where `{src_file}` is replaced by its source, otherwise a warning
+        /// is reported that compilation is not checked
+        compile_command: Option<String>,
+    },
+
+    /// Convert each problem in a directory between formats, printing warnings
+    /// as JSON
+    Convert {
+        #[arg(long, value_name = "FORMAT")]
+        /// The format to convert from, `rsjudge` or one of `hydro`, `qduoj`,
+        /// `domjudge` and `lemon`
+        from: String,
+
+        #[arg(long, value_name = "FORMAT")]
+        /// The format to convert to, `rsjudge` or one of `hydro`, `qduoj`,
+        /// `domjudge` and `lemon`
+        to: String,
+
+        /// The directory containing a problem in each subdirectory
+        #[arg(value_name = "INPUT_DIR")]
+        input_dir: PathBuf,
+
+        /// The directory to write converted problems into
+        #[arg(value_name = "OUTPUT_DIR")]
+        output_dir: PathBuf,
+    },
 }
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use rsjudge_amqp::config::AmqpConfig;
 #[cfg(feature = "grpc")]
 use rsjudge_grpc::config::GrpcConfig;
+use rsjudge_judger::judger::cache::CacheConfig;
 #[cfg(feature = "rest")]
 use rsjudge_rest::config::RestConfig;
 use rsjudge_traits::language::config::LanguageDef;
@@ -16,6 +17,8 @@ use serde::{Deserialize, Serialize};
 pub struct Config {
     pub executors: HashMap<String, LanguageDef>,
     pub services: Services,
+    #[serde(default)]
+    pub compile_cache: CacheConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -6,12 +6,19 @@
 //！ An online judge sandbox server in Rust,
 //！ inspired by [go-judge](https://github.com/criyle/go-judge), for SASTOJ.
 
-use anyhow::Context;
+use std::io::{Write as _, stdout};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
 use log::{debug, warn};
+use rsjudge_judger::judger::convert::Format;
+use rsjudge_judger::judger::discover::{DiscoverConfig, load_or_discover};
+use rsjudge_judger::judger::lint::{LintLevel, LintOptions, lint_problem};
+use serde::Serialize;
 use sysinfo::System;
-use tokio::fs::read;
+use tokio::fs::{read, read_dir};
 
-pub use crate::cli::Args;
+pub use crate::cli::{Args, Command};
 
 mod cli;
 mod config;
@@ -25,6 +32,20 @@ mod config;
 pub async fn async_main(args: Args) -> anyhow::Result<()> {
     debug!("{args:?}");
 
+    match args.command {
+        Some(Command::Lint {
+            problem_dir,
+            compile_command,
+        }) => return lint(&problem_dir, LintOptions { compile_command }).await,
+        Some(Command::Convert {
+            from,
+            to,
+            input_dir,
+            output_dir,
+        }) => return convert(&from, &to, &input_dir, &output_dir).await,
+        None => {}
+    }
+
     let executor_config_path = &args.config_dir.join("executors.toml");
     let config = read(executor_config_path).await.with_context(|| {
         format!(
@@ -57,3 +78,141 @@ pub async fn async_main(args: Args) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Lint the problem in `problem_dir`, printing the findings as JSON.
+///
+/// # Errors
+///
+/// This function returns error if any finding is an error, or the lint cannot
+/// be run.
+async fn lint(problem_dir: &Path, options: LintOptions) -> anyhow::Result<()> {
+    let findings = lint_problem(problem_dir, &options)
+        .await
+        .with_context(|| format!("Failed to lint problem at {}", problem_dir.display()))?;
+
+    let mut output = serde_json::to_vec_pretty(&findings)?;
+    output.push(b'\n');
+    stdout().write_all(&output)?;
+
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.level == LintLevel::Error)
+        .count();
+    if errors > 0 {
+        bail!(
+            "Found {errors} error(s) in problem at {}",
+            problem_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Name of the native format of `cases.json`.
+const NATIVE_FORMAT: &str = "rsjudge";
+
+/// Conversion between the native format and another one.
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Import(Format),
+    Export(Format),
+}
+
+/// Result of converting a problem.
+#[derive(Debug, Serialize)]
+struct ConvertReport {
+    problem: PathBuf,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Convert each problem in `input_dir` from format `from` to format `to` into
+/// `output_dir`, printing the warnings and errors as JSON.
+///
+/// Failing problems are reported without stopping the conversion of others.
+///
+/// # Errors
+///
+/// This function returns error if neither format is native, or any problem
+/// fails to convert.
+async fn convert(from: &str, to: &str, input_dir: &Path, output_dir: &Path) -> anyhow::Result<()> {
+    let direction = match (
+        from.eq_ignore_ascii_case(NATIVE_FORMAT),
+        to.eq_ignore_ascii_case(NATIVE_FORMAT),
+    ) {
+        (true, false) => Direction::Export(to.parse()?),
+        (false, true) => Direction::Import(from.parse()?),
+        _ => bail!("Exactly one of the formats must be `{NATIVE_FORMAT}`"),
+    };
+
+    let mut problems = Vec::new();
+    let mut entries = read_dir(input_dir)
+        .await
+        .with_context(|| format!("Cannot read problems in {}", input_dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            problems.push(entry.path());
+        }
+    }
+    problems.sort();
+
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    for src in problems {
+        let dst = output_dir.join(src.file_name().unwrap_or_default());
+        match convert_problem(direction, &src, &dst).await {
+            Ok(converted) => reports.extend(converted),
+            Err(error) => {
+                failed += 1;
+                reports.push(ConvertReport {
+                    problem: src,
+                    warnings: Vec::new(),
+                    error: Some(format!("{error:#}")),
+                });
+            }
+        }
+    }
+    reports.retain(|report| !report.warnings.is_empty() || report.error.is_some());
+
+    let mut output = serde_json::to_vec_pretty(&reports)?;
+    output.push(b'\n');
+    stdout().write_all(&output)?;
+
+    if failed > 0 {
+        bail!(
+            "Failed to convert {failed} problem(s) in {}",
+            input_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Convert the problem in `src` into `dst`, returning a report for each
+/// converted problem.
+async fn convert_problem(
+    direction: Direction,
+    src: &Path,
+    dst: &Path,
+) -> anyhow::Result<Vec<ConvertReport>> {
+    Ok(match direction {
+        Direction::Import(format) => format
+            .import(src, dst)
+            .await?
+            .into_iter()
+            .map(|converted| ConvertReport {
+                problem: converted.dir,
+                warnings: converted.warnings,
+                error: None,
+            })
+            .collect(),
+        Direction::Export(format) => {
+            let config = load_or_discover(src, DiscoverConfig::default()).await?;
+            vec![ConvertReport {
+                problem: dst.to_path_buf(),
+                warnings: format.export(&config, src, dst).await?,
+                error: None,
+            }]
+        }
+    })
+}